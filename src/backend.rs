@@ -0,0 +1,250 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::logs;
+use crate::screen::ScreenManager;
+use crate::terminal;
+use crate::tmux::TmuxManager;
+
+/// Terminal multiplexer that hosts Claude Code sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Tmux,
+    Screen,
+}
+
+impl std::fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendKind::Tmux => write!(f, "tmux"),
+            BackendKind::Screen => write!(f, "screen"),
+        }
+    }
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "tmux" => Ok(BackendKind::Tmux),
+            "screen" => Ok(BackendKind::Screen),
+            _ => Err(anyhow!("Unknown backend '{}'. Use: tmux, screen", s)),
+        }
+    }
+}
+
+/// Basic information about a running session
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct SessionInfo {
+    pub name: String,
+    pub created: u64,
+    pub windows: u32,
+    pub attached: bool,
+    pub path: PathBuf,
+}
+
+/// Program to run in a new session. It is handed to the multiplexer as an
+/// argument vector rather than a shell string, so arguments need no quoting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LaunchCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Variables set for the program on top of the multiplexer's environment
+    pub env: BTreeMap<String, String>,
+}
+
+impl LaunchCommand {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Program followed by its arguments
+    pub fn argv(&self) -> Vec<String> {
+        std::iter::once(self.program.clone())
+            .chain(self.args.iter().cloned())
+            .collect()
+    }
+}
+
+/// Shell-quoted form, for logs and for backends that only take a command line
+impl std::fmt::Display for LaunchCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let words: Vec<String> = self
+            .env
+            .iter()
+            .map(|(key, value)| format!("{key}={}", shell_quote(value)))
+            .chain(self.argv().iter().map(|arg| shell_quote(arg)))
+            .collect();
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Quote `value` for a POSIX shell, leaving plain words untouched
+pub fn shell_quote(value: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "/._-+:@%=,".contains(c);
+    if !value.is_empty() && value.chars().all(is_safe) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// Keys that cannot be sent as plain text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialKey {
+    Enter,
+    Escape,
+}
+
+impl SpecialKey {
+    /// Key name understood by `tmux send-keys`
+    pub fn tmux_name(&self) -> &'static str {
+        match self {
+            SpecialKey::Enter => "Enter",
+            SpecialKey::Escape => "Escape",
+        }
+    }
+
+    /// Raw bytes the key produces in a terminal
+    pub fn as_bytes(&self) -> &'static str {
+        match self {
+            SpecialKey::Enter => "\r",
+            SpecialKey::Escape => "\x1b",
+        }
+    }
+}
+
+/// Pause between pasting a message and pressing Enter, so the application
+/// sees the submit as a separate keystroke rather than part of the paste
+pub const PASTE_SETTLE_TIME: std::time::Duration = std::time::Duration::from_millis(150);
+
+/// Whether `text` has to be pasted rather than typed: newlines would submit
+/// it early and other control characters act as shortcuts
+pub fn needs_paste(text: &str) -> bool {
+    text.chars().any(char::is_control)
+}
+
+/// Operations the session managers need from a terminal multiplexer
+pub trait TerminalBackend: Send + Sync {
+    fn session_exists(&self, session_name: &str) -> Result<bool>;
+
+    fn list_sessions(&self) -> Result<Vec<String>>;
+
+    fn create_session(
+        &self,
+        session_name: &str,
+        working_dir: Option<&PathBuf>,
+        command: Option<&LaunchCommand>,
+    ) -> Result<()>;
+
+    fn kill_session(&self, session_name: &str) -> Result<()>;
+
+    /// Type `keys` literally; key names such as `Enter` or `C-c` are not interpreted
+    fn send_keys(&self, session_name: &str, keys: &str) -> Result<()>;
+
+    fn send_special_key(&self, session_name: &str, key: SpecialKey) -> Result<()>;
+
+    /// Deliver `keys` exactly, including newlines, then submit it with Enter
+    fn send_keys_with_enter(&self, session_name: &str, keys: &str) -> Result<()>;
+
+    fn capture_pane(&self, session_name: &str, lines: Option<usize>) -> Result<String>;
+
+    /// Start piping everything the session prints into a new log for this run
+    fn enable_session_logging(&self, session_name: &str) -> Result<()>;
+
+    fn attach_session(&self, session_name: &str) -> Result<()>;
+
+    fn get_session_info(&self, session_name: &str) -> Result<SessionInfo>;
+
+    /// Whether the session's command has exited while the session was kept
+    /// open to inspect its output (see [`TerminalBackend::close_on_exit`])
+    fn command_exited(&self, _session_name: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Let the session close when its command exits. Backends that can keep
+    /// a dead session around do so until this is called, so startup failures
+    /// can still be read.
+    fn close_on_exit(&self, _session_name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Height of the session's pane, when the backend can tell
+    fn pane_rows(&self, _session_name: &str) -> Option<u16> {
+        None
+    }
+
+    /// Log of the session's latest run
+    fn get_log_file_path(&self, session_name: &str) -> String {
+        logs::current_run_log(session_name)
+            .unwrap_or_else(|| logs::log_dir().join(format!("{session_name}.log")))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn read_session_log(&self, session_name: &str, lines: Option<usize>) -> Result<String> {
+        let log_file = self.get_log_file_path(session_name);
+
+        if !std::path::Path::new(&log_file).exists() {
+            debug!("Log file does not exist for session: {}", session_name);
+            // Fall back to capturing current pane content
+            return self.capture_pane(session_name, lines);
+        }
+
+        debug!("Reading log file: {}", log_file);
+
+        if let Some(lines) = lines {
+            // Read only the last N lines
+            let output = Command::new("tail")
+                .args(["-n", &lines.to_string(), &log_file])
+                .output()?;
+
+            if !output.status.success() {
+                return Err(anyhow!("Failed to read log file: {}", log_file));
+            }
+
+            Ok(String::from_utf8(output.stdout)?)
+        } else {
+            // Read entire file, rotated archives included
+            let bytes = logs::read_with_archives(std::path::Path::new(&log_file))?;
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        }
+    }
+
+    /// The session log as the terminal rendered it, keeping the last `lines`
+    /// lines. Falls back to the pane content when there is no log.
+    fn render_session_log(&self, session_name: &str, lines: Option<usize>) -> Result<String> {
+        let log_file = self.get_log_file_path(session_name);
+
+        if !std::path::Path::new(&log_file).exists() {
+            debug!("Log file does not exist for session: {}", session_name);
+            return self.capture_pane(session_name, lines);
+        }
+
+        debug!("Replaying log file: {}", log_file);
+
+        let rows = self
+            .pane_rows(session_name)
+            .unwrap_or(terminal::DEFAULT_ROWS);
+        logs::render(std::path::Path::new(&log_file), rows, lines)
+    }
+}
+
+pub fn create_backend(kind: BackendKind) -> Arc<dyn TerminalBackend> {
+    match kind {
+        BackendKind::Tmux => Arc::new(TmuxManager::new()),
+        BackendKind::Screen => Arc::new(ScreenManager::new()),
+    }
+}
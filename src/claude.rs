@@ -1,52 +1,185 @@
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use tracing::{debug, error, info};
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
 
-use crate::tmux::TmuxManager;
+use crate::backend::{LaunchCommand, SpecialKey, TerminalBackend};
+use crate::hooks::{self, HookEvent, HookKind};
+use crate::permissions::{self, PermissionDecision, PermissionPolicy, PermissionPrompt};
 use crate::Config;
 
+/// Why waiting for a Claude Code turn stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitReason {
+    /// The Claude Code Stop hook recorded a completion event
+    StopHook,
+    /// Claude Code is blocked on a permission prompt
+    AwaitingPermission,
+    /// The pane output stopped changing
+    OutputStable,
+    /// The pane output contains a completion or error indicator
+    CompletionIndicator,
+    /// The timeout elapsed before completion was detected
+    Timeout,
+    /// The message was sent without waiting for completion
+    NotWaited,
+}
+
+impl std::fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExitReason::StopHook => write!(f, "stop hook"),
+            ExitReason::AwaitingPermission => write!(f, "awaiting permission"),
+            ExitReason::OutputStable => write!(f, "output stable"),
+            ExitReason::CompletionIndicator => write!(f, "completion indicator"),
+            ExitReason::Timeout => write!(f, "timeout"),
+            ExitReason::NotWaited => write!(f, "not waited"),
+        }
+    }
+}
+
+impl ExitReason {
+    /// Whether Claude finished its turn, as opposed to stopping mid-turn
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            ExitReason::StopHook | ExitReason::OutputStable | ExitReason::CompletionIndicator
+        )
+    }
+}
+
+/// Pane output captured when waiting for a Claude Code turn stopped
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub output: String,
+    pub reason: ExitReason,
+    /// Hook event that ended the wait, if any
+    pub event: Option<HookEvent>,
+    /// Permission dialog the session is blocked on, if any
+    pub permission_prompt: Option<PermissionPrompt>,
+}
+
+/// Outcome of looking for a permission dialog while waiting
+enum PromptCheck {
+    NoPrompt,
+    AutoApproved,
+    Blocked(PermissionPrompt),
+}
+
+/// How often the pane is checked while Claude Code starts up
+const READY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Pane lines included in startup errors
+const STARTUP_ERROR_LINES: usize = 20;
+
+/// Scrollback searched for the output of a CLI that exited during startup
+const STARTUP_SCROLLBACK_LINES: usize = 500;
+
+/// Screens that stop Claude Code at startup until someone answers them
+const STARTUP_BLOCKERS: [(&str, &str); 3] = [
+    (
+        "Do you trust the files in this folder?",
+        "folder trust prompt",
+    ),
+    ("Select login method", "login required"),
+    ("Please run /login", "login required"),
+];
+
+/// How often the hook waiter looks at the pane for permission dialogs
+const PANE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+const SKIP_PERMISSIONS_FLAG: &str = "--dangerously-skip-permissions";
+
+/// Which Claude Code conversation a new session opens
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Conversation {
+    #[default]
+    New,
+    /// The most recent conversation in the working directory
+    Continue,
+    /// A specific conversation, by Claude Code session ID
+    Resume(String),
+}
+
+impl Conversation {
+    /// Claude Code arguments selecting the conversation
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Conversation::New => Vec::new(),
+            Conversation::Continue => vec!["--continue".to_string()],
+            Conversation::Resume(id) => vec!["--resume".to_string(), id.clone()],
+        }
+    }
+}
+
 pub struct ClaudeCodeManager {
-    tmux: TmuxManager,
+    backend: Arc<dyn TerminalBackend>,
     config: Config,
+    policy: PermissionPolicy,
 }
 
 impl ClaudeCodeManager {
-    pub fn new(config: Config) -> Self {
+    pub fn with_backend(config: Config, backend: Arc<dyn TerminalBackend>) -> Self {
+        let policy = PermissionPolicy::load().unwrap_or_else(|e| {
+            warn!("Ignoring permission policy: {}", e);
+            PermissionPolicy::default()
+        });
+
         Self {
-            tmux: TmuxManager::new(),
+            backend,
             config,
+            policy,
         }
     }
 
+    /// Run `command` in a new session, wait for Claude Code to come up and
+    /// send the initial message, if any
     pub fn start_claude_session(
         &self,
         session_name: &str,
         working_dir: Option<&PathBuf>,
-        initial_message: &str,
+        command: &LaunchCommand,
+        initial_message: Option<&str>,
     ) -> Result<()> {
         info!("Starting Claude Code session: {}", session_name);
 
         // Create tmux session with Claude Code
-        let claude_command = if self.config.skip_permissions {
+        if command.args.iter().any(|arg| arg == SKIP_PERMISSIONS_FLAG) {
             info!("WARNING: Starting Claude Code with --dangerously-skip-permissions");
-            "claude-code --dangerously-skip-permissions"
-        } else {
-            "claude-code"
-        };
+        }
 
-        self.tmux
-            .create_session(session_name, working_dir, Some(claude_command))?;
+        // Events left by an earlier session of this name belong to another conversation
+        hooks::clear_events(session_name);
+        self.backend
+            .create_session(session_name, working_dir, Some(command))?;
 
         // Wait for Claude to initialize
         info!("Waiting for Claude Code to initialize...");
-        std::thread::sleep(std::time::Duration::from_secs(5));
+        self.wait_until_ready(session_name, self.config.startup_timeout)?;
 
         // Send the initial message
-        self.send_message_to_claude(session_name, initial_message)?;
+        if let Some(message) = initial_message {
+            self.send_message_to_claude(session_name, message)?;
+        }
 
         Ok(())
     }
 
+    /// The Claude Code command line, built from the configured executable,
+    /// extra arguments and environment
+    pub fn launch_command(&self) -> LaunchCommand {
+        let mut command = LaunchCommand::new(&self.config.claude_binary);
+        if self.config.skip_permissions {
+            command.args.push(SKIP_PERMISSIONS_FLAG.to_string());
+        }
+        command.args.extend(self.config.claude_args.iter().cloned());
+        command.env = self.config.claude_env.clone();
+        command
+    }
+
     pub fn send_message_to_claude(&self, session_name: &str, message: &str) -> Result<()> {
         debug!(
             "Sending message to Claude session {}: {}",
@@ -54,7 +187,7 @@ impl ClaudeCodeManager {
         );
 
         // Send the message and Enter key together to avoid timing issues
-        self.tmux.send_keys_with_enter(session_name, message)?;
+        self.backend.send_keys_with_enter(session_name, message)?;
 
         info!("Message sent to Claude Code session: {}", session_name);
         Ok(())
@@ -63,45 +196,89 @@ impl ClaudeCodeManager {
     pub fn get_claude_output(&self, session_name: &str, lines: Option<usize>) -> Result<String> {
         debug!("Getting Claude output from session: {}", session_name);
 
-        let output = self.tmux.capture_pane(session_name, lines)?;
+        let output = self.backend.capture_pane(session_name, lines)?;
         Ok(output)
     }
 
-    #[allow(dead_code)]
-    pub fn is_claude_ready(&self, session_name: &str) -> Result<bool> {
-        debug!("Checking if Claude is ready in session: {}", session_name);
+    /// Poll the pane until Claude Code shows its input box, failing early if
+    /// the CLI exits or stops at a screen that needs a human
+    pub fn wait_until_ready(&self, session_name: &str, timeout_secs: u64) -> Result<()> {
+        let start_time = std::time::Instant::now();
+        let timeout = std::time::Duration::from_secs(timeout_secs);
+
+        loop {
+            if !self.backend.session_exists(session_name)? {
+                return Err(anyhow!(
+                    "Claude Code exited during startup in session {} (is {} installed and on your PATH?)",
+                    session_name,
+                    self.config.claude_binary
+                ));
+            }
 
-        let output = self.get_claude_output(session_name, Some(10))?;
+            if self.backend.command_exited(session_name)? {
+                // tmux may scroll the last output out of view when the pane dies
+                let output = self
+                    .get_claude_output(session_name, Some(STARTUP_SCROLLBACK_LINES))
+                    .unwrap_or_default();
+                let _ = self.backend.kill_session(session_name);
+                return Err(anyhow!(
+                    "Claude Code exited during startup in session {}:\n{}",
+                    session_name,
+                    last_lines(&output, STARTUP_ERROR_LINES)
+                ));
+            }
 
-        // Look for Claude's prompt or ready indicators
-        // This is a heuristic - you might need to adjust based on Claude Code's actual output
-        let ready_indicators = [
-            "claude-code>",
-            "How can I help you",
-            "What would you like me to help you with",
-            "I'm ready to help",
-        ];
+            let output = self.get_claude_output(session_name, None)?;
 
-        let is_ready = ready_indicators
-            .iter()
-            .any(|indicator| output.to_lowercase().contains(&indicator.to_lowercase()));
+            if let Some(blocker) = startup_blocker(&output) {
+                let _ = self.backend.close_on_exit(session_name);
+                return Err(anyhow!(
+                    "Claude Code in session {} is waiting for input before it can start ({}). Attach with 'claude-code-manager attach {}' to answer it",
+                    session_name,
+                    blocker,
+                    session_name
+                ));
+            }
 
-        debug!("Claude ready status for {}: {}", session_name, is_ready);
-        Ok(is_ready)
+            if looks_ready(&output) {
+                info!(
+                    "Claude Code ready in session {} after {:.1}s",
+                    session_name,
+                    start_time.elapsed().as_secs_f64()
+                );
+                return self.backend.close_on_exit(session_name);
+            }
+
+            if start_time.elapsed() > timeout {
+                let _ = self.backend.close_on_exit(session_name);
+                return Err(anyhow!(
+                    "Claude Code did not become ready within {}s in session {}. Last output:\n{}",
+                    timeout_secs,
+                    session_name,
+                    last_lines(&output, STARTUP_ERROR_LINES)
+                ));
+            }
+
+            std::thread::sleep(READY_POLL_INTERVAL);
+        }
     }
 
+    /// Wait for the turn that started at `since`, or for the next one to end
+    /// when `None`. Hook events recorded after `since` are kept, so a turn
+    /// that ended before the wait began is still seen.
     pub fn wait_for_claude_completion(
         &self,
         session_name: &str,
         timeout_secs: u64,
-    ) -> Result<String> {
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Completion> {
         info!(
             "Waiting for Claude completion in session: {} (timeout: {}s)",
             session_name, timeout_secs
         );
 
         // Try hook-based completion detection first
-        if let Ok(result) = self.wait_for_completion_hook(session_name, timeout_secs) {
+        if let Ok(result) = self.wait_for_completion_hook(session_name, timeout_secs, since) {
             return Ok(result);
         }
 
@@ -111,47 +288,152 @@ impl ClaudeCodeManager {
         self.wait_for_completion_heuristic(session_name, timeout_secs)
     }
 
-    fn wait_for_completion_hook(&self, session_name: &str, timeout_secs: u64) -> Result<String> {
+    fn wait_for_completion_hook(
+        &self,
+        session_name: &str,
+        timeout_secs: u64,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Completion> {
         let start_time = std::time::Instant::now();
         let timeout = std::time::Duration::from_secs(timeout_secs);
         let check_interval = std::time::Duration::from_millis(500); // Check more frequently
 
-        let completion_file = format!("/tmp/claude-code-manager/{session_name}.done");
-
-        // Remove any existing completion file to start fresh
-        let _ = std::fs::remove_file(&completion_file);
+        match since {
+            // Skip the events of earlier turns below
+            Some(_) => {
+                let _ = std::fs::remove_file(hooks::completion_marker_path(session_name));
+            }
+            // Remove any earlier events to start fresh
+            None => hooks::clear_events(session_name),
+        }
 
-        info!("Monitoring completion file: {}", completion_file);
+        info!(
+            "Monitoring hook events: {}",
+            hooks::events_path(session_name).display()
+        );
 
+        let mut seen = 0;
+        let mut auto_approved = 0;
+        let mut last_pane_check = std::time::Instant::now();
         loop {
             if start_time.elapsed() > timeout {
                 return Err(anyhow!("Timeout waiting for Claude completion"));
             }
 
-            // Check if completion file exists
-            if std::path::Path::new(&completion_file).exists() {
-                info!("Completion detected via hook file: {}", completion_file);
-
-                // Give Claude a moment to finish writing output after the hook fires
-                std::thread::sleep(std::time::Duration::from_millis(500));
-
-                let final_output = self.get_claude_output(session_name, None)?;
+            let events = hooks::read_events(session_name);
+            let new_events = events
+                .iter()
+                .skip(seen)
+                .filter(|event| since.is_none_or(|since| event.timestamp >= since));
+            for event in new_events {
+                match event.hook {
+                    HookKind::Stop => {
+                        info!(
+                            "Completion detected via Stop hook in session: {}",
+                            session_name
+                        );
+                        return self.hook_completion(
+                            session_name,
+                            ExitReason::StopHook,
+                            Some(event),
+                            None,
+                        );
+                    }
+                    HookKind::Notification if event.is_permission_request() => {
+                        let output = self.get_claude_output(session_name, None)?;
+                        match self.check_permission_prompt(session_name, &output)? {
+                            PromptCheck::AutoApproved => auto_approved += 1,
+                            // The dialog was already auto-approved from the pane
+                            PromptCheck::NoPrompt if auto_approved > 0 => {
+                                debug!("Permission prompt in {} already answered", session_name)
+                            }
+                            PromptCheck::NoPrompt => {
+                                info!(
+                                    "Session {} is waiting for permission: {}",
+                                    session_name,
+                                    event.message.as_deref().unwrap_or_default()
+                                );
+                                return self.hook_completion(
+                                    session_name,
+                                    ExitReason::AwaitingPermission,
+                                    Some(event),
+                                    None,
+                                );
+                            }
+                            PromptCheck::Blocked(prompt) => {
+                                return self.hook_completion(
+                                    session_name,
+                                    ExitReason::AwaitingPermission,
+                                    Some(event),
+                                    Some(prompt),
+                                );
+                            }
+                        }
+                    }
+                    _ => debug!("Ignoring {} event in session {}", event.hook, session_name),
+                }
+            }
+            seen = events.len();
 
-                // Clean up the completion file
-                let _ = std::fs::remove_file(&completion_file);
+            // Hooks installed by hand from older READMEs only touch a marker file
+            if hooks::completion_marker_path(session_name).exists() {
+                info!(
+                    "Completion detected via legacy marker file in session: {}",
+                    session_name
+                );
+                return self.hook_completion(session_name, ExitReason::StopHook, None, None);
+            }
 
-                return Ok(final_output);
+            // Catch permission dialogs even when the Notification hook is not installed
+            if last_pane_check.elapsed() >= PANE_CHECK_INTERVAL {
+                last_pane_check = std::time::Instant::now();
+                let output = self.get_claude_output(session_name, None)?;
+                match self.check_permission_prompt(session_name, &output)? {
+                    PromptCheck::NoPrompt => {}
+                    PromptCheck::AutoApproved => auto_approved += 1,
+                    PromptCheck::Blocked(prompt) => {
+                        return self.hook_completion(
+                            session_name,
+                            ExitReason::AwaitingPermission,
+                            None,
+                            Some(prompt),
+                        );
+                    }
+                }
             }
 
             std::thread::sleep(check_interval);
         }
     }
 
+    fn hook_completion(
+        &self,
+        session_name: &str,
+        reason: ExitReason,
+        event: Option<&HookEvent>,
+        permission_prompt: Option<PermissionPrompt>,
+    ) -> Result<Completion> {
+        // Give Claude a moment to finish writing output after the hook fires
+        std::thread::sleep(std::time::Duration::from_millis(500));
+
+        let final_output = self.get_claude_output(session_name, None)?;
+
+        // Clean up so the next wait only sees new events
+        hooks::clear_events(session_name);
+
+        Ok(Completion {
+            output: final_output,
+            reason,
+            event: event.cloned(),
+            permission_prompt,
+        })
+    }
+
     fn wait_for_completion_heuristic(
         &self,
         session_name: &str,
         timeout_secs: u64,
-    ) -> Result<String> {
+    ) -> Result<Completion> {
         let start_time = std::time::Instant::now();
         let timeout = std::time::Duration::from_secs(timeout_secs);
         let check_interval = std::time::Duration::from_secs(3);
@@ -163,16 +445,44 @@ impl ClaudeCodeManager {
         loop {
             if start_time.elapsed() > timeout {
                 error!("Timeout waiting for Claude completion");
-                return Err(anyhow!("Timeout waiting for Claude completion"));
+                return Ok(Completion {
+                    output: last_output,
+                    reason: ExitReason::Timeout,
+                    event: None,
+                    permission_prompt: None,
+                });
             }
 
             let current_output = self.get_claude_output(session_name, None)?;
 
+            // A dialog waiting for an answer also looks like stable output
+            match self.check_permission_prompt(session_name, &current_output)? {
+                PromptCheck::NoPrompt => {}
+                PromptCheck::AutoApproved => {
+                    stable_count = 0;
+                    std::thread::sleep(check_interval);
+                    continue;
+                }
+                PromptCheck::Blocked(prompt) => {
+                    return Ok(Completion {
+                        output: current_output,
+                        reason: ExitReason::AwaitingPermission,
+                        event: None,
+                        permission_prompt: Some(prompt),
+                    });
+                }
+            }
+
             if current_output == last_output {
                 stable_count += 1;
                 if stable_count >= stability_threshold {
                     info!("Claude output appears stable, assuming completion");
-                    return Ok(current_output);
+                    return Ok(Completion {
+                        output: current_output,
+                        reason: ExitReason::OutputStable,
+                        event: None,
+                        permission_prompt: None,
+                    });
                 }
             } else {
                 stable_count = 0;
@@ -182,13 +492,100 @@ impl ClaudeCodeManager {
             // Additional heuristics for completion detection
             if self.looks_like_completion(&last_output) {
                 info!("Claude completion detected based on output analysis");
-                return Ok(last_output);
+                return Ok(Completion {
+                    output: last_output,
+                    reason: ExitReason::CompletionIndicator,
+                    event: None,
+                    permission_prompt: None,
+                });
             }
 
             std::thread::sleep(check_interval);
         }
     }
 
+    /// Look for a permission dialog in `output`, approving it if the policy allows
+    fn check_permission_prompt(&self, session_name: &str, output: &str) -> Result<PromptCheck> {
+        let Some(prompt) = permissions::detect_permission_prompt(output) else {
+            return Ok(PromptCheck::NoPrompt);
+        };
+
+        let Some(pattern) = self.policy.matching_pattern(&prompt) else {
+            info!(
+                "Session {} is waiting for permission to use {}",
+                session_name, prompt.tool
+            );
+            return Ok(PromptCheck::Blocked(prompt));
+        };
+
+        info!(
+            "Auto-approving {} in session {} (policy pattern: {})",
+            prompt.tool, session_name, pattern
+        );
+        self.send_permission_keys(session_name, &prompt, PermissionDecision::Approve)?;
+
+        // Let the dialog close before the pane is looked at again
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        Ok(PromptCheck::AutoApproved)
+    }
+
+    /// The permission dialog currently shown in the session, if any
+    pub fn pending_permission_prompt(
+        &self,
+        session_name: &str,
+    ) -> Result<Option<PermissionPrompt>> {
+        let output = self.get_claude_output(session_name, None)?;
+        Ok(permissions::detect_permission_prompt(&output))
+    }
+
+    /// Answer the pending permission dialog, returning the prompt that was answered
+    pub fn answer_permission_prompt(
+        &self,
+        session_name: &str,
+        decision: PermissionDecision,
+    ) -> Result<PermissionPrompt> {
+        let prompt = self
+            .pending_permission_prompt(session_name)?
+            .ok_or_else(|| {
+                anyhow!(
+                    "No permission prompt is pending in session: {}",
+                    session_name
+                )
+            })?;
+
+        self.send_permission_keys(session_name, &prompt, decision)?;
+        info!(
+            "Permission for {} {} in session {}",
+            prompt.tool, decision, session_name
+        );
+        Ok(prompt)
+    }
+
+    fn send_permission_keys(
+        &self,
+        session_name: &str,
+        prompt: &PermissionPrompt,
+        decision: PermissionDecision,
+    ) -> Result<()> {
+        match decision {
+            // Options are picked by their number
+            PermissionDecision::Approve => self.backend.send_keys(session_name, "1"),
+            PermissionDecision::ApproveAlways => {
+                if !prompt.has_always_option() {
+                    return Err(anyhow!(
+                        "The {} prompt in session {} has no \"don't ask again\" option",
+                        prompt.tool,
+                        session_name
+                    ));
+                }
+                self.backend.send_keys(session_name, "2")
+            }
+            PermissionDecision::Deny => self
+                .backend
+                .send_special_key(session_name, SpecialKey::Escape),
+        }
+    }
+
     fn looks_like_completion(&self, output: &str) -> bool {
         // Check if Claude is still actively working
         let still_working_indicators = [
@@ -235,13 +632,13 @@ impl ClaudeCodeManager {
 
     pub fn kill_claude_session(&self, session_name: &str) -> Result<()> {
         info!("Killing Claude Code session: {}", session_name);
-        self.tmux.kill_session(session_name)
+        self.backend.kill_session(session_name)
     }
 
     pub fn list_claude_sessions(&self) -> Result<Vec<String>> {
         debug!("Listing Claude Code sessions");
 
-        let all_sessions = self.tmux.list_sessions()?;
+        let all_sessions = self.backend.list_sessions()?;
 
         // Filter for sessions that are likely Claude Code sessions
         // This is a heuristic - you might want to adjust based on your naming convention
@@ -273,6 +670,472 @@ impl ClaudeCodeManager {
 
     pub fn attach_to_session(&self, session_name: &str) -> Result<()> {
         info!("Attaching to Claude Code session: {}", session_name);
-        self.tmux.attach_session(session_name)
+        self.backend.attach_session(session_name)
+    }
+}
+
+/// Bullet Claude Code prints in front of assistant messages and tool calls
+const MESSAGE_MARKERS: [char; 2] = ['⏺', '●'];
+
+/// Lines at the bottom of the pane where Claude Code shows its status
+const BUSY_STATUS_LINES: usize = 8;
+
+/// Glyphs Claude Code cycles through in its "Thinking…" status line
+const SPINNER_GLYPHS: [char; 7] = ['✻', '✶', '✳', '✢', '✽', '·', '*'];
+
+/// Whether the pane shows Claude Code's input box, i.e. it accepts a message
+fn looks_ready(output: &str) -> bool {
+    output.contains("? for shortcuts")
+        || output.lines().any(|line| {
+            let line = line.trim();
+            line.starts_with('│') && line.trim_start_matches('│').trim_start().starts_with('>')
+        })
+}
+
+/// Whether the pane shows Claude Code working on a turn
+pub fn looks_busy(output: &str) -> bool {
+    last_lines(output, BUSY_STATUS_LINES)
+        .lines()
+        .any(|line| line.contains("esc to interrupt") || is_spinner_line(line.trim()))
+}
+
+fn startup_blocker(output: &str) -> Option<&'static str> {
+    STARTUP_BLOCKERS
+        .iter()
+        .find(|(text, _)| output.contains(text))
+        .map(|(_, description)| *description)
+}
+
+/// The last `count` non-blank lines of `output`
+fn last_lines(output: &str, count: usize) -> String {
+    let lines: Vec<&str> = output.lines().filter(|l| !l.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(count)..].join("\n")
+}
+
+/// Extract Claude's reply to `message` from a captured pane.
+///
+/// Finds the last echo of the prompt, then keeps only the assistant's text
+/// blocks up to the input box or the next prompt, dropping the banner, tool calls and their
+/// results. Returns `None` if no assistant text could be found.
+pub fn extract_response(output: &str, message: &str) -> Option<String> {
+    let lines: Vec<&str> = output.lines().collect();
+
+    let start = find_prompt_echo(&lines, message).map_or(0, |index| index + 1);
+    // The reply ends at the input box or at the next prompt
+    let end = lines[start..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('╭') || is_prompt_echo(line))
+        .map_or(lines.len(), |offset| start + offset);
+
+    let mut blocks: Vec<Vec<String>> = Vec::new();
+    let mut in_text_block = false;
+
+    for line in &lines[start..end] {
+        let trimmed = line.trim_start();
+
+        if let Some(rest) = trimmed.strip_prefix(MESSAGE_MARKERS) {
+            let text = rest.trim_start();
+            in_text_block = !looks_like_tool_call(text);
+            if in_text_block {
+                blocks.push(vec![text.trim_end().to_string()]);
+            }
+        } else if trimmed.starts_with('⎿') || is_spinner_line(trimmed) {
+            in_text_block = false;
+        } else if in_text_block {
+            if let Some(block) = blocks.last_mut() {
+                // Continuation lines are indented to line up with the text after the marker
+                let text = line.strip_prefix("  ").unwrap_or(line);
+                block.push(text.trim_end().to_string());
+            }
+        }
+    }
+
+    let response = blocks
+        .iter()
+        .map(|block| block.join("\n").trim_end().to_string())
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    if response.is_empty() {
+        None
+    } else {
+        Some(response)
+    }
+}
+
+/// Index of the last line echoing the prompt, preferring one that matches `message`
+fn find_prompt_echo(lines: &[&str], message: &str) -> Option<usize> {
+    let echoes: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| is_prompt_echo(line))
+        .map(|(index, _)| index)
+        .collect();
+
+    let first_line = message.lines().next().unwrap_or("").trim();
+    let needle: String = first_line.chars().take(40).collect();
+
+    echoes
+        .iter()
+        .rev()
+        .find(|&&index| {
+            let echoed = lines[index].trim_start_matches('>').trim();
+            !needle.is_empty() && echoed.starts_with(needle.as_str())
+        })
+        .or_else(|| echoes.last())
+        .copied()
+}
+
+fn is_prompt_echo(line: &str) -> bool {
+    line.starts_with("> ") || line.trim_end() == ">"
+}
+
+/// Tool calls render as `Name(arguments)`, e.g. `Bash(cargo test)` or `Update(src/main.rs)`
+fn looks_like_tool_call(text: &str) -> bool {
+    match text.find('(') {
+        Some(paren) if paren > 0 => text[..paren]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':' || c == '-'),
+        _ => false,
+    }
+}
+
+fn is_spinner_line(trimmed: &str) -> bool {
+    trimmed.starts_with(SPINNER_GLYPHS) && trimmed.contains('…')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBackend;
+
+    const PANE: &str = "\
+╭──────────────────────────╮
+│ ✻ Welcome to Claude Code! │
+╰──────────────────────────╯
+
+> What is 2 + 2?
+
+⏺ 4
+
+> Create hello.py
+  and run it
+
+⏺ I'll create the script.
+
+⏺ Write(hello.py)
+  ⎿  Wrote 1 lines to hello.py
+     print(\"Hello\")
+
+⏺ Bash(python hello.py)
+  ⎿  Hello
+
+⏺ Created hello.py, which prints
+  \"Hello\".
+
+╭──────────────────────────╮
+│ >                        │
+╰──────────────────────────╯
+  ? for shortcuts
+";
+
+    const PERMISSION_PANE: &str = "\
+> Clean up the build
+
+⏺ Bash(rm -rf build)
+
+╭──────────────────────────────────────────────────────╮
+│ Bash command                                         │
+│                                                      │
+│   rm -rf build                                       │
+│                                                      │
+│ Do you want to proceed?                              │
+│ ❯ 1. Yes                                             │
+│   2. Yes, and don't ask again for rm commands        │
+│   3. No, and tell Claude what to do differently (esc) │
+╰──────────────────────────────────────────────────────╯
+";
+
+    fn manager(backend: FakeBackend) -> (ClaudeCodeManager, Arc<FakeBackend>) {
+        let backend = Arc::new(backend);
+        let mut manager = ClaudeCodeManager::with_backend(Config::default(), backend.clone());
+        // Keep tests independent of the policy file in the real HOME
+        manager.policy = PermissionPolicy::default();
+        (manager, backend)
+    }
+
+    fn unique_session(label: &str) -> String {
+        format!("claude-unit-{}-{label}", std::process::id())
+    }
+
+    #[test]
+    fn launch_command_combines_binary_flags_and_environment() {
+        let config = Config {
+            skip_permissions: true,
+            claude_binary: "claude".to_string(),
+            claude_args: vec!["--model".to_string(), "opus".to_string()],
+            claude_env: [("ANTHROPIC_LOG".to_string(), "debug".to_string())].into(),
+            ..Config::default()
+        };
+        let manager = ClaudeCodeManager::with_backend(config, Arc::new(FakeBackend::new()));
+
+        let command = manager.launch_command();
+        assert_eq!(
+            command.argv(),
+            vec![
+                "claude",
+                "--dangerously-skip-permissions",
+                "--model",
+                "opus"
+            ]
+        );
+        assert_eq!(command.env["ANTHROPIC_LOG"], "debug");
+        assert_eq!(
+            command.to_string(),
+            "ANTHROPIC_LOG=debug claude --dangerously-skip-permissions --model opus"
+        );
+    }
+
+    #[test]
+    fn extract_response_returns_only_the_latest_reply() {
+        let response = extract_response(PANE, "Create hello.py\nand run it").unwrap();
+        assert_eq!(
+            response,
+            "I'll create the script.\n\nCreated hello.py, which prints\n\"Hello\"."
+        );
+    }
+
+    #[test]
+    fn extract_response_prefers_the_matching_prompt() {
+        assert_eq!(
+            extract_response(PANE, "What is 2 + 2?").as_deref(),
+            Some("4")
+        );
+    }
+
+    #[test]
+    fn extract_response_without_assistant_text_is_none() {
+        let pane = "> Run the tests\n\n⏺ Bash(cargo test)\n  ⎿  ok\n\n╭──╮\n│ > │\n╰──╯\n";
+        assert_eq!(extract_response(pane, "Run the tests"), None);
+    }
+
+    #[test]
+    fn working_indicators_are_not_completion() {
+        let (manager, _) = manager(FakeBackend::new());
+        assert!(!manager.looks_like_completion("✓ step one\n✻ Thinking… (esc to interrupt)"));
+        assert!(manager.looks_like_completion("⏺ Done! All tests pass"));
+        assert!(!manager.looks_like_completion("⏺ Here is the answer"));
+    }
+
+    #[test]
+    fn only_a_status_line_at_the_bottom_means_busy() {
+        assert!(!looks_busy(PANE));
+        assert!(looks_busy(
+            "> Create hello.py\n\n✻ Thinking… (esc to interrupt)\n"
+        ));
+        assert!(looks_busy("> Create hello.py\n\n✢ Pondering…\n"));
+
+        // A spinner line scrolled far up is an old one
+        let old_status = format!("✻ Thinking…\n{}", "⏺ line\n".repeat(20));
+        assert!(!looks_busy(&old_status));
+    }
+
+    #[test]
+    fn start_sends_the_first_message_once_ready() {
+        let (manager, backend) = manager(FakeBackend::new());
+
+        let start = std::time::Instant::now();
+        let command = manager.launch_command();
+        manager
+            .start_claude_session("claude-start", None, &command, Some("hello"))
+            .unwrap();
+
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        assert_eq!(backend.sent_to("claude-start"), vec!["hello"]);
+    }
+
+    #[test]
+    fn wait_until_ready_reports_blockers_and_timeouts() {
+        let (manager, backend) = manager(FakeBackend::new());
+
+        backend.set_pane(
+            "claude-trust",
+            "╭────╮\n│ Do you trust the files in this folder? │\n│ ❯ 1. Yes, proceed │\n╰────╯\n",
+        );
+        let error = manager.wait_until_ready("claude-trust", 5).unwrap_err();
+        assert!(error.to_string().contains("folder trust prompt"));
+
+        backend.set_pane("claude-loading", "Loading…\n");
+        let error = manager.wait_until_ready("claude-loading", 1).unwrap_err();
+        assert!(error.to_string().contains("did not become ready within 1s"));
+        assert!(error.to_string().contains("Loading…"));
+
+        let error = manager.wait_until_ready("claude-missing", 5).unwrap_err();
+        assert!(error.to_string().contains("exited during startup"));
+    }
+
+    #[test]
+    fn send_message_types_into_the_session() {
+        let (manager, backend) = manager(FakeBackend::new());
+        backend.create_session("claude-send", None, None).unwrap();
+
+        manager
+            .send_message_to_claude("claude-send", "hello")
+            .unwrap();
+
+        assert_eq!(backend.sent_to("claude-send"), vec!["hello"]);
+        let output = manager.get_claude_output("claude-send", None).unwrap();
+        assert_eq!(
+            extract_response(&output, "hello").as_deref(),
+            Some("Echo: hello")
+        );
+    }
+
+    #[test]
+    fn wait_for_completion_hook_accepts_legacy_marker() {
+        let session = unique_session("hook");
+        let (manager, backend) = manager(FakeBackend::new());
+        backend.create_session(&session, None, None).unwrap();
+        manager.send_message_to_claude(&session, "ping").unwrap();
+
+        let marker = format!("/tmp/claude-code-manager/{session}.done");
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            std::fs::create_dir_all("/tmp/claude-code-manager").unwrap();
+            std::fs::write(&marker, "done").unwrap();
+        });
+
+        let completion = manager
+            .wait_for_completion_hook(&session, 10, None)
+            .unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(completion.reason, ExitReason::StopHook);
+        assert!(completion.output.contains("⏺ Echo: ping"));
+    }
+
+    #[test]
+    fn wait_for_completion_hook_reports_permission_prompts() {
+        let session = unique_session("permission");
+        let (manager, backend) = manager(FakeBackend::new());
+        backend.create_session(&session, None, None).unwrap();
+
+        let writer_session = session.clone();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            for (kind, payload) in [
+                (HookKind::SubagentStop, serde_json::json!({})),
+                (
+                    HookKind::Notification,
+                    serde_json::json!({ "message": "Claude needs your permission to use Bash" }),
+                ),
+            ] {
+                hooks::append_event(&HookEvent::from_payload(&writer_session, kind, &payload))
+                    .unwrap();
+            }
+        });
+
+        let completion = manager
+            .wait_for_completion_hook(&session, 10, None)
+            .unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(completion.reason, ExitReason::AwaitingPermission);
+        assert!(!completion.reason.is_finished());
+        assert_eq!(
+            completion.event.unwrap().message.as_deref(),
+            Some("Claude needs your permission to use Bash")
+        );
+        assert!(hooks::read_events(&session).is_empty());
+    }
+
+    #[test]
+    fn wait_for_completion_heuristic_stops_at_permission_prompt() {
+        let (manager, backend) = manager(FakeBackend::new());
+        backend.set_pane("claude-blocked", PERMISSION_PANE);
+
+        let completion = manager
+            .wait_for_completion_heuristic("claude-blocked", 10)
+            .unwrap();
+
+        assert_eq!(completion.reason, ExitReason::AwaitingPermission);
+        let prompt = completion.permission_prompt.unwrap();
+        assert_eq!(prompt.tool, "Bash");
+        assert_eq!(prompt.detail.as_deref(), Some("rm -rf build"));
+        assert!(backend.sent_to("claude-blocked").is_empty());
+    }
+
+    #[test]
+    fn policy_auto_approves_matching_prompts() {
+        let (mut manager, backend) = manager(FakeBackend::new());
+        backend.set_pane("claude-policy", PERMISSION_PANE);
+
+        manager.policy = PermissionPolicy {
+            allow: vec!["Bash(cargo *)".to_string()],
+        };
+        assert!(matches!(
+            manager
+                .check_permission_prompt("claude-policy", PERMISSION_PANE)
+                .unwrap(),
+            PromptCheck::Blocked(_)
+        ));
+
+        manager.policy = PermissionPolicy {
+            allow: vec!["Bash(rm -rf build)".to_string()],
+        };
+        assert!(matches!(
+            manager
+                .check_permission_prompt("claude-policy", PERMISSION_PANE)
+                .unwrap(),
+            PromptCheck::AutoApproved
+        ));
+        assert_eq!(backend.sent_to("claude-policy"), vec!["1"]);
+    }
+
+    #[test]
+    fn answering_permission_prompts_sends_the_right_keys() {
+        let (manager, backend) = manager(FakeBackend::new());
+        backend.set_pane("claude-answer", PERMISSION_PANE);
+
+        manager
+            .answer_permission_prompt("claude-answer", PermissionDecision::ApproveAlways)
+            .unwrap();
+        manager
+            .answer_permission_prompt("claude-answer", PermissionDecision::Deny)
+            .unwrap();
+        assert_eq!(backend.sent_to("claude-answer"), vec!["2", "<Escape>"]);
+
+        backend.create_session("claude-idle", None, None).unwrap();
+        assert!(manager
+            .answer_permission_prompt("claude-idle", PermissionDecision::Approve)
+            .is_err());
+    }
+
+    #[test]
+    fn wait_for_completion_hook_times_out_without_marker() {
+        let session = unique_session("no-hook");
+        let (manager, backend) = manager(FakeBackend::new());
+        backend.create_session(&session, None, None).unwrap();
+
+        assert!(manager.wait_for_completion_hook(&session, 1, None).is_err());
+    }
+
+    #[test]
+    fn wait_for_completion_heuristic_detects_completion_indicator() {
+        let (manager, backend) = manager(FakeBackend::with_reply("Done! {prompt}"));
+        backend
+            .create_session("claude-heuristic", None, None)
+            .unwrap();
+        manager
+            .send_message_to_claude("claude-heuristic", "build it")
+            .unwrap();
+
+        let completion = manager
+            .wait_for_completion_heuristic("claude-heuristic", 10)
+            .unwrap();
+
+        assert_eq!(completion.reason, ExitReason::CompletionIndicator);
+        assert!(completion.output.contains("Done! build it"));
     }
 }
@@ -0,0 +1,686 @@
+//! Background daemon supervising sessions.
+//!
+//! `daemon start` runs `daemon run` detached from the terminal. The daemon
+//! keeps a `SessionWatcher` on every Claude Code session, so completions and
+//! state changes are noticed even when no command is waiting for them, and
+//! appends what it sees to `~/.claude-code-manager/events.jsonl`. It notices
+//! when Claude Code exits inside a session, resumes message queues once their
+//! session is idle again and cleans up the hook files of ended sessions.
+//!
+//! The CLI talks to it over a Unix socket, one JSON request and one JSON
+//! response per connection, and carries on without it when it is not running.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::hooks::{self, HookKind};
+use crate::logs;
+use crate::queue::{self, MessageQueue};
+use crate::registry::SessionRegistry;
+use crate::session::SessionManager;
+use crate::stream::{Activity, SessionWatcher, StreamEvent};
+use crate::Config;
+
+/// How often the daemon looks at the sessions
+const TICK: Duration = Duration::from_secs(1);
+
+/// Ticks between rotating logs and cleaning up after ended sessions
+const MAINTENANCE_TICKS: u64 = 60;
+
+/// How long the CLI waits for the daemon to answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn state_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".claude-code-manager")
+}
+
+pub fn socket_path() -> PathBuf {
+    state_dir().join("daemon.sock")
+}
+
+/// Where `daemon start` sends the daemon's own log
+pub fn log_path() -> PathBuf {
+    state_dir().join("daemon.log")
+}
+
+/// JSON lines file of everything the daemon saw happen
+pub fn events_path() -> PathBuf {
+    state_dir().join("events.jsonl")
+}
+
+/// Something that happened to a session, as persisted by the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonEvent {
+    pub timestamp: DateTime<Utc>,
+    pub session: String,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    /// The daemon started watching the session
+    Discovered,
+    /// The session changed what it is doing
+    State {
+        state: Activity,
+        /// Tool a permission prompt asks about
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tool: Option<String>,
+    },
+    /// A Claude Code hook fired
+    Hook {
+        hook: HookKind,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stop_reason: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    /// Claude finished its turn
+    Completed { response: Option<String> },
+    /// Claude Code exited on its own, e.g. it crashed or was told to `/exit`
+    Exited,
+    /// The session was killed with `kill`
+    Ended,
+}
+
+impl std::fmt::Display for DaemonEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let time = self.timestamp.format("%Y-%m-%d %H:%M:%S");
+        write!(f, "{time} {}: ", self.session)?;
+        match &self.kind {
+            EventKind::Discovered => write!(f, "watching"),
+            EventKind::State {
+                state,
+                tool: Some(tool),
+            } => write!(f, "{state} ({tool})"),
+            EventKind::State { state, tool: None } => write!(f, "{state}"),
+            EventKind::Hook {
+                hook, stop_reason, ..
+            } => match stop_reason {
+                Some(reason) => write!(f, "{hook} hook ({reason})"),
+                None => write!(f, "{hook} hook"),
+            },
+            EventKind::Completed { .. } => write!(f, "completed"),
+            EventKind::Exited => write!(f, "Claude Code exited"),
+            EventKind::Ended => write!(f, "ended"),
+        }
+    }
+}
+
+/// A session as the daemon currently sees it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackedSession {
+    pub session: String,
+    pub state: Activity,
+    /// When the session entered its current state
+    pub since: DateTime<Utc>,
+    pub last_completed_at: Option<DateTime<Utc>>,
+}
+
+/// Answer to a `status` request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+    pub socket: PathBuf,
+    pub events: PathBuf,
+    pub sessions: Vec<TrackedSession>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    /// Send the session's queued messages, resuming whenever it is idle again
+    RunQueue {
+        session: String,
+    },
+    Shutdown,
+}
+
+/// Send a request to the daemon. Returns `None` when it is not running.
+#[cfg(unix)]
+pub fn request(request: &Request) -> Result<Option<Value>> {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = match UnixStream::connect(socket_path()) {
+        Ok(stream) => stream,
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(e) => return Err(anyhow!("Failed to connect to the daemon: {}", e)),
+    };
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.write_all(format!("{}\n", serde_json::to_string(request)?).as_bytes())?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|e| anyhow!("The daemon did not answer: {}", e))?;
+    let response: Value = serde_json::from_str(&line)
+        .map_err(|e| anyhow!("Invalid response from the daemon: {}", e))?;
+    if let Some(error) = response["error"].as_str() {
+        return Err(anyhow!("{}", error));
+    }
+    Ok(Some(response))
+}
+
+#[cfg(not(unix))]
+pub fn request(_request: &Request) -> Result<Option<Value>> {
+    Ok(None)
+}
+
+/// The running daemon's status, or `None` when it is not running
+pub fn status() -> Result<Option<DaemonStatus>> {
+    request(&Request::Status)?
+        .map(|response| Ok(serde_json::from_value(response)?))
+        .transpose()
+}
+
+/// The daemon's view of one session, when the daemon is running and tracks it
+pub fn tracked_session(session_name: &str) -> Option<TrackedSession> {
+    match status() {
+        Ok(status) => status?
+            .sessions
+            .into_iter()
+            .find(|tracked| tracked.session == session_name),
+        Err(e) => {
+            warn!(
+                "Failed to ask the daemon about session {}: {}",
+                session_name, e
+            );
+            None
+        }
+    }
+}
+
+/// Persisted events, oldest first, optionally of one session only
+pub fn read_events(session_name: Option<&str>) -> Vec<DaemonEvent> {
+    let Ok(content) = std::fs::read_to_string(events_path()) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<DaemonEvent>(line) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                warn!("Skipping malformed daemon event: {}", e);
+                None
+            }
+        })
+        .filter(|event| session_name.is_none_or(|name| event.session == name))
+        .collect()
+}
+
+/// State shared by the supervisor loop and the control socket
+struct Daemon {
+    config: Config,
+    started_at: DateTime<Utc>,
+    sessions: Mutex<BTreeMap<String, TrackedSession>>,
+    /// Sessions whose queue the daemon was asked to run
+    queues: Mutex<BTreeSet<String>>,
+    /// Queues being sent right now
+    running_queues: Mutex<BTreeSet<String>>,
+    stopping: AtomicBool,
+    runtime: tokio::runtime::Handle,
+}
+
+impl Daemon {
+    fn status(&self) -> DaemonStatus {
+        DaemonStatus {
+            pid: std::process::id(),
+            started_at: self.started_at,
+            socket: socket_path(),
+            events: events_path(),
+            sessions: self.sessions.lock().unwrap().values().cloned().collect(),
+        }
+    }
+
+    /// Send the session's queue on a blocking thread, unless it already is
+    fn run_queue(self: &Arc<Self>, session_name: &str) {
+        self.queues.lock().unwrap().insert(session_name.to_string());
+        if !self
+            .running_queues
+            .lock()
+            .unwrap()
+            .insert(session_name.to_string())
+        {
+            return;
+        }
+
+        info!("Sending the queue of session {}", session_name);
+        let daemon = self.clone();
+        let session_name = session_name.to_string();
+        self.runtime.spawn_blocking(move || {
+            let mut manager = SessionManager::new(daemon.config.clone());
+            match tokio::runtime::Handle::current().block_on(manager.run_queue(&session_name)) {
+                Ok(sent) => info!(
+                    "Sent {} queued message(s) to session {}",
+                    sent, session_name
+                ),
+                Err(e) => warn!("Queue of session {} failed: {:#}", session_name, e),
+            }
+            daemon.running_queues.lock().unwrap().remove(&session_name);
+        });
+    }
+
+    fn handle(self: &Arc<Self>, request: Request) -> Value {
+        debug!("Daemon request: {:?}", request);
+        match request {
+            Request::Status => serde_json::to_value(self.status()).unwrap_or_default(),
+            Request::RunQueue { session } => {
+                self.run_queue(&session);
+                json!({ "ok": true })
+            }
+            Request::Shutdown => {
+                info!("Shutdown requested");
+                self.stopping.store(true, Ordering::SeqCst);
+                json!({ "ok": true })
+            }
+        }
+    }
+}
+
+/// Run the daemon in the foreground until `daemon stop`, Ctrl-C or SIGTERM
+#[cfg(unix)]
+pub async fn run(config: Config) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+    use tokio::signal::unix::{signal, SignalKind};
+
+    if let Some(status) = status()? {
+        return Err(anyhow!(
+            "The daemon is already running (pid {})",
+            status.pid
+        ));
+    }
+
+    let socket = socket_path();
+    std::fs::create_dir_all(state_dir())?;
+    // Nothing answered on it, so it was left behind by a daemon that died
+    let _ = std::fs::remove_file(&socket);
+    let listener = tokio::net::UnixListener::bind(&socket)
+        .map_err(|e| anyhow!("Failed to listen on {}: {}", socket.display(), e))?;
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+    }
+    info!(
+        "Daemon started (pid {}), listening on {}",
+        std::process::id(),
+        socket.display()
+    );
+
+    let daemon = Arc::new(Daemon {
+        config,
+        started_at: Utc::now(),
+        sessions: Mutex::new(BTreeMap::new()),
+        queues: Mutex::new(BTreeSet::new()),
+        running_queues: Mutex::new(BTreeSet::new()),
+        stopping: AtomicBool::new(false),
+        runtime: tokio::runtime::Handle::current(),
+    });
+
+    let supervisor = {
+        let daemon = daemon.clone();
+        tokio::task::spawn_blocking(move || Supervisor::new(daemon).run())
+    };
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut check = tokio::time::interval(Duration::from_millis(200));
+    while !daemon.stopping.load(Ordering::SeqCst) {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!("Failed to accept a connection: {}", e);
+                        continue;
+                    }
+                };
+                let daemon = daemon.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut line = String::new();
+                    if tokio::io::BufReader::new(reader).read_line(&mut line).await.is_err() {
+                        return;
+                    }
+                    let response = match serde_json::from_str::<Request>(&line) {
+                        Ok(request) => daemon.handle(request),
+                        Err(e) => json!({ "error": format!("Invalid request: {e}") }),
+                    };
+                    let _ = writer.write_all(format!("{response}\n").as_bytes()).await;
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break,
+            _ = terminate.recv() => break,
+            _ = check.tick() => {}
+        }
+    }
+
+    info!("Daemon stopping");
+    daemon.stopping.store(true, Ordering::SeqCst);
+    let _ = std::fs::remove_file(&socket);
+    supervisor.await??;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn run(_config: Config) -> Result<()> {
+    Err(anyhow!(
+        "The daemon needs Unix sockets, which this platform does not support"
+    ))
+}
+
+/// The loop that watches the sessions, on a blocking thread
+struct Supervisor {
+    daemon: Arc<Daemon>,
+    manager: SessionManager,
+    watchers: BTreeMap<String, SessionWatcher>,
+    /// Sessions that disappeared last tick, judged on the next one so a
+    /// `kill` has time to record that it ended them
+    vanished: Vec<String>,
+    ticks: u64,
+}
+
+impl Supervisor {
+    fn new(daemon: Arc<Daemon>) -> Self {
+        Self {
+            manager: SessionManager::new(daemon.config.clone()),
+            daemon,
+            watchers: BTreeMap::new(),
+            vanished: Vec::new(),
+            ticks: 0,
+        }
+    }
+
+    fn run(mut self) -> Result<()> {
+        while !self.daemon.stopping.load(Ordering::SeqCst) {
+            if let Err(e) = self.tick() {
+                warn!("Failed to check sessions: {:#}", e);
+            }
+            std::thread::sleep(TICK);
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) -> Result<()> {
+        if self.ticks.is_multiple_of(MAINTENANCE_TICKS) {
+            self.maintain();
+        }
+        self.ticks += 1;
+
+        for session_name in std::mem::take(&mut self.vanished) {
+            self.session_gone(&session_name)?;
+        }
+
+        let live = self.manager.running_sessions()?;
+        let gone: Vec<String> = self
+            .watchers
+            .keys()
+            .filter(|name| !live.contains(name))
+            .cloned()
+            .collect();
+        for session_name in gone {
+            self.watchers.remove(&session_name);
+            self.daemon.sessions.lock().unwrap().remove(&session_name);
+            self.vanished.push(session_name);
+        }
+
+        for session_name in &live {
+            if let Err(e) = self.poll(session_name) {
+                warn!("Failed to check session {}: {:#}", session_name, e);
+            }
+        }
+
+        self.resume_queues(&live);
+        Ok(())
+    }
+
+    fn poll(&mut self, session_name: &str) -> Result<()> {
+        if !self.watchers.contains_key(session_name) {
+            info!("Watching session {}", session_name);
+            self.watchers.insert(
+                session_name.to_string(),
+                self.manager.watch_session(session_name).without_output(),
+            );
+            self.record(session_name, EventKind::Discovered);
+        }
+
+        // A backend that keeps the pane after Claude exits shows it as dead
+        if self.manager.claude_exited(session_name)? {
+            let tracked = self
+                .daemon
+                .sessions
+                .lock()
+                .unwrap()
+                .get(session_name)
+                .cloned();
+            if tracked.is_none_or(|tracked| tracked.state != Activity::Exited) {
+                self.record(session_name, EventKind::Exited);
+                self.set_state(session_name, Activity::Exited);
+            }
+            return Ok(());
+        }
+
+        let events = self
+            .watchers
+            .get_mut(session_name)
+            .map(SessionWatcher::poll)
+            .transpose()?
+            .unwrap_or_default();
+        for event in events {
+            match event {
+                StreamEvent::Output { .. } => {}
+                // Handled once the session is gone for good
+                StreamEvent::State {
+                    state: Activity::Exited,
+                    ..
+                } => {}
+                StreamEvent::State {
+                    state,
+                    permission_prompt,
+                } => {
+                    self.set_state(session_name, state);
+                    self.record(
+                        session_name,
+                        EventKind::State {
+                            state,
+                            tool: permission_prompt.map(|prompt| prompt.tool),
+                        },
+                    );
+                }
+                StreamEvent::Hook { event } => self.record(
+                    session_name,
+                    EventKind::Hook {
+                        hook: event.hook,
+                        stop_reason: event.stop_reason,
+                        message: event.message,
+                    },
+                ),
+                StreamEvent::Completed { response, .. } => {
+                    let now = Utc::now();
+                    if let Some(tracked) =
+                        self.daemon.sessions.lock().unwrap().get_mut(session_name)
+                    {
+                        tracked.last_completed_at = Some(now);
+                    }
+                    SessionRegistry::update(|registry| {
+                        if let Some(record) = registry.get_mut(session_name) {
+                            record.last_completed_at = Some(now);
+                        }
+                    })?;
+                    self.record(session_name, EventKind::Completed { response });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A session disappeared: either `kill` ended it, or Claude Code exited
+    /// and took the session with it
+    fn session_gone(&mut self, session_name: &str) -> Result<()> {
+        let killed = SessionRegistry::update(|registry| {
+            let killed = registry
+                .get(session_name)
+                .is_none_or(|record| record.ended_at.is_some());
+            if !killed {
+                registry.end(session_name);
+            }
+            killed
+        })?;
+        if killed {
+            info!("Session {} ended", session_name);
+            self.record(session_name, EventKind::Ended);
+        } else {
+            warn!("Claude Code exited in session {}", session_name);
+            self.record(session_name, EventKind::Exited);
+        }
+        hooks::clear_events(session_name);
+        Ok(())
+    }
+
+    fn set_state(&self, session_name: &str, state: Activity) {
+        let mut sessions = self.daemon.sessions.lock().unwrap();
+        let tracked = sessions
+            .entry(session_name.to_string())
+            .or_insert_with(|| TrackedSession {
+                session: session_name.to_string(),
+                state,
+                since: Utc::now(),
+                last_completed_at: None,
+            });
+        if tracked.state != state {
+            tracked.state = state;
+            tracked.since = Utc::now();
+        }
+    }
+
+    /// Start the queues the daemon was asked to run again once their
+    /// session is idle, e.g. after a message timed out
+    fn resume_queues(&self, live: &[String]) {
+        let wanted: Vec<String> = self.daemon.queues.lock().unwrap().iter().cloned().collect();
+        for session_name in wanted {
+            if self
+                .daemon
+                .running_queues
+                .lock()
+                .unwrap()
+                .contains(&session_name)
+            {
+                continue;
+            }
+            let pending = MessageQueue::load(&session_name)
+                .map(|queue| queue.has_pending())
+                .unwrap_or(false);
+            if !pending || !live.contains(&session_name) {
+                self.daemon.queues.lock().unwrap().remove(&session_name);
+                continue;
+            }
+            let idle = self
+                .daemon
+                .sessions
+                .lock()
+                .unwrap()
+                .get(&session_name)
+                .is_some_and(|tracked| tracked.state == Activity::Idle);
+            if idle && !queue::worker_running(&session_name) {
+                self.daemon.run_queue(&session_name);
+            }
+        }
+    }
+
+    /// Keep the logs in check and remove the hook files of ended sessions
+    fn maintain(&self) {
+        if let Err(e) = self.manager.rotate_logs() {
+            warn!("Failed to rotate session logs: {}", e);
+        }
+        if let Err(e) = logs::rotate_if_oversized(&events_path(), &self.daemon.config.log_policy())
+        {
+            warn!("Failed to rotate the daemon events: {}", e);
+        }
+        if let Ok(registry) = SessionRegistry::load() {
+            for record in registry
+                .records()
+                .filter(|record| record.ended_at.is_some())
+            {
+                hooks::clear_events(&record.name);
+            }
+        }
+    }
+
+    fn record(&self, session_name: &str, kind: EventKind) {
+        let event = DaemonEvent {
+            timestamp: Utc::now(),
+            session: session_name.to_string(),
+            kind,
+        };
+        info!("{}", event);
+        if let Err(e) = append_event(&event) {
+            warn!("Failed to record daemon event: {}", e);
+        }
+    }
+}
+
+fn append_event(event: &DaemonEvent) -> Result<()> {
+    std::fs::create_dir_all(state_dir())?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(events_path())?;
+    file.write_all(format!("{}\n", serde_json::to_string(event)?).as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_flat_json_lines() {
+        let event = DaemonEvent {
+            timestamp: DateTime::parse_from_rfc3339("2025-01-02T03:04:05Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            session: "dev".to_string(),
+            kind: EventKind::State {
+                state: Activity::WaitingForPermission,
+                tool: Some("Bash".to_string()),
+            },
+        };
+        let line = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            line,
+            r#"{"timestamp":"2025-01-02T03:04:05Z","session":"dev","event":"state","state":"waiting_for_permission","tool":"Bash"}"#
+        );
+        let parsed: DaemonEvent = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            parsed.to_string(),
+            "2025-01-02 03:04:05 dev: waiting for permission (Bash)"
+        );
+
+        let exited: DaemonEvent = serde_json::from_str(
+            r#"{"timestamp":"2025-01-02T03:04:05Z","session":"dev","event":"exited"}"#,
+        )
+        .unwrap();
+        assert!(matches!(exited.kind, EventKind::Exited));
+    }
+}
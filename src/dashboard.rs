@@ -0,0 +1,560 @@
+//! Terminal dashboard of all sessions.
+//!
+//! `dashboard` lists every session with its working directory, age, what
+//! Claude Code is doing and the last message sent, next to a live tail of the
+//! selected pane. Keys run the same operations as the commands: send, attach,
+//! approve, deny, kill and export.
+
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::daemon;
+use crate::export::ExportFormat;
+use crate::permissions::{PermissionDecision, PermissionPrompt};
+use crate::session::{Session, SessionManager, SessionStatus};
+use crate::stream::Activity;
+use crate::{format_age, truncate_message, Config};
+
+/// How often the tail of the selected pane is redrawn
+const TICK: Duration = Duration::from_millis(250);
+
+/// How often the session list is reloaded
+const REFRESH: Duration = Duration::from_secs(2);
+
+/// Where the dashboard logs, since it owns the terminal. Creates its directory.
+pub fn log_path() -> Result<PathBuf> {
+    let dir = daemon::state_dir();
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join("dashboard.log"))
+}
+
+/// A session and what Claude Code is doing in it
+struct SessionRow {
+    session: Session,
+    activity: Activity,
+    permission_prompt: Option<PermissionPrompt>,
+}
+
+impl SessionRow {
+    fn is_running(&self) -> bool {
+        !matches!(
+            self.session.status,
+            SessionStatus::Ended | SessionStatus::Failed
+        )
+    }
+
+    fn state(&self) -> String {
+        if !self.is_running() {
+            return self.session.status.to_string();
+        }
+        match &self.permission_prompt {
+            Some(prompt) => format!("{} ({})", self.activity, prompt.tool),
+            None => self.activity.to_string(),
+        }
+    }
+
+    fn state_style(&self) -> Style {
+        let color = match self.activity {
+            _ if !self.is_running() => Color::DarkGray,
+            Activity::Working => Color::Yellow,
+            Activity::WaitingForPermission => Color::Red,
+            Activity::Idle => Color::Green,
+            Activity::Exited => Color::DarkGray,
+        };
+        Style::default().fg(color)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    /// Typing a message for the selected session
+    Compose(String),
+    /// Asking before killing the selected session
+    ConfirmKill,
+}
+
+/// What a key asks the dashboard to do to the selected session
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Send(String),
+    Attach,
+    Approve,
+    Deny,
+    Kill,
+    Export,
+    Quit,
+}
+
+/// Everything on screen, apart from the sessions themselves
+struct View {
+    rows: Vec<SessionRow>,
+    table: TableState,
+    mode: Mode,
+    /// Last lines of the selected pane
+    tail: String,
+    /// Outcome of the last action, shown in the footer
+    notice: Option<String>,
+}
+
+impl View {
+    fn new() -> Self {
+        Self {
+            rows: Vec::new(),
+            table: TableState::default(),
+            mode: Mode::Browse,
+            tail: String::new(),
+            notice: None,
+        }
+    }
+
+    fn selected(&self) -> Option<&SessionRow> {
+        self.rows.get(self.table.selected()?)
+    }
+
+    /// Replace the rows, keeping the same session selected
+    fn set_rows(&mut self, rows: Vec<SessionRow>) {
+        let selected = self.selected().map(|row| row.session.name.clone());
+        self.rows = rows;
+        let index = selected
+            .and_then(|name| self.rows.iter().position(|row| row.session.name == name))
+            .or_else(|| {
+                self.table
+                    .selected()
+                    .map(|index| index.min(self.rows.len().saturating_sub(1)))
+            })
+            .unwrap_or(0);
+        self.table.select((!self.rows.is_empty()).then_some(index));
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let current = self.table.selected().unwrap_or(0);
+        let next = if down {
+            (current + 1).min(self.rows.len() - 1)
+        } else {
+            current.saturating_sub(1)
+        };
+        self.table.select(Some(next));
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Compose(mut message) => {
+                match key.code {
+                    KeyCode::Esc => {}
+                    KeyCode::Enter if !message.trim().is_empty() => {
+                        return Some(Action::Send(message));
+                    }
+                    KeyCode::Backspace => {
+                        message.pop();
+                        self.mode = Mode::Compose(message);
+                    }
+                    KeyCode::Char(c) => {
+                        message.push(c);
+                        self.mode = Mode::Compose(message);
+                    }
+                    _ => self.mode = Mode::Compose(message),
+                }
+                None
+            }
+            Mode::ConfirmKill => (key.code == KeyCode::Char('y')).then_some(Action::Kill),
+            Mode::Browse => {
+                let running = self.selected().is_some_and(SessionRow::is_running);
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.move_selection(true);
+                        None
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.move_selection(false);
+                        None
+                    }
+                    KeyCode::Char('s') | KeyCode::Enter if running => {
+                        self.mode = Mode::Compose(String::new());
+                        None
+                    }
+                    KeyCode::Char('x') if running => {
+                        self.mode = Mode::ConfirmKill;
+                        None
+                    }
+                    KeyCode::Char('a') if running => Some(Action::Attach),
+                    KeyCode::Char('y') if running => Some(Action::Approve),
+                    KeyCode::Char('n') if running => Some(Action::Deny),
+                    KeyCode::Char('e') if self.selected().is_some() => Some(Action::Export),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list_area, tail_area, footer_area] = Layout::vertical([
+            Constraint::Length(self.rows.len().clamp(1, 12) as u16 + 3),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let rows = self.rows.iter().map(|row| {
+            let session = &row.session;
+            let last_message = session
+                .last_message
+                .as_deref()
+                .map(|message| truncate_message(message, 60))
+                .unwrap_or_default();
+            Row::new(vec![
+                Cell::from(session.name.clone()),
+                Cell::from(
+                    session
+                        .working_dir
+                        .as_ref()
+                        .map(|dir| dir.display().to_string())
+                        .unwrap_or_default(),
+                ),
+                Cell::from(format_age(session.created_at)),
+                Cell::from(Span::styled(row.state(), row.state_style())),
+                Cell::from(last_message),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(22),
+                Constraint::Percentage(25),
+                Constraint::Length(9),
+                Constraint::Length(30),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(
+            Row::new(["Session", "Directory", "Age", "State", "Last message"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(Block::default().borders(Borders::ALL).title(" Sessions "));
+        frame.render_stateful_widget(table, list_area, &mut self.table);
+
+        let title = match self.selected() {
+            Some(row) => format!(" {} ", row.session.name),
+            None => " No sessions ".to_string(),
+        };
+        let height = tail_area.height.saturating_sub(2) as usize;
+        let lines: Vec<&str> = self.tail.trim_end().lines().collect();
+        let tail = lines[lines.len().saturating_sub(height)..].join("\n");
+        frame.render_widget(
+            Paragraph::new(tail).block(Block::default().borders(Borders::ALL).title(title)),
+            tail_area,
+        );
+
+        let footer = match &self.mode {
+            Mode::Compose(message) => Line::from(vec![
+                Span::styled("Send: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(format!("{message}█")),
+                Span::styled(
+                    "  (Enter to send, Esc to cancel)",
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]),
+            Mode::ConfirmKill => Line::from(format!(
+                "Kill session {}? (y/n)",
+                self.selected()
+                    .map(|row| row.session.name.as_str())
+                    .unwrap_or_default()
+            )),
+            Mode::Browse => match &self.notice {
+                Some(notice) => Line::from(notice.as_str()),
+                None => Line::styled(
+                    "↑/↓ select  s send  a attach  y approve  n deny  x kill  e export  q quit",
+                    Style::default().fg(Color::DarkGray),
+                ),
+            },
+        };
+        frame.render_widget(Paragraph::new(footer), footer_area);
+    }
+}
+
+struct Dashboard {
+    manager: SessionManager,
+    runtime: tokio::runtime::Handle,
+    view: View,
+    refreshed_at: Option<Instant>,
+}
+
+impl Dashboard {
+    fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            if self
+                .refreshed_at
+                .is_none_or(|refreshed_at| refreshed_at.elapsed() >= REFRESH)
+            {
+                self.refresh()?;
+            }
+            self.update_tail(terminal.size()?.height as usize);
+            terminal.draw(|frame| self.view.draw(frame))?;
+
+            if !event::poll(TICK)? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.view.handle_key(key) {
+                Some(Action::Quit) => return Ok(()),
+                Some(action) => {
+                    let notice = match self.perform(action, terminal) {
+                        Ok(notice) => notice,
+                        Err(e) => format!("Error: {e:#}"),
+                    };
+                    self.view.notice = Some(notice);
+                    self.refreshed_at = None;
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Reload the sessions and what each one is doing
+    fn refresh(&mut self) -> Result<()> {
+        let sessions = self.runtime.block_on(self.manager.list_sessions())?;
+        let rows = sessions
+            .into_iter()
+            .map(|session| {
+                let (activity, permission_prompt) = match session.status {
+                    SessionStatus::Ended | SessionStatus::Failed => (Activity::Exited, None),
+                    _ => self
+                        .manager
+                        .session_activity(&session.name)
+                        .unwrap_or((Activity::Exited, None)),
+                };
+                SessionRow {
+                    session,
+                    activity,
+                    permission_prompt,
+                }
+            })
+            .collect();
+        self.view.set_rows(rows);
+        self.refreshed_at = Some(Instant::now());
+        Ok(())
+    }
+
+    fn update_tail(&mut self, lines: usize) {
+        self.view.tail = match self.view.selected() {
+            Some(row) if row.is_running() => {
+                let name = row.session.name.clone();
+                self.runtime
+                    .block_on(self.manager.get_session_status(&name, lines))
+                    .unwrap_or_else(|e| format!("Failed to read the pane: {e}"))
+            }
+            Some(row) => format!(
+                "Session {}. Revive it with 'claude-code-manager revive -s {}'",
+                row.session.status, row.session.name
+            ),
+            None => "Start one with 'claude-code-manager start'".to_string(),
+        };
+    }
+
+    /// Run `action` on the selected session, returning what to tell the user
+    fn perform(&mut self, action: Action, terminal: &mut DefaultTerminal) -> Result<String> {
+        let Some(row) = self.view.selected() else {
+            return Ok("No session selected".to_string());
+        };
+        let name = row.session.name.clone();
+
+        match action {
+            Action::Send(message) => {
+                self.runtime
+                    .block_on(self.manager.send_message(&name, &message))?;
+                Ok(format!("Message sent to session: {name}"))
+            }
+            Action::Attach => {
+                ratatui::try_restore()?;
+                let attached = self.runtime.block_on(self.manager.attach_session(&name));
+                *terminal = ratatui::try_init()?;
+                attached?;
+                Ok(format!("Detached from session: {name}"))
+            }
+            Action::Approve | Action::Deny => {
+                let decision = if action == Action::Approve {
+                    PermissionDecision::Approve
+                } else {
+                    PermissionDecision::Deny
+                };
+                let prompt = self
+                    .runtime
+                    .block_on(self.manager.answer_permission(&name, decision))?;
+                Ok(format!(
+                    "{} {} in session: {name}",
+                    decision,
+                    prompt.detail.as_deref().unwrap_or(&prompt.tool)
+                ))
+            }
+            Action::Kill => {
+                self.runtime
+                    .block_on(self.manager.kill_session(&name, false))?;
+                Ok(format!("Killed session: {name}"))
+            }
+            Action::Export => {
+                let path = PathBuf::from(format!("{name}.md"));
+                self.runtime.block_on(self.manager.export_session_history(
+                    &name,
+                    &path,
+                    ExportFormat::Markdown,
+                    true,
+                    false,
+                ))?;
+                Ok(format!(
+                    "Exported session '{name}' history to: {}",
+                    path.display()
+                ))
+            }
+            Action::Quit => Ok(String::new()),
+        }
+    }
+}
+
+/// Show the dashboard until the user quits
+pub async fn run(config: Config) -> Result<()> {
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let dashboard = Dashboard {
+            manager: SessionManager::new(config),
+            runtime,
+            view: View::new(),
+            refreshed_at: None,
+        };
+        let mut terminal = ratatui::try_init()?;
+        let result = dashboard.run(&mut terminal);
+        ratatui::try_restore()?;
+        result
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use serde_json::json;
+
+    fn row(name: &str, status: &str, activity: Activity) -> SessionRow {
+        SessionRow {
+            session: serde_json::from_value(json!({
+                "id": name,
+                "name": name,
+                "working_dir": format!("/work/{name}"),
+                "created_at": chrono::Utc::now(),
+                "status": status,
+                "permission_mode": "default",
+                "command": [],
+                "last_message": "Fix the flaky test\nand push",
+            }))
+            .unwrap(),
+            activity,
+            permission_prompt: None,
+        }
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn view() -> View {
+        let mut view = View::new();
+        view.set_rows(vec![
+            row("api", "active", Activity::Working),
+            row("web", "idle", Activity::Idle),
+            row("old", "ended", Activity::Exited),
+        ]);
+        view
+    }
+
+    #[test]
+    fn sessions_are_listed_with_their_state() {
+        let mut view = view();
+        view.tail = "⏺ Done\n\n> ".to_string();
+        let mut terminal = Terminal::new(TestBackend::new(140, 20)).unwrap();
+        terminal.draw(|frame| view.draw(frame)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("/work/api"));
+        assert!(screen.contains("working"));
+        assert!(screen.contains("idle"));
+        assert!(screen.contains("ended"));
+        assert!(screen.contains("Fix the flaky test..."));
+        assert!(screen.contains("⏺ Done"));
+    }
+
+    #[test]
+    fn selection_survives_a_refresh() {
+        let mut view = view();
+        assert_eq!(view.handle_key(key(KeyCode::Down)), None);
+        assert_eq!(view.selected().unwrap().session.name, "web");
+
+        view.set_rows(vec![
+            row("new", "active", Activity::Idle),
+            row("api", "active", Activity::Working),
+            row("web", "idle", Activity::Idle),
+        ]);
+        assert_eq!(view.selected().unwrap().session.name, "web");
+
+        view.set_rows(vec![row("api", "active", Activity::Working)]);
+        assert_eq!(view.selected().unwrap().session.name, "api");
+    }
+
+    #[test]
+    fn keys_compose_messages_and_confirm_kills() {
+        let mut view = view();
+        assert_eq!(view.handle_key(key(KeyCode::Char('s'))), None);
+        for c in "hi there".chars() {
+            assert_eq!(view.handle_key(key(KeyCode::Char(c))), None);
+        }
+        view.handle_key(key(KeyCode::Backspace));
+        assert_eq!(
+            view.handle_key(key(KeyCode::Enter)),
+            Some(Action::Send("hi ther".to_string()))
+        );
+        assert_eq!(view.mode, Mode::Browse);
+
+        assert_eq!(view.handle_key(key(KeyCode::Char('x'))), None);
+        assert_eq!(view.handle_key(key(KeyCode::Char('n'))), None);
+        assert_eq!(view.mode, Mode::Browse);
+        view.handle_key(key(KeyCode::Char('x')));
+        assert_eq!(view.handle_key(key(KeyCode::Char('y'))), Some(Action::Kill));
+
+        // Only export makes sense for a session that has ended
+        view.handle_key(key(KeyCode::Down));
+        view.handle_key(key(KeyCode::Down));
+        assert_eq!(view.handle_key(key(KeyCode::Char('a'))), None);
+        assert_eq!(
+            view.handle_key(key(KeyCode::Char('e'))),
+            Some(Action::Export)
+        );
+        assert_eq!(view.handle_key(key(KeyCode::Char('q'))), Some(Action::Quit));
+    }
+}
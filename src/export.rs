@@ -0,0 +1,486 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::path::Path;
+
+use crate::transcript::{self, ToolCall, ToolResult, Transcript, TranscriptEntry};
+
+/// File format written by `export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// Plain text, as `history` shows it
+    Txt,
+    /// Markdown with a heading per turn and collapsible tool calls
+    Markdown,
+    /// A self-contained HTML page
+    Html,
+    /// The conversation entries as JSON
+    Json,
+}
+
+impl ExportFormat {
+    /// Guess the format from the output file extension, defaulting to text
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "md" | "markdown" => ExportFormat::Markdown,
+            "html" | "htm" => ExportFormat::Html,
+            "json" => ExportFormat::Json,
+            _ => ExportFormat::Txt,
+        }
+    }
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Txt => write!(f, "txt"),
+            ExportFormat::Markdown => write!(f, "markdown"),
+            ExportFormat::Html => write!(f, "html"),
+            ExportFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    User,
+    Assistant,
+}
+
+impl Role {
+    fn title(&self) -> &'static str {
+        match self {
+            Role::User => "User",
+            Role::Assistant => "Claude",
+        }
+    }
+
+    fn class(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+}
+
+enum TurnItem<'a> {
+    Text(&'a str),
+    Tool {
+        call: &'a ToolCall,
+        result: Option<&'a ToolResult>,
+    },
+}
+
+/// Consecutive entries from one side of the conversation
+struct Turn<'a> {
+    role: Role,
+    timestamp: Option<DateTime<Utc>>,
+    items: Vec<TurnItem<'a>>,
+}
+
+/// Group entries into alternating user and assistant turns, pairing each
+/// tool call with its result
+fn turns(entries: &[TranscriptEntry]) -> Vec<Turn<'_>> {
+    let mut turns: Vec<Turn> = Vec::new();
+
+    for entry in entries {
+        let (role, timestamp, item) = match entry {
+            TranscriptEntry::User(message) => {
+                (Role::User, message.timestamp, TurnItem::Text(&message.text))
+            }
+            TranscriptEntry::Assistant(message) => (
+                Role::Assistant,
+                message.timestamp,
+                TurnItem::Text(&message.text),
+            ),
+            TranscriptEntry::ToolCall(call) => (
+                Role::Assistant,
+                call.timestamp,
+                TurnItem::Tool { call, result: None },
+            ),
+            TranscriptEntry::ToolResult(result) => {
+                let pending_call = turns
+                    .iter_mut()
+                    .rev()
+                    .flat_map(|turn| turn.items.iter_mut())
+                    .find_map(|item| match item {
+                        TurnItem::Tool { call, result: slot } if call.id == result.tool_use_id => {
+                            Some(slot)
+                        }
+                        _ => None,
+                    });
+                if let Some(slot) = pending_call {
+                    *slot = Some(result);
+                }
+                continue;
+            }
+        };
+
+        match turns.last_mut() {
+            Some(turn) if turn.role == role => turn.items.push(item),
+            _ => turns.push(Turn {
+                role,
+                timestamp,
+                items: vec![item],
+            }),
+        }
+    }
+
+    turns
+}
+
+/// Render a transcript in `format`
+pub fn render_transcript(
+    format: ExportFormat,
+    session_name: &str,
+    transcript: &Transcript,
+) -> Result<String> {
+    match format {
+        ExportFormat::Txt => Ok(transcript.render_text()),
+        ExportFormat::Markdown => Ok(render_markdown(session_name, transcript)),
+        ExportFormat::Html => Ok(render_html(session_name, transcript)),
+        ExportFormat::Json => {
+            let document = json!({
+                "session": session_name,
+                "source": "transcript",
+                "conversation_id": transcript.conversation_id,
+                "transcript": transcript.path,
+                "exported_at": Utc::now(),
+                "entries": transcript.entries,
+            });
+            Ok(format!("{}\n", serde_json::to_string_pretty(&document)?))
+        }
+    }
+}
+
+/// Render a terminal log, which has no turns, in `format`
+pub fn render_log(format: ExportFormat, session_name: &str, log: &str) -> Result<String> {
+    match format {
+        ExportFormat::Txt => Ok(log.to_string()),
+        ExportFormat::Markdown => Ok(format!(
+            "# Claude Code session: {session_name}\n\n{}\n",
+            fenced(log, "")
+        )),
+        ExportFormat::Html => Ok(html_page(
+            session_name,
+            &format!("<pre>{}</pre>\n", escape_html(log)),
+        )),
+        ExportFormat::Json => {
+            let document = json!({
+                "session": session_name,
+                "source": "log",
+                "exported_at": Utc::now(),
+                "history": log,
+            });
+            Ok(format!("{}\n", serde_json::to_string_pretty(&document)?))
+        }
+    }
+}
+
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+fn render_markdown(session_name: &str, transcript: &Transcript) -> String {
+    let mut out = format!("# Claude Code session: {session_name}\n\n");
+    out.push_str(&format!(
+        "- Conversation: `{}`\n- Exported: {}\n",
+        transcript.conversation_id,
+        format_timestamp(Utc::now())
+    ));
+
+    for turn in turns(&transcript.entries) {
+        out.truncate(out.trim_end().len());
+        out.push_str(&format!("\n\n## {}", turn.role.title()));
+        if let Some(timestamp) = turn.timestamp {
+            out.push_str(&format!(" · {}", format_timestamp(timestamp)));
+        }
+        out.push_str("\n\n");
+
+        for item in &turn.items {
+            match item {
+                // Messages are Markdown already, code fences included
+                TurnItem::Text(text) => out.push_str(&format!("{}\n\n", text.trim_end())),
+                TurnItem::Tool { call, result } => {
+                    out.push_str(&format!(
+                        "<details>\n<summary>{}</summary>\n\n",
+                        escape_html(&transcript::tool_call_summary(call))
+                    ));
+                    out.push_str(&format!(
+                        "{}\n\n",
+                        fenced(&pretty_json(&call.input), "json")
+                    ));
+                    if let Some(result) = result {
+                        let label = if result.is_error { "Error" } else { "Result" };
+                        out.push_str(&format!("{label}:\n\n{}\n\n", fenced(&result.content, "")));
+                    }
+                    out.push_str("</details>\n\n");
+                }
+            }
+        }
+    }
+
+    format!("{}\n", out.trim_end())
+}
+
+fn render_html(session_name: &str, transcript: &Transcript) -> String {
+    let mut body = format!(
+        "<p class=\"meta\">Conversation <code>{}</code> · exported {}</p>\n",
+        escape_html(&transcript.conversation_id),
+        format_timestamp(Utc::now())
+    );
+
+    for turn in turns(&transcript.entries) {
+        body.push_str(&format!(
+            "<section class=\"turn {}\">\n<h2>{}",
+            turn.role.class(),
+            turn.role.title()
+        ));
+        if let Some(timestamp) = turn.timestamp {
+            body.push_str(&format!(
+                " <time datetime=\"{}\">{}</time>",
+                timestamp.to_rfc3339(),
+                format_timestamp(timestamp)
+            ));
+        }
+        body.push_str("</h2>\n");
+
+        for item in &turn.items {
+            match item {
+                TurnItem::Text(text) => body.push_str(&html_text(text)),
+                TurnItem::Tool { call, result } => {
+                    body.push_str(&format!(
+                        "<details class=\"tool\">\n<summary>{}</summary>\n<pre><code class=\"language-json\">{}</code></pre>\n",
+                        escape_html(&transcript::tool_call_summary(call)),
+                        escape_html(&pretty_json(&call.input))
+                    ));
+                    if let Some(result) = result {
+                        let class = if result.is_error {
+                            "result error"
+                        } else {
+                            "result"
+                        };
+                        body.push_str(&format!(
+                            "<pre class=\"{class}\">{}</pre>\n",
+                            escape_html(&result.content)
+                        ));
+                    }
+                    body.push_str("</details>\n");
+                }
+            }
+        }
+        body.push_str("</section>\n");
+    }
+
+    html_page(session_name, &body)
+}
+
+fn html_page(session_name: &str, body: &str) -> String {
+    let title = escape_html(&format!("Claude Code session: {session_name}"));
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 860px; margin: 2em auto; padding: 0 1em; color: #1f2328; }}
+h2 {{ font-size: 1em; margin: 0 0 .5em; }}
+time, .meta {{ color: #656d76; font-weight: normal; font-size: .9em; }}
+.turn {{ border-left: 4px solid #d0d7de; padding: .5em 1em; margin: 1em 0; }}
+.turn.user {{ border-color: #0969da; background: #f6f8fa; }}
+.turn.assistant {{ border-color: #bf8700; }}
+.text {{ white-space: pre-wrap; }}
+pre {{ background: #f6f8fa; padding: .75em; overflow-x: auto; }}
+details.tool summary {{ cursor: pointer; font-family: ui-monospace, monospace; }}
+pre.error {{ background: #ffebe9; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}</body>
+</html>
+"#
+    )
+}
+
+/// Message text as HTML, with fenced code blocks turned into `<pre>` blocks
+fn html_text(text: &str) -> String {
+    let mut html = String::new();
+    let mut prose: Vec<&str> = Vec::new();
+    // Opening fence line and the code lines after it
+    let mut code: Option<(&str, Vec<&str>)> = None;
+
+    let flush_prose = |html: &mut String, prose: &mut Vec<&str>| {
+        let joined = prose.join("\n");
+        if !joined.trim().is_empty() {
+            html.push_str(&format!(
+                "<div class=\"text\">{}</div>\n",
+                escape_html(joined.trim())
+            ));
+        }
+        prose.clear();
+    };
+
+    for line in text.lines() {
+        let fence = line.trim_start().starts_with("```");
+        match code.take() {
+            Some((opening, lines)) if fence => {
+                let language = opening.trim_start().trim_start_matches('`').trim();
+                let class = if language.is_empty() {
+                    String::new()
+                } else {
+                    format!(" class=\"language-{}\"", escape_html(language))
+                };
+                html.push_str(&format!(
+                    "<pre><code{class}>{}</code></pre>\n",
+                    escape_html(&lines.join("\n"))
+                ));
+            }
+            Some((opening, mut lines)) => {
+                lines.push(line);
+                code = Some((opening, lines));
+            }
+            None if fence => {
+                flush_prose(&mut html, &mut prose);
+                code = Some((line, Vec::new()));
+            }
+            None => prose.push(line),
+        }
+    }
+
+    // An unterminated fence is shown as it was written
+    if let Some((opening, lines)) = code {
+        prose.push(opening);
+        prose.extend(lines);
+    }
+    flush_prose(&mut html, &mut prose);
+    html
+}
+
+/// Wrap `text` in a code fence longer than any backtick run inside it
+fn fenced(text: &str, language: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat((longest + 1).max(3));
+    format!("{fence}{language}\n{}\n{fence}", text.trim_end())
+}
+
+fn pretty_json(value: &serde_json::Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::{AssistantMessage, UserMessage};
+    use std::path::PathBuf;
+
+    fn transcript() -> Transcript {
+        let timestamp = "2025-06-01T10:00:00Z".parse().ok();
+        Transcript {
+            conversation_id: "abc-123".to_string(),
+            path: PathBuf::from("abc-123.jsonl"),
+            entries: vec![
+                TranscriptEntry::User(UserMessage {
+                    timestamp,
+                    text: "Print <hello>".to_string(),
+                }),
+                TranscriptEntry::Assistant(AssistantMessage {
+                    timestamp,
+                    model: None,
+                    text: "Running it:\n```python\nprint('hi')\n```".to_string(),
+                }),
+                TranscriptEntry::ToolCall(ToolCall {
+                    timestamp,
+                    id: "toolu_1".to_string(),
+                    name: "Bash".to_string(),
+                    input: json!({ "command": "python hello.py" }),
+                }),
+                TranscriptEntry::ToolResult(ToolResult {
+                    timestamp,
+                    tool_use_id: "toolu_1".to_string(),
+                    content: "hi ```".to_string(),
+                    is_error: false,
+                }),
+                TranscriptEntry::Assistant(AssistantMessage {
+                    timestamp,
+                    model: None,
+                    text: "Done.".to_string(),
+                }),
+            ],
+        }
+    }
+
+    #[test]
+    fn tool_results_join_their_call_in_one_assistant_turn() {
+        let transcript = transcript();
+        let turns = turns(&transcript.entries);
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].role, Role::User);
+        assert_eq!(turns[1].role, Role::Assistant);
+        assert_eq!(turns[1].items.len(), 3);
+        assert!(matches!(
+            turns[1].items[1],
+            TurnItem::Tool {
+                result: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn markdown_has_turn_headings_and_collapsible_tools() {
+        let markdown = render_markdown("claude-demo", &transcript());
+        assert!(markdown.starts_with("# Claude Code session: claude-demo\n"));
+        assert!(markdown.contains("\n## User · 2025-06-01 10:00:00 UTC\n\nPrint <hello>\n"));
+        assert!(markdown.contains("```python\nprint('hi')\n```"));
+        assert!(markdown.contains("<summary>Bash(python hello.py)</summary>"));
+        // The result contains a fence, so it gets a longer one
+        assert!(markdown.contains("Result:\n\n````\nhi ```\n````"));
+    }
+
+    #[test]
+    fn html_escapes_text_and_renders_code_blocks() {
+        let html = render_html("claude-demo", &transcript());
+        assert!(html.contains("<div class=\"text\">Print &lt;hello&gt;</div>"));
+        assert!(html.contains("<pre><code class=\"language-python\">print('hi')</code></pre>"));
+        assert!(html.contains("<details class=\"tool\">\n<summary>Bash(python hello.py)</summary>"));
+        assert!(html.contains("<time datetime=\"2025-06-01T10:00:00+00:00\">"));
+    }
+
+    #[test]
+    fn format_follows_the_file_extension() {
+        assert_eq!(
+            ExportFormat::from_path(Path::new("pr.md")),
+            ExportFormat::Markdown
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out/incident.HTML")),
+            ExportFormat::Html
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("log.json")),
+            ExportFormat::Json
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("session.log")),
+            ExportFormat::Txt
+        );
+    }
+}
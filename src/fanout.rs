@@ -0,0 +1,412 @@
+//! Sending one prompt to many sessions at once.
+//!
+//! `fanout` resolves its targets (named sessions, and one directory session
+//! per directory matching `--dirs`, started when it is not running yet),
+//! sends the prompt to each and waits for all of them. Sessions are started
+//! and sent to one at a time, since both type into the multiplexer, and
+//! then waited on in parallel, at most `--concurrency` at a time.
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::claude::{Conversation, ExitReason};
+use crate::session::{CompletionReport, SessionManager};
+use crate::{generate_directory_session_name, Config};
+
+/// A session to send the prompt to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub session: String,
+    /// Directory to start the session in when it is not running
+    pub working_dir: Option<PathBuf>,
+}
+
+/// Targets for the named sessions and the directories matching `patterns`,
+/// in order and without duplicates
+pub fn resolve_targets(sessions: &[String], patterns: &[String]) -> Result<Vec<Target>> {
+    let mut targets: Vec<Target> = sessions
+        .iter()
+        .map(|session| Target {
+            session: session.clone(),
+            working_dir: None,
+        })
+        .collect();
+
+    for pattern in patterns {
+        let paths = glob::glob(pattern)
+            .map_err(|e| anyhow!("Invalid directory pattern '{}': {}", pattern, e))?;
+        let mut matched = false;
+        for path in paths {
+            let path = path?;
+            if !path.is_dir() {
+                continue;
+            }
+            matched = true;
+            let dir = path.canonicalize()?;
+            targets.push(Target {
+                session: generate_directory_session_name(&dir),
+                working_dir: Some(dir),
+            });
+        }
+        if !matched {
+            warn!("No directories match '{}'", pattern);
+        }
+    }
+
+    let mut seen = std::collections::BTreeSet::new();
+    targets.retain(|target| seen.insert(target.session.clone()));
+    if targets.is_empty() {
+        return Err(anyhow!("No sessions or directories to send to"));
+    }
+    Ok(targets)
+}
+
+/// How the prompt fared in one session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FanoutStatus {
+    /// Claude finished its turn
+    Done,
+    /// Claude is blocked on a permission prompt
+    AwaitingPermission,
+    /// Claude did not finish in time
+    Timeout,
+    /// The session could not be started or sent to
+    Failed,
+}
+
+impl FanoutStatus {
+    /// Exit code reported for the session
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FanoutStatus::Done => 0,
+            FanoutStatus::Failed => 1,
+            FanoutStatus::Timeout => 2,
+            FanoutStatus::AwaitingPermission => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for FanoutStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FanoutStatus::Done => write!(f, "done"),
+            FanoutStatus::AwaitingPermission => write!(f, "awaiting permission"),
+            FanoutStatus::Timeout => write!(f, "timeout"),
+            FanoutStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FanoutResult {
+    pub session: String,
+    pub working_dir: Option<PathBuf>,
+    /// Whether the session was started for this fan-out
+    pub started: bool,
+    pub status: FanoutStatus,
+    pub exit_code: i32,
+    pub exit_reason: Option<ExitReason>,
+    pub elapsed_secs: f64,
+    pub response: Option<String>,
+    pub error: Option<String>,
+}
+
+impl FanoutResult {
+    fn new(
+        target: Target,
+        started: bool,
+        elapsed_secs: f64,
+        outcome: Result<CompletionReport>,
+    ) -> Self {
+        let (status, report, error) = match outcome {
+            Ok(report) => {
+                let status = match report.exit_reason {
+                    ExitReason::Timeout => FanoutStatus::Timeout,
+                    ExitReason::AwaitingPermission => FanoutStatus::AwaitingPermission,
+                    _ => FanoutStatus::Done,
+                };
+                (status, Some(report), None)
+            }
+            Err(e) => (FanoutStatus::Failed, None, Some(format!("{e:#}"))),
+        };
+        Self {
+            session: target.session,
+            working_dir: target.working_dir,
+            started,
+            status,
+            exit_code: status.exit_code(),
+            exit_reason: report.as_ref().map(|report| report.exit_reason),
+            elapsed_secs,
+            response: report.and_then(|report| report.response),
+            error,
+        }
+    }
+}
+
+/// Send `message` to every target and wait for them all, waiting on at
+/// most `concurrency` at a time. Results come back in the order of `targets`.
+pub async fn fan_out(
+    config: &Config,
+    targets: Vec<Target>,
+    message: &str,
+    timeout: u64,
+    concurrency: usize,
+) -> Result<Vec<FanoutResult>> {
+    let config = config.clone();
+    fan_out_with(
+        move || SessionManager::new(config.clone()),
+        targets,
+        message,
+        timeout,
+        concurrency,
+    )
+    .await
+}
+
+async fn fan_out_with(
+    new_manager: impl Fn() -> SessionManager + Send + Sync + 'static,
+    targets: Vec<Target>,
+    message: &str,
+    timeout: u64,
+    concurrency: usize,
+) -> Result<Vec<FanoutResult>> {
+    let new_manager = Arc::new(new_manager);
+
+    let permits = Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+    let mut waits = tokio::task::JoinSet::new();
+    let mut results = Vec::new();
+
+    // Starting a session and typing into one go through the multiplexer, so
+    // they happen one target at a time; each wait starts once its target was
+    // sent to, and the waits run in parallel
+    for (index, target) in targets.into_iter().enumerate() {
+        let start_time = std::time::Instant::now();
+        let sent_at = Utc::now();
+        let send_manager = new_manager.clone();
+        let message = message.to_string();
+        let (target, outcome) = tokio::task::spawn_blocking(move || {
+            let mut manager = send_manager();
+            let outcome = tokio::runtime::Handle::current().block_on(send_to(
+                &mut manager,
+                &target,
+                &message,
+            ));
+            (target, outcome)
+        })
+        .await?;
+        let started = match outcome {
+            Ok(started) => started,
+            Err(e) => {
+                let elapsed = start_time.elapsed().as_secs_f64();
+                results.push((index, finish(target, false, elapsed, Err(e))));
+                continue;
+            }
+        };
+
+        let permits = permits.clone();
+        let new_manager = new_manager.clone();
+        waits.spawn(async move {
+            let _permit = permits.acquire_owned().await?;
+            let result =
+                tokio::task::spawn_blocking(move || {
+                    let mut manager = new_manager();
+                    // The turn may have ended while waiting for a permit
+                    let outcome = tokio::runtime::Handle::current().block_on(
+                        manager.wait_for_turn(&target.session, timeout, false, Some(sent_at)),
+                    );
+                    finish(target, started, start_time.elapsed().as_secs_f64(), outcome)
+                })
+                .await?;
+            Ok::<_, anyhow::Error>((index, result))
+        });
+    }
+
+    while let Some(wait) = waits.join_next().await {
+        results.push(wait??);
+    }
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+/// Start or message one session, returning whether it was started
+async fn send_to(manager: &mut SessionManager, target: &Target, message: &str) -> Result<bool> {
+    if manager.session_exists(&target.session).await? {
+        manager.send_message(&target.session, message).await?;
+        Ok(false)
+    } else if let Some(dir) = &target.working_dir {
+        info!(
+            "Starting session {} in {} for fan-out",
+            target.session,
+            dir.display()
+        );
+        manager
+            .start_session(
+                Some(message.to_string()),
+                Some(target.session.clone()),
+                Some(dir.clone()),
+                Conversation::New,
+            )
+            .await?;
+        Ok(true)
+    } else {
+        Err(anyhow!("Session not found: {}", target.session))
+    }
+}
+
+fn finish(
+    target: Target,
+    started: bool,
+    elapsed_secs: f64,
+    outcome: Result<CompletionReport>,
+) -> FanoutResult {
+    let result = FanoutResult::new(target, started, elapsed_secs, outcome);
+    info!(
+        "Session {} finished fan-out: {}",
+        result.session, result.status
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::TerminalBackend;
+    use crate::hooks;
+    use crate::testing::FakeBackend;
+
+    #[test]
+    fn targets_cover_sessions_and_matching_directories_once() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in ["svc-a", "svc-b", "other"] {
+            std::fs::create_dir(root.path().join(dir)).unwrap();
+        }
+        std::fs::write(root.path().join("svc-file"), "").unwrap();
+        let pattern = format!("{}/svc-*", root.path().display());
+
+        let targets = resolve_targets(
+            &["web".to_string(), "web".to_string()],
+            &[pattern.clone(), pattern],
+        )
+        .unwrap();
+        let dir = |name: &str| root.path().join(name).canonicalize().unwrap();
+        assert_eq!(
+            targets,
+            [
+                Target {
+                    session: "web".to_string(),
+                    working_dir: None,
+                },
+                Target {
+                    session: generate_directory_session_name(&dir("svc-a")),
+                    working_dir: Some(dir("svc-a")),
+                },
+                Target {
+                    session: generate_directory_session_name(&dir("svc-b")),
+                    working_dir: Some(dir("svc-b")),
+                },
+            ]
+        );
+
+        let nothing = format!("{}/missing-*", root.path().display());
+        assert!(resolve_targets(&[], &[nothing]).is_err());
+    }
+
+    #[test]
+    fn results_map_exit_reasons_to_exit_codes() {
+        let target = Target {
+            session: "api".to_string(),
+            working_dir: None,
+        };
+        let report = |exit_reason| CompletionReport {
+            exit_reason,
+            response: Some("Done".to_string()),
+            ..CompletionReport::not_waited("api")
+        };
+
+        let done = FanoutResult::new(target.clone(), true, 1.0, Ok(report(ExitReason::StopHook)));
+        assert_eq!((done.status, done.exit_code), (FanoutStatus::Done, 0));
+        assert_eq!(done.response.as_deref(), Some("Done"));
+
+        let timeout =
+            FanoutResult::new(target.clone(), false, 1.0, Ok(report(ExitReason::Timeout)));
+        assert_eq!(
+            (timeout.status, timeout.exit_code),
+            (FanoutStatus::Timeout, 2)
+        );
+
+        let failed = FanoutResult::new(target, false, 0.0, Err(anyhow!("Session not found: api")));
+        assert_eq!((failed.status, failed.exit_code), (FanoutStatus::Failed, 1));
+        assert_eq!(failed.error.as_deref(), Some("Session not found: api"));
+        assert_eq!(failed.exit_reason, None);
+    }
+
+    #[tokio::test]
+    async fn every_session_gets_the_prompt_once() {
+        let backend = Arc::new(FakeBackend::with_reply("Done! {prompt}"));
+        let sessions: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|label| format!("claude-fanout-{}-{label}", std::process::id()))
+            .collect();
+        for session in &sessions {
+            backend.create_session(session, None, None).unwrap();
+        }
+        let mut targets = resolve_targets(&sessions, &[]).unwrap();
+        targets.push(Target {
+            session: "claude-fanout-missing".to_string(),
+            working_dir: None,
+        });
+
+        let fake = backend.clone();
+        let results = fan_out_with(
+            move || SessionManager::with_backend(Config::default(), fake.clone()),
+            targets,
+            "run the migration",
+            2,
+            2,
+        )
+        .await
+        .unwrap();
+
+        for (session, result) in sessions.iter().zip(&results) {
+            assert_eq!(backend.sent_to(session), ["run the migration"]);
+            assert_eq!(&result.session, session);
+            assert_eq!(result.status, FanoutStatus::Done, "{result:?}");
+        }
+        assert_eq!(results[3].status, FanoutStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn turns_that_end_before_their_wait_starts_are_not_missed() {
+        let backend = Arc::new(FakeBackend::new().with_stop_hook());
+        let sessions: Vec<String> = ["fast-a", "fast-b"]
+            .iter()
+            .map(|label| format!("claude-fanout-{}-{label}", std::process::id()))
+            .collect();
+        for session in &sessions {
+            backend.create_session(session, None, None).unwrap();
+            hooks::clear_events(session);
+        }
+
+        // Both reply at once; the second waits for the first's permit
+        let fake = backend.clone();
+        let results = fan_out_with(
+            move || SessionManager::with_backend(Config::default(), fake.clone()),
+            resolve_targets(&sessions, &[]).unwrap(),
+            "run the migration",
+            5,
+            1,
+        )
+        .await
+        .unwrap();
+
+        for result in &results {
+            assert_eq!(result.exit_reason, Some(ExitReason::StopHook), "{result:?}");
+            assert!(result.elapsed_secs < 5.0, "{result:?}");
+        }
+    }
+}
@@ -0,0 +1,721 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::backend::shell_quote;
+
+/// Directory where hooks drop completion events
+pub const MARKER_DIR: &str = "/tmp/claude-code-manager";
+
+/// Environment variable overriding the session a hook reports for
+pub const HOOK_SESSION_ENV: &str = "CLAUDE_CODE_MANAGER_SESSION";
+
+/// Claude Code hook events this tool listens to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HookKind {
+    /// The main agent finished its turn
+    Stop,
+    /// A subagent (Task tool) finished; the main agent keeps going
+    SubagentStop,
+    /// Claude Code needs attention, e.g. a permission prompt
+    Notification,
+}
+
+impl HookKind {
+    pub const ALL: [HookKind; 3] = [
+        HookKind::Stop,
+        HookKind::SubagentStop,
+        HookKind::Notification,
+    ];
+
+    /// Event name used as the key in the Claude settings
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            HookKind::Stop => "Stop",
+            HookKind::SubagentStop => "SubagentStop",
+            HookKind::Notification => "Notification",
+        }
+    }
+
+    /// Argument passed to `claude-code-manager hook`
+    fn arg(&self) -> &'static str {
+        match self {
+            HookKind::Stop => "stop",
+            HookKind::SubagentStop => "subagent-stop",
+            HookKind::Notification => "notification",
+        }
+    }
+}
+
+impl std::fmt::Display for HookKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.event_name())
+    }
+}
+
+impl std::str::FromStr for HookKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_lowercase();
+        HookKind::ALL
+            .into_iter()
+            .find(|kind| kind.event_name().to_lowercase() == normalized)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown hook event: {} (expected stop, subagent-stop or notification)",
+                    s
+                )
+            })
+    }
+}
+
+/// One hook invocation, appended as a JSON line to the session's events file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HookEvent {
+    /// Multiplexer session the hook ran in
+    pub session: String,
+    pub hook: HookKind,
+    /// Claude Code's own conversation ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claude_session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript_path: Option<PathBuf>,
+    /// Why the hook fired: `completed`, `subagent_completed`, `permission_prompt`, `idle_prompt`, ...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+    /// Notification text shown by Claude Code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl HookEvent {
+    /// Build an event from the JSON payload Claude Code sends on the hook's stdin
+    pub fn from_payload(session: &str, hook: HookKind, payload: &Value) -> Self {
+        let message = payload["message"].as_str().map(str::to_string);
+
+        let stop_reason = payload["stop_reason"]
+            .as_str()
+            .or_else(|| payload["notification_type"].as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| match hook {
+                HookKind::Stop => "completed".to_string(),
+                HookKind::SubagentStop => "subagent_completed".to_string(),
+                // Older Claude Code versions only send the message text
+                HookKind::Notification => {
+                    if message
+                        .as_deref()
+                        .is_some_and(|m| m.to_lowercase().contains("permission"))
+                    {
+                        "permission_prompt".to_string()
+                    } else {
+                        "idle_prompt".to_string()
+                    }
+                }
+            });
+
+        Self {
+            session: session.to_string(),
+            hook,
+            claude_session_id: payload["session_id"].as_str().map(str::to_string),
+            transcript_path: payload["transcript_path"].as_str().map(PathBuf::from),
+            stop_reason: Some(stop_reason),
+            message,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Whether Claude Code is blocked waiting for the user to allow a tool
+    pub fn is_permission_request(&self) -> bool {
+        self.hook == HookKind::Notification
+            && self.stop_reason.as_deref() == Some("permission_prompt")
+    }
+}
+
+/// JSON lines file the hooks append events to
+pub fn events_path(session_name: &str) -> PathBuf {
+    Path::new(MARKER_DIR).join(format!("{session_name}.events.jsonl"))
+}
+
+/// Marker written by the hand-written Stop hook from older READMEs
+pub fn completion_marker_path(session_name: &str) -> PathBuf {
+    Path::new(MARKER_DIR).join(format!("{session_name}.done"))
+}
+
+pub fn append_event(event: &HookEvent) -> Result<()> {
+    std::fs::create_dir_all(MARKER_DIR)?;
+    let path = events_path(&event.session);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    // One write per line so concurrent hooks never interleave
+    file.write_all(format!("{}\n", serde_json::to_string(event)?).as_bytes())?;
+    debug!("Appended {} event to: {}", event.hook, path.display());
+    Ok(())
+}
+
+/// All events recorded for a session since they were last cleared
+pub fn read_events(session_name: &str) -> Vec<HookEvent> {
+    let Ok(content) = std::fs::read_to_string(events_path(session_name)) else {
+        return Vec::new();
+    };
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                warn!("Skipping malformed hook event: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Forget recorded events (and any legacy marker) before waiting for a new turn
+pub fn clear_events(session_name: &str) {
+    let _ = std::fs::remove_file(events_path(session_name));
+    let _ = std::fs::remove_file(completion_marker_path(session_name));
+}
+
+pub fn default_settings_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".claude").join("settings.json")
+}
+
+/// Shell command Claude Code runs for `event`, pointing back at this binary
+pub fn hook_command(event: HookKind) -> Result<String> {
+    let exe = std::env::current_exe().context("Failed to locate the claude-code-manager binary")?;
+    let inner = format!(
+        "{} hook {}",
+        shell_quote(&exe.to_string_lossy()),
+        event.arg()
+    );
+    // Explicit bash wrapper avoids "spawn /bin/sh ENOENT" inside tmux sessions
+    Ok(format!("/bin/bash -c {}", shell_quote(&inner)))
+}
+
+/// Stop hook the README used to have users paste into their settings by hand
+const LEGACY_STOP_COMMAND: &str = r##"/bin/bash -c 'mkdir -p /tmp/claude-code-manager && echo "$(date -Iseconds)" > "/tmp/claude-code-manager/$(tmux display-message -p "#{session_name}" 2>/dev/null || echo "unknown").done"'"##;
+
+/// Whether `command` is the hook this tool installs for `event` (or, for
+/// the Stop hook, the README one-liner it replaces)
+fn is_manager_hook(event: HookKind, command: &str) -> bool {
+    hook_command(event).is_ok_and(|c| c == command)
+        || (event == HookKind::Stop && command == LEGACY_STOP_COMMAND)
+}
+
+/// Whether `command` is this tool's hook for `event` as installed from a
+/// binary at another path
+fn is_moved_manager_hook(event: HookKind, command: &str) -> bool {
+    command.starts_with("/bin/bash -c '")
+        && command.ends_with(&format!(" hook {}'", event.arg()))
+        && !is_manager_hook(event, command)
+}
+
+fn hook_commands(entry: &Value) -> Vec<&str> {
+    entry["hooks"]
+        .as_array()
+        .map(|hooks| hooks.iter().filter_map(|h| h["command"].as_str()).collect())
+        .unwrap_or_default()
+}
+
+fn load_settings(path: &Path) -> Result<Value> {
+    if !path.exists() {
+        return Ok(json!({}));
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    if content.trim().is_empty() {
+        return Ok(json!({}));
+    }
+
+    let settings: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse Claude settings: {}", path.display()))?;
+    if !settings.is_object() {
+        return Err(anyhow!(
+            "Claude settings is not a JSON object: {}",
+            path.display()
+        ));
+    }
+    Ok(settings)
+}
+
+fn save_settings(path: &Path, settings: &Value) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Keep a copy of the previous settings in case the user wants to roll back
+    if path.exists() {
+        std::fs::copy(path, path.with_extension("json.bak"))?;
+    }
+
+    std::fs::write(path, serde_json::to_string_pretty(settings)?)?;
+    Ok(())
+}
+
+/// Drop this tool's hooks from the entries of `event`, and any entry they
+/// leave without hooks, returning how many hooks were removed
+fn remove_manager_hooks(event: HookKind, entries: &mut Vec<Value>) -> usize {
+    let mut removed = 0;
+    entries.retain_mut(|entry| {
+        let Some(hooks) = entry.get_mut("hooks").and_then(Value::as_array_mut) else {
+            return true;
+        };
+        let before = hooks.len();
+        hooks.retain(|hook| {
+            !hook["command"]
+                .as_str()
+                .is_some_and(|c| is_manager_hook(event, c))
+        });
+        removed += before - hooks.len();
+        !hooks.is_empty() || hooks.len() == before
+    });
+    removed
+}
+
+/// Merge the completion hooks into the Claude settings, leaving other hooks untouched
+pub fn install(settings_path: &Path) -> Result<()> {
+    let mut settings = load_settings(settings_path)?;
+
+    let hooks = settings
+        .as_object_mut()
+        .unwrap()
+        .entry("hooks")
+        .or_insert_with(|| json!({}));
+    let hooks = hooks
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("\"hooks\" in Claude settings is not an object"))?;
+
+    for event in HookKind::ALL {
+        let entries = hooks.entry(event.event_name()).or_insert_with(|| json!([]));
+        let entries = entries
+            .as_array_mut()
+            .ok_or_else(|| anyhow!("\"hooks.{}\" in Claude settings is not an array", event))?;
+
+        let replaced = remove_manager_hooks(event, entries);
+        if replaced > 0 {
+            debug!("Replacing {} existing {} hook(s)", replaced, event);
+        }
+
+        entries.push(json!({
+            "matcher": "",
+            "hooks": [{ "type": "command", "command": hook_command(event)? }]
+        }));
+    }
+
+    save_settings(settings_path, &settings)?;
+    info!("Installed hooks into: {}", settings_path.display());
+    Ok(())
+}
+
+/// Remove this tool's hooks from the Claude settings, returning how many were removed
+pub fn uninstall(settings_path: &Path) -> Result<usize> {
+    if !settings_path.exists() {
+        return Ok(0);
+    }
+
+    let mut settings = load_settings(settings_path)?;
+    let mut removed = 0;
+
+    if let Some(hooks) = settings["hooks"].as_object_mut() {
+        for event in HookKind::ALL {
+            let name = event.event_name();
+            if let Some(entries) = hooks.get_mut(name).and_then(Value::as_array_mut) {
+                removed += remove_manager_hooks(event, entries);
+                if entries.is_empty() {
+                    hooks.remove(name);
+                }
+            }
+        }
+
+        if hooks.is_empty() {
+            settings.as_object_mut().unwrap().remove("hooks");
+        }
+    }
+
+    if removed > 0 {
+        save_settings(settings_path, &settings)?;
+        info!(
+            "Removed {} hook(s) from: {}",
+            removed,
+            settings_path.display()
+        );
+    }
+
+    Ok(removed)
+}
+
+/// Name of the multiplexer session the current process runs in
+fn current_session_name() -> Option<String> {
+    if let Ok(session) = std::env::var(HOOK_SESSION_ENV) {
+        return Some(session).filter(|s| !s.is_empty());
+    }
+
+    if std::env::var_os("TMUX").is_some() {
+        let output = Command::new("tmux")
+            .args(["display-message", "-p", "#{session_name}"])
+            .output()
+            .ok()?;
+        let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        return Some(name).filter(|n| !n.is_empty());
+    }
+
+    // GNU screen exports STY as "<pid>.<session name>"
+    std::env::var("STY")
+        .ok()
+        .and_then(|sty| sty.split_once('.').map(|(_, name)| name.to_string()))
+}
+
+/// Entry point for `claude-code-manager hook <event>`, run by Claude Code itself
+pub fn run_hook(event: &str) -> Result<()> {
+    let hook: HookKind = event.parse()?;
+
+    // Claude Code sends the hook payload on stdin; drain it so it never blocks
+    let mut payload = String::new();
+    let _ = std::io::Read::read_to_string(&mut std::io::stdin(), &mut payload);
+    debug!("Received {} hook payload: {}", hook, payload);
+
+    let Some(session_name) = current_session_name() else {
+        debug!(
+            "Not running inside a managed session, ignoring {} hook",
+            hook
+        );
+        return Ok(());
+    };
+
+    // A malformed or empty payload still records the event itself
+    let payload: Value = serde_json::from_str(&payload).unwrap_or_else(|_| json!({}));
+    append_event(&HookEvent::from_payload(&session_name, hook, &payload))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl DoctorCheck {
+    fn new(name: &str, ok: bool, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            ok,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Verify the hooks are installed and that running them records an event
+pub fn doctor(settings_path: &Path) -> Vec<DoctorCheck> {
+    let mut checks = Vec::new();
+
+    let settings = match load_settings(settings_path) {
+        Ok(settings) => {
+            checks.push(DoctorCheck::new(
+                "settings",
+                true,
+                format!("{}", settings_path.display()),
+            ));
+            settings
+        }
+        Err(e) => {
+            checks.push(DoctorCheck::new("settings", false, e.to_string()));
+            return checks;
+        }
+    };
+
+    for event in HookKind::ALL {
+        let all_commands: Vec<&str> = settings["hooks"][event.event_name()]
+            .as_array()
+            .map(|entries| entries.iter().flat_map(hook_commands).collect())
+            .unwrap_or_default();
+
+        // Install leaves hooks it did not write alone, including its own
+        // from before the binary moved
+        for stale in all_commands
+            .iter()
+            .filter(|c| is_moved_manager_hook(event, c))
+        {
+            checks.push(DoctorCheck::new(
+                &format!("{event} hook path"),
+                false,
+                format!(
+                    "{stale} points at an old binary (remove it from {})",
+                    settings_path.display()
+                ),
+            ));
+        }
+
+        let commands: Vec<String> = all_commands
+            .iter()
+            .filter(|c| is_manager_hook(event, c))
+            .map(|c| c.to_string())
+            .collect();
+
+        let Some(command) = commands.first() else {
+            checks.push(DoctorCheck::new(
+                &format!("{event} hook"),
+                false,
+                "not installed (run: claude-code-manager hooks install)",
+            ));
+            continue;
+        };
+
+        if command == LEGACY_STOP_COMMAND {
+            checks.push(DoctorCheck::new(
+                &format!("{event} hook"),
+                false,
+                "hand-pasted README hook (run: claude-code-manager hooks install)",
+            ));
+        } else {
+            checks.push(DoctorCheck::new(&format!("{event} hook"), true, command));
+        }
+
+        if commands.len() > 1 {
+            checks.push(DoctorCheck::new(
+                &format!("{event} hook duplicates"),
+                false,
+                format!("{} entries installed, expected 1", commands.len()),
+            ));
+        }
+
+        checks.push(run_hook_probe(event, command));
+    }
+
+    checks
+}
+
+/// Run an installed hook command the way Claude Code would and look for its event
+fn run_hook_probe(event: HookKind, command: &str) -> DoctorCheck {
+    let name = format!("{event} event");
+    let probe_session = format!("claude-doctor-{}", std::process::id());
+    clear_events(&probe_session);
+
+    let result = Command::new("/bin/bash")
+        .args(["-c", command])
+        .env(HOOK_SESSION_ENV, &probe_session)
+        .stdin(std::process::Stdio::null())
+        .output();
+
+    let check = match result {
+        Ok(output) if !output.status.success() => DoctorCheck::new(
+            &name,
+            false,
+            format!(
+                "hook exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ),
+        Err(e) => DoctorCheck::new(&name, false, format!("failed to run hook: {e}")),
+        Ok(_) => {
+            if read_events(&probe_session).iter().any(|e| e.hook == event) {
+                DoctorCheck::new(&name, true, format!("recorded in {MARKER_DIR}"))
+            } else if completion_marker_path(&probe_session).exists() {
+                DoctorCheck::new(
+                    &name,
+                    false,
+                    "hook wrote a legacy .done marker (run: claude-code-manager hooks install)",
+                )
+            } else {
+                warn!("Hook ran but recorded no event for {}", probe_session);
+                DoctorCheck::new(
+                    &name,
+                    false,
+                    format!("hook ran but recorded no event in {MARKER_DIR}"),
+                )
+            }
+        }
+    };
+
+    clear_events(&probe_session);
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn install_keeps_unrelated_hooks_and_is_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "model": "opus",
+                "hooks": {
+                    "Stop": [{ "matcher": "", "hooks": [{ "type": "command", "command": "notify-send done" }] }],
+                    "PreToolUse": [{ "matcher": "Bash", "hooks": [{ "type": "command", "command": "audit" }] }]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        install(&path).unwrap();
+        install(&path).unwrap();
+
+        let settings = load_settings(&path).unwrap();
+        assert_eq!(settings["model"], "opus");
+        assert_eq!(
+            settings["hooks"]["PreToolUse"][0]["hooks"][0]["command"],
+            "audit"
+        );
+
+        let stop = settings["hooks"]["Stop"].as_array().unwrap();
+        assert_eq!(stop.len(), 2);
+        assert_eq!(stop[0]["hooks"][0]["command"], "notify-send done");
+        assert_eq!(
+            stop[1]["hooks"][0]["command"],
+            hook_command(HookKind::Stop).unwrap()
+        );
+    }
+
+    #[test]
+    fn install_replaces_the_readme_one_liner_and_uninstall_removes_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        let settings = json!({
+            "hooks": { "Stop": [{ "matcher": "", "hooks": [
+                { "type": "command", "command": LEGACY_STOP_COMMAND }
+            ] }] }
+        });
+        std::fs::write(&path, settings.to_string()).unwrap();
+
+        install(&path).unwrap();
+        let settings = load_settings(&path).unwrap();
+        assert_eq!(settings["hooks"]["Stop"].as_array().unwrap().len(), 1);
+
+        assert_eq!(uninstall(&path).unwrap(), HookKind::ALL.len());
+        let settings = load_settings(&path).unwrap();
+        assert!(settings.get("hooks").is_none());
+    }
+
+    #[test]
+    fn uninstall_keeps_other_hooks_sharing_an_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        let stop = hook_command(HookKind::Stop).unwrap();
+        let settings = json!({
+            "hooks": { "Stop": [{ "matcher": "", "hooks": [
+                { "type": "command", "command": "notify-send done" },
+                { "type": "command", "command": stop },
+                { "type": "command", "command": "~/bin/claude-code-manager-stats" }
+            ] }] }
+        });
+        std::fs::write(&path, settings.to_string()).unwrap();
+
+        assert_eq!(uninstall(&path).unwrap(), 1);
+        let settings = load_settings(&path).unwrap();
+        let hooks = settings["hooks"]["Stop"][0]["hooks"].as_array().unwrap();
+        let commands: Vec<&str> = hooks.iter().filter_map(|h| h["command"].as_str()).collect();
+        assert_eq!(
+            commands,
+            ["notify-send done", "~/bin/claude-code-manager-stats"]
+        );
+
+        // Installing again adds its own entry beside the shared one
+        install(&path).unwrap();
+        let settings = load_settings(&path).unwrap();
+        let entries = settings["hooks"]["Stop"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["hooks"].as_array().unwrap().len(), 2);
+        assert_eq!(entries[1]["hooks"][0]["command"], stop);
+    }
+
+    #[test]
+    fn notification_payloads_distinguish_permission_prompts() {
+        let permission = HookEvent::from_payload(
+            "claude-test",
+            HookKind::Notification,
+            &json!({
+                "session_id": "abc123",
+                "transcript_path": "/home/me/.claude/projects/-repo/abc123.jsonl",
+                "message": "Claude needs your permission to use Bash"
+            }),
+        );
+        assert!(permission.is_permission_request());
+        assert_eq!(permission.claude_session_id.as_deref(), Some("abc123"));
+
+        let idle = HookEvent::from_payload(
+            "claude-test",
+            HookKind::Notification,
+            &json!({ "message": "Claude is waiting for your input" }),
+        );
+        assert!(!idle.is_permission_request());
+        assert_eq!(idle.stop_reason.as_deref(), Some("idle_prompt"));
+
+        let stop = HookEvent::from_payload("claude-test", HookKind::Stop, &json!({}));
+        assert_eq!(stop.stop_reason.as_deref(), Some("completed"));
+    }
+
+    #[test]
+    fn events_round_trip_through_the_events_file() {
+        let session = format!("claude-unit-{}-events", std::process::id());
+        clear_events(&session);
+
+        let event = HookEvent::from_payload(&session, HookKind::SubagentStop, &json!({}));
+        append_event(&event).unwrap();
+        append_event(&HookEvent::from_payload(
+            &session,
+            HookKind::Stop,
+            &json!({}),
+        ))
+        .unwrap();
+
+        let events = read_events(&session);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], event);
+        assert_eq!(events[1].hook, HookKind::Stop);
+
+        clear_events(&session);
+        assert!(read_events(&session).is_empty());
+    }
+
+    #[test]
+    fn hook_kinds_parse_from_cli_and_settings_names() {
+        assert_eq!("stop".parse::<HookKind>().unwrap(), HookKind::Stop);
+        assert_eq!(
+            "subagent-stop".parse::<HookKind>().unwrap(),
+            HookKind::SubagentStop
+        );
+        assert_eq!(
+            "Notification".parse::<HookKind>().unwrap(),
+            HookKind::Notification
+        );
+        assert!("pre-tool-use".parse::<HookKind>().is_err());
+    }
+
+    #[test]
+    fn hooks_from_a_moved_binary_are_told_apart_from_others() {
+        let stop = hook_command(HookKind::Stop).unwrap();
+        let moved = "/bin/bash -c '/old/claude-code-manager hook stop'";
+        assert!(is_moved_manager_hook(HookKind::Stop, moved));
+        assert!(!is_moved_manager_hook(HookKind::Notification, moved));
+        assert!(!is_moved_manager_hook(HookKind::Stop, &stop));
+        assert!(!is_moved_manager_hook(HookKind::Stop, "notify-send done"));
+        assert!(!is_manager_hook(HookKind::Stop, moved));
+    }
+
+    #[test]
+    fn doctor_reports_missing_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let checks = doctor(&dir.path().join("settings.json"));
+
+        assert!(checks.iter().any(|c| c.name == "Stop hook" && !c.ok));
+    }
+}
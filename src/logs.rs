@@ -0,0 +1,665 @@
+//! Session log files: one log per run, their rotation and retention.
+//!
+//! Each time a session is created its output goes to a new timestamped log
+//! in `logs/<session>/`, next to a JSON file describing the run. Logs from
+//! before per-run logging, `logs/<session>.log`, count as a session's first run.
+//!
+//! Logs are written by the terminal backend through an append-mode handle
+//! that stays open for the life of the session, so they are rotated by
+//! copying the content into a gzip archive and truncating the file in place.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info};
+
+use crate::terminal;
+
+/// Limits applied to the logs in the log directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogPolicy {
+    /// Size in bytes above which a log is rotated
+    pub max_size: u64,
+    /// Rotated archives kept per run log
+    pub keep: usize,
+    /// Days after which logs of sessions that are gone are deleted (0 keeps them)
+    pub retention_days: u64,
+}
+
+/// Something `prune` did, or would do with `dry_run`, to a log file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogAction {
+    pub action: LogActionKind,
+    pub session: String,
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogActionKind {
+    Rotated,
+    Removed,
+}
+
+impl std::fmt::Display for LogActionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogActionKind::Rotated => write!(f, "rotated"),
+            LogActionKind::Removed => write!(f, "removed"),
+        }
+    }
+}
+
+/// A file in the log directory: a run's log or one of its archives
+struct LogFile {
+    session: String,
+    /// `None` for the log itself, the rotation index for `<run>.log.<n>.gz`
+    archive: Option<usize>,
+    /// Whether this is the log of the session's latest run
+    current: bool,
+    path: PathBuf,
+    bytes: u64,
+    modified: SystemTime,
+}
+
+/// What is known about one run of a session, stored next to its log
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunMetadata {
+    pub session: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub working_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub command: Vec<String>,
+    pub initial_message: Option<String>,
+    pub conversation_id: Option<String>,
+}
+
+/// A session run's log, numbered from 1 for the oldest run
+#[derive(Debug, Clone, Serialize)]
+pub struct RunLog {
+    pub run: usize,
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub metadata: RunMetadata,
+}
+
+const RUN_TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3f";
+
+pub fn log_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home)
+        .join(".claude-code-manager")
+        .join("logs")
+}
+
+/// Create the empty log for a run of `session` starting now
+pub fn new_run_log(session_name: &str) -> Result<PathBuf> {
+    let dir = log_dir().join(session_name);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}Z.log", Utc::now().format(RUN_TIMESTAMP_FORMAT)));
+    OpenOptions::new().create(true).append(true).open(&path)?;
+    Ok(path)
+}
+
+/// Names of the sessions that have logs
+pub fn logged_sessions() -> Result<Vec<String>> {
+    let mut sessions: Vec<String> = read_dir_if_exists(&log_dir())?
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            if path.is_dir() {
+                Some(name)
+            } else {
+                name.strip_suffix(".log").map(str::to_string)
+            }
+        })
+        .collect();
+    sessions.sort();
+    sessions.dedup();
+    Ok(sessions)
+}
+
+/// Every run of `session` with a log, oldest first
+pub fn runs(session_name: &str) -> Result<Vec<RunLog>> {
+    runs_in(&log_dir(), session_name)
+}
+
+fn runs_in(dir: &Path, session_name: &str) -> Result<Vec<RunLog>> {
+    let mut paths = Vec::new();
+
+    let legacy = dir.join(format!("{session_name}.log"));
+    if legacy.is_file() {
+        paths.push(legacy);
+    }
+
+    let mut run_paths: Vec<PathBuf> = read_dir_if_exists(&dir.join(session_name))?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+    // Timestamps in the names sort chronologically
+    run_paths.sort();
+    paths.extend(run_paths);
+
+    Ok(paths
+        .into_iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let metadata = read_run_metadata(&path).unwrap_or_else(|| RunMetadata {
+                session: session_name.to_string(),
+                started_at: run_started_at(&path),
+                ..RunMetadata::default()
+            });
+            RunLog {
+                run: index + 1,
+                path,
+                metadata,
+            }
+        })
+        .collect())
+}
+
+/// Run `number` of `session`, counting from 1 for the oldest
+pub fn run(session_name: &str, number: usize) -> Result<RunLog> {
+    let mut runs = runs(session_name)?;
+    let count = runs.len();
+    if number == 0 || number > count {
+        return Err(anyhow!(
+            "Session {} has no run {} ({} run(s) logged)",
+            session_name,
+            number,
+            count
+        ));
+    }
+    Ok(runs.swap_remove(number - 1))
+}
+
+/// Log of the session's latest run
+pub fn current_run_log(session_name: &str) -> Option<PathBuf> {
+    runs(session_name).ok()?.pop().map(|run| run.path)
+}
+
+/// Change the metadata of the session's latest run, if it has a log
+pub fn update_current_run(session_name: &str, update: impl FnOnce(&mut RunMetadata)) -> Result<()> {
+    let Some(run) = runs(session_name)?.pop() else {
+        return Ok(());
+    };
+    let mut metadata = run.metadata.clone();
+    update(&mut metadata);
+    if metadata == run.metadata && metadata_path(&run.path).exists() {
+        return Ok(());
+    }
+    fs::write(
+        metadata_path(&run.path),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+    Ok(())
+}
+
+fn metadata_path(log: &Path) -> PathBuf {
+    log.with_extension("json")
+}
+
+fn read_run_metadata(log: &Path) -> Option<RunMetadata> {
+    let content = fs::read_to_string(metadata_path(log)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Start time encoded in a run log's name
+fn run_started_at(log: &Path) -> Option<DateTime<Utc>> {
+    let stem = log.file_stem()?.to_str()?.strip_suffix('Z')?;
+    let started = NaiveDateTime::parse_from_str(stem, RUN_TIMESTAMP_FORMAT).ok()?;
+    Some(started.and_utc())
+}
+
+/// The log at `path` replayed as a terminal `rows` high showed it, keeping
+/// the last `lines` lines
+pub fn render(path: &Path, rows: u16, lines: Option<usize>) -> Result<String> {
+    let bytes = read_with_archives(path)?;
+    let rendered = terminal::replay(&bytes, rows);
+
+    Ok(match lines {
+        Some(lines) => {
+            let all: Vec<&str> = rendered.lines().collect();
+            all[all.len().saturating_sub(lines)..].join("\n")
+        }
+        None => rendered,
+    })
+}
+
+/// Everything written to `log`: its rotated archives, oldest first, then
+/// what the log holds now
+pub fn read_with_archives(log: &Path) -> Result<Vec<u8>> {
+    let archives: Vec<PathBuf> = (1..)
+        .map(|index| archive_path(log, index))
+        .take_while(|archive| archive.exists())
+        .collect();
+
+    let mut bytes = Vec::new();
+    for archive in archives.iter().rev() {
+        GzDecoder::new(File::open(archive)?).read_to_end(&mut bytes)?;
+    }
+    bytes.extend(fs::read(log)?);
+    Ok(bytes)
+}
+
+/// Path of the `index`-th rotated archive of `log`, 1 being the newest
+pub fn archive_path(log: &Path, index: usize) -> PathBuf {
+    let mut name = log.as_os_str().to_owned();
+    name.push(format!(".{index}.gz"));
+    PathBuf::from(name)
+}
+
+/// Compress `log` into its first archive, shifting older archives up and
+/// dropping those past `keep`, then empty the log
+pub fn rotate(log: &Path, keep: usize) -> Result<()> {
+    for index in (1..=keep).rev() {
+        let archive = archive_path(log, index);
+        if !archive.exists() {
+            continue;
+        }
+        if index == keep {
+            fs::remove_file(&archive)?;
+        } else {
+            fs::rename(&archive, archive_path(log, index + 1))?;
+        }
+    }
+
+    if keep > 0 {
+        let mut source = File::open(log)?;
+        let mut encoder =
+            GzEncoder::new(File::create(archive_path(log, 1))?, Compression::default());
+        io::copy(&mut source, &mut encoder)?;
+        encoder.finish()?;
+    }
+
+    // Anything written between the copy and this truncation is lost; the
+    // writer's append handle carries on at the start of the emptied file
+    OpenOptions::new().write(true).open(log)?.set_len(0)?;
+
+    info!("Rotated log file: {}", log.display());
+    Ok(())
+}
+
+/// Rotate `log` if it has grown past the policy's size limit
+pub fn rotate_if_oversized(log: &Path, policy: &LogPolicy) -> Result<bool> {
+    match fs::metadata(log) {
+        Ok(metadata) if metadata.len() > policy.max_size => {
+            rotate(log, policy.keep)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Take the lock held while logs in `dir` are rotated or pruned, so the
+/// daemon and a command run by hand never rotate the same log at once
+fn lock_dir(dir: &Path) -> Result<File> {
+    let lock = crate::open_lock(&dir.join("rotate.lock"))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+/// Rotate every oversized log in `dir`
+pub fn rotate_oversized(dir: &Path, policy: &LogPolicy) -> Result<Vec<LogAction>> {
+    let _lock = lock_dir(dir)?;
+    let mut actions = Vec::new();
+    for file in scan(dir)? {
+        if file.archive.is_none() && rotate_if_oversized(&file.path, policy)? {
+            actions.push(LogAction {
+                action: LogActionKind::Rotated,
+                session: file.session,
+                path: file.path,
+                bytes: file.bytes,
+                reason: format!("larger than {}", format_size(policy.max_size)),
+            });
+        }
+    }
+    Ok(actions)
+}
+
+/// Rotate oversized logs, delete archives past the keep count, and delete the
+/// logs of sessions that are no longer live once they are older than the
+/// retention period. With `dry_run` nothing is changed.
+pub fn prune(
+    dir: &Path,
+    policy: &LogPolicy,
+    is_live: impl Fn(&str) -> bool,
+    now: SystemTime,
+    dry_run: bool,
+) -> Result<Vec<LogAction>> {
+    let _lock = lock_dir(dir)?;
+    let retention = (policy.retention_days > 0)
+        .then(|| Duration::from_secs(policy.retention_days * 24 * 60 * 60));
+    let mut actions = Vec::new();
+
+    // Live logs first: rotating one shifts its archives, so those are only
+    // listed once the rotations are done
+    for archives in [false, true] {
+        for file in scan(dir)? {
+            if file.archive.is_some() != archives {
+                continue;
+            }
+            let age = now.duration_since(file.modified).unwrap_or_default();
+            let expired = retention.is_some_and(|retention| age > retention);
+
+            let (action, reason) = match file.archive {
+                Some(index) if index > policy.keep => (
+                    LogActionKind::Removed,
+                    format!("more than {} rotated logs", policy.keep),
+                ),
+                Some(_) if expired => (
+                    LogActionKind::Removed,
+                    format!("older than {} days", policy.retention_days),
+                ),
+                None if expired && !(file.current && is_live(&file.session)) => (
+                    LogActionKind::Removed,
+                    format!("run ended more than {} days ago", policy.retention_days),
+                ),
+                None if file.bytes > policy.max_size => (
+                    LogActionKind::Rotated,
+                    format!("larger than {}", format_size(policy.max_size)),
+                ),
+                _ => continue,
+            };
+
+            if !dry_run {
+                match action {
+                    LogActionKind::Removed => {
+                        fs::remove_file(&file.path)?;
+                        if file.archive.is_none() {
+                            let _ = fs::remove_file(metadata_path(&file.path));
+                        }
+                    }
+                    LogActionKind::Rotated => rotate(&file.path, policy.keep)?,
+                }
+            }
+            debug!("{} {}: {}", action, file.path.display(), reason);
+
+            actions.push(LogAction {
+                action,
+                session: file.session,
+                path: file.path,
+                bytes: file.bytes,
+                reason,
+            });
+        }
+    }
+
+    if !dry_run {
+        // Drop the directories of sessions whose runs are all gone
+        for path in read_dir_if_exists(dir)? {
+            if path.is_dir() && read_dir_if_exists(&path)?.is_empty() {
+                let _ = fs::remove_dir(&path);
+            }
+        }
+    }
+
+    Ok(actions)
+}
+
+/// Session logs and archives in `dir`, sorted by path
+fn scan(dir: &Path) -> Result<Vec<LogFile>> {
+    let mut files = Vec::new();
+
+    for path in read_dir_if_exists(dir)? {
+        let Some(name) = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+
+        if path.is_dir() {
+            let mut run_files = Vec::new();
+            for run_path in read_dir_if_exists(&path)? {
+                let run_name = run_path.file_name().unwrap_or_default().to_string_lossy();
+                if let Some((_, archive)) = parse_log_name(&run_name) {
+                    run_files.push(log_file(&run_path, &name, archive, false)?);
+                }
+            }
+            // The session writes to the newest run's log
+            if let Some(current) = run_files
+                .iter_mut()
+                .filter(|file| file.archive.is_none())
+                .max_by(|a, b| a.path.cmp(&b.path))
+            {
+                current.current = true;
+            }
+            files.extend(run_files);
+        } else if let Some((session, archive)) = parse_log_name(&name) {
+            // A log from before per-run logging is current until the next run
+            let current = archive.is_none() && !dir.join(&session).is_dir();
+            files.push(log_file(&path, &session, archive, current)?);
+        }
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn log_file(path: &Path, session: &str, archive: Option<usize>, current: bool) -> Result<LogFile> {
+    let metadata = fs::metadata(path)?;
+    Ok(LogFile {
+        session: session.to_string(),
+        archive,
+        current,
+        path: path.to_path_buf(),
+        bytes: metadata.len(),
+        modified: metadata.modified()?,
+    })
+}
+
+fn read_dir_if_exists(dir: &Path) -> Result<Vec<PathBuf>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<_>>()?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Split `<name>.log` or `<name>.log.<n>.gz` into the name and archive index
+fn parse_log_name(name: &str) -> Option<(String, Option<usize>)> {
+    if let Some(stem) = name.strip_suffix(".log") {
+        return Some((stem.to_string(), None));
+    }
+    let rest = name.strip_suffix(".gz")?;
+    let (log, index) = rest.rsplit_once('.')?;
+    let stem = log.strip_suffix(".log")?;
+    Some((stem.to_string(), Some(index.parse().ok()?)))
+}
+
+/// Parse a size such as `50M`, `1.5GB`, `512k` or a plain number of bytes
+pub fn parse_size(value: &str) -> Result<u64> {
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => {
+            return Err(anyhow!(
+                "Invalid size '{}'. Use e.g. 50M, 1G or 1048576",
+                value
+            ))
+        }
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid size '{}'. Use e.g. 50M, 1G or 1048576", value))?;
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> LogPolicy {
+        LogPolicy {
+            max_size: 10,
+            keep: 2,
+            retention_days: 7,
+        }
+    }
+
+    fn gunzip(path: &Path) -> String {
+        let mut text = String::new();
+        GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn rotation_compresses_shifts_and_caps_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("claude-demo.log");
+
+        for content in ["first run!", "second run", "third run!"] {
+            fs::write(&log, content).unwrap();
+            rotate(&log, 2).unwrap();
+        }
+
+        assert_eq!(fs::metadata(&log).unwrap().len(), 0);
+        assert_eq!(gunzip(&archive_path(&log, 1)), "third run!");
+        assert_eq!(gunzip(&archive_path(&log, 2)), "second run");
+        assert!(!archive_path(&log, 3).exists());
+    }
+
+    #[test]
+    fn prune_applies_size_count_and_age_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(path("claude-live.log"), "more than ten bytes").unwrap();
+        fs::write(path("claude-live.log.3.gz"), "").unwrap();
+        fs::write(path("claude-gone.log"), "old").unwrap();
+        fs::write(path("claude-gone.log.1.gz"), "").unwrap();
+        fs::write(path("notes.txt"), "not a log").unwrap();
+        fs::create_dir(path("claude-runs")).unwrap();
+        fs::write(path("claude-runs/20250101T090000.000Z.log"), "old run").unwrap();
+        fs::write(path("claude-runs/20250101T090000.000Z.json"), "{}").unwrap();
+        fs::write(path("claude-runs/20250102T090000.000Z.log"), "running").unwrap();
+
+        // Everything was last written two weeks ago
+        let two_weeks_ago = SystemTime::now() - Duration::from_secs(14 * 24 * 60 * 60);
+        for file in scan(dir.path()).unwrap() {
+            File::options()
+                .write(true)
+                .open(&file.path)
+                .unwrap()
+                .set_modified(two_weeks_ago)
+                .unwrap();
+        }
+        let is_live = |session: &str| session == "claude-live" || session == "claude-runs";
+        let now = SystemTime::now();
+
+        let planned = prune(dir.path(), &policy(), is_live, now, true).unwrap();
+        assert_eq!(planned.len(), 5);
+        assert!(path("claude-gone.log").exists());
+
+        prune(dir.path(), &policy(), is_live, now, false).unwrap();
+        let mut remaining: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            [
+                "claude-live.log",
+                "claude-live.log.1.gz",
+                "claude-runs",
+                "notes.txt",
+                "rotate.lock"
+            ]
+        );
+        // Only the run the live session is still writing to survives
+        assert_eq!(fs::read_dir(path("claude-runs")).unwrap().count(), 1);
+        assert!(path("claude-runs/20250102T090000.000Z.log").exists());
+        assert_eq!(gunzip(&path("claude-live.log.1.gz")), "more than ten bytes");
+    }
+
+    #[test]
+    fn runs_are_numbered_oldest_first_after_the_legacy_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        fs::write(path("claude-demo.log"), "before runs").unwrap();
+        fs::create_dir(path("claude-demo")).unwrap();
+        fs::write(path("claude-demo/20250102T090000.000Z.log"), "").unwrap();
+        fs::write(path("claude-demo/20250101T090000.000Z.log"), "").unwrap();
+        fs::write(path("claude-demo/20250101T090000.000Z.log.1.gz"), "").unwrap();
+        let metadata = RunMetadata {
+            session: "claude-demo".to_string(),
+            conversation_id: Some("abc-123".to_string()),
+            ..RunMetadata::default()
+        };
+        fs::write(
+            path("claude-demo/20250102T090000.000Z.json"),
+            serde_json::to_string(&metadata).unwrap(),
+        )
+        .unwrap();
+
+        let runs = runs_in(dir.path(), "claude-demo").unwrap();
+        let names: Vec<_> = runs
+            .iter()
+            .map(|run| run.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "claude-demo.log",
+                "20250101T090000.000Z.log",
+                "20250102T090000.000Z.log"
+            ]
+        );
+        assert_eq!(runs[0].metadata.started_at, None);
+        assert_eq!(
+            runs[1].metadata.started_at,
+            "2025-01-01T09:00:00Z".parse().ok()
+        );
+        assert_eq!(runs[2].run, 3);
+        assert_eq!(runs[2].metadata.conversation_id.as_deref(), Some("abc-123"));
+    }
+
+    #[test]
+    fn sizes_accept_units() {
+        assert_eq!(parse_size("1048576").unwrap(), 1 << 20);
+        assert_eq!(parse_size("50M").unwrap(), 50 << 20);
+        assert_eq!(parse_size("1.5gb").unwrap(), 3 << 29);
+        assert_eq!(parse_size("512 KiB").unwrap(), 512 << 10);
+        assert!(parse_size("lots").is_err());
+        assert_eq!(format_size(50 << 20), "50.0 MB");
+        assert_eq!(format_size(12), "12 B");
+    }
+}
@@ -1,12 +1,41 @@
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+mod backend;
 mod claude;
+mod daemon;
+mod dashboard;
+mod export;
+mod fanout;
+mod hooks;
+mod logs;
+mod mcp;
+mod output;
+mod permissions;
+mod queue;
+mod registry;
+mod screen;
+mod search;
+mod server;
 mod session;
+mod stream;
+mod terminal;
+#[cfg(test)]
+mod testing;
 mod tmux;
-
-use session::SessionManager;
+mod transcript;
+
+use backend::BackendKind;
+use claude::{Conversation, ExitReason};
+use export::ExportFormat;
+use logs::{LogActionKind, LogPolicy};
+use output::OutputFormat;
+use permissions::PermissionDecision;
+use queue::{MessageQueue, QueuedMessage};
+use search::SearchQuery;
+use session::{CompletionReport, SessionManager};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Config {
@@ -25,6 +54,38 @@ struct Config {
     /// Use directory-based session names
     #[serde(default = "default_use_directory_sessions")]
     pub use_directory_sessions: bool,
+
+    /// Terminal multiplexer hosting the sessions (tmux or screen)
+    #[serde(default)]
+    pub backend: BackendKind,
+
+    /// How long to wait for Claude Code to start up, in seconds
+    #[serde(default = "default_startup_timeout")]
+    pub startup_timeout: u64,
+
+    /// Claude Code executable, looked up on PATH unless it is a path
+    #[serde(default = "default_claude_binary")]
+    pub claude_binary: String,
+
+    /// Extra arguments for Claude Code, e.g. ["--model", "opus"]
+    #[serde(default)]
+    pub claude_args: Vec<String>,
+
+    /// Extra environment variables for Claude Code
+    #[serde(default)]
+    pub claude_env: BTreeMap<String, String>,
+
+    /// Size in bytes above which a session log is rotated
+    #[serde(default = "default_log_max_size")]
+    pub log_max_size: u64,
+
+    /// Compressed rotated logs kept for each run log of a session
+    #[serde(default = "default_log_keep")]
+    pub log_keep: usize,
+
+    /// Days to keep the logs of sessions that are gone (0 keeps them forever)
+    #[serde(default = "default_log_retention_days")]
+    pub log_retention_days: u64,
 }
 
 impl Default for Config {
@@ -34,6 +95,24 @@ impl Default for Config {
             default_timeout: 300,
             default_session_name: "claude-default".to_string(),
             use_directory_sessions: true, // Enable by default
+            backend: BackendKind::Tmux,
+            startup_timeout: 60,
+            claude_binary: "claude-code".to_string(),
+            claude_args: Vec::new(),
+            claude_env: BTreeMap::new(),
+            log_max_size: default_log_max_size(),
+            log_keep: default_log_keep(),
+            log_retention_days: default_log_retention_days(),
+        }
+    }
+}
+
+impl Config {
+    pub fn log_policy(&self) -> LogPolicy {
+        LogPolicy {
+            max_size: self.log_max_size,
+            keep: self.log_keep,
+            retention_days: self.log_retention_days,
         }
     }
 }
@@ -42,6 +121,26 @@ fn default_timeout() -> u64 {
     300
 }
 
+fn default_startup_timeout() -> u64 {
+    60
+}
+
+fn default_claude_binary() -> String {
+    "claude-code".to_string()
+}
+
+fn default_log_max_size() -> u64 {
+    50 * 1024 * 1024
+}
+
+fn default_log_keep() -> usize {
+    5
+}
+
+fn default_log_retention_days() -> u64 {
+    30
+}
+
 fn default_session_name() -> String {
     "claude-default".to_string()
 }
@@ -53,60 +152,299 @@ fn default_use_directory_sessions() -> bool {
 fn generate_directory_session_name(current_dir: &std::path::Path) -> String {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
-    
+
     // Get the full path as string
     let path_str = current_dir.to_string_lossy();
-    
+
     // Generate hash of the full path
     let mut hasher = DefaultHasher::new();
     path_str.hash(&mut hasher);
     let hash = hasher.finish();
-    
+
     // Get the last 2 directory components for the suffix
-    let components: Vec<_> = current_dir.components()
+    let components: Vec<_> = current_dir
+        .components()
         .filter_map(|c| match c {
             std::path::Component::Normal(name) => Some(name.to_string_lossy()),
             _ => None,
         })
         .collect();
-    
+
     let suffix = if components.len() >= 2 {
-        format!("{}-{}", components[components.len()-2], components[components.len()-1])
+        format!(
+            "{}-{}",
+            components[components.len() - 2],
+            components[components.len() - 1]
+        )
     } else if components.len() == 1 {
-        components[0].to_string()  
+        components[0].to_string()
     } else {
         "root".to_string()
     };
-    
+
     // Sanitize suffix (replace non-alphanumeric with dashes, limit length)
     let safe_suffix = suffix
         .chars()
-        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
         .collect::<String>()
         .trim_matches('-')
         .chars()
         .take(30) // Limit suffix length
         .collect::<String>();
-    
+
     format!("claude-{:x}-{}", hash & 0xFFFFFF, safe_suffix) // Use 6 hex digits for hash
 }
 
-fn get_session_name_for_current_dir(config: &Config, explicit_session: Option<&str>) -> anyhow::Result<String> {
+/// Open (creating it if needed) a file used only to hold a lock on
+fn open_lock(path: &std::path::Path) -> anyhow::Result<std::fs::File> {
+    use anyhow::Context;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open lock file: {}", path.display()))
+}
+
+/// Human-friendly relative time, e.g. "5m ago"
+fn format_age(time: chrono::DateTime<chrono::Utc>) -> String {
+    let secs = (chrono::Utc::now() - time).num_seconds().max(0);
+    match secs {
+        0..=59 => format!("{secs}s ago"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Shorten a message to a single line of at most `max_chars` characters
+fn truncate_message(message: &str, max_chars: usize) -> String {
+    let first_line = message.lines().next().unwrap_or("");
+    if first_line.chars().count() > max_chars || message.lines().count() > 1 {
+        let truncated: String = first_line.chars().take(max_chars).collect();
+        format!("{truncated}...")
+    } else {
+        first_line.to_string()
+    }
+}
+
+fn print_queue(session_name: &str, messages: &[QueuedMessage]) {
+    let worker = if queue::worker_running(session_name) {
+        "worker running"
+    } else {
+        "no worker"
+    };
+    if messages.is_empty() {
+        println!("Queue for session '{session_name}' is empty ({worker})");
+        return;
+    }
+
+    println!("Queue for session '{session_name}' ({worker}):");
+    for message in messages {
+        let when = message
+            .finished_at
+            .or(message.started_at)
+            .map(|time| format!(" ({})", format_age(time)))
+            .unwrap_or_default();
+        println!(
+            "  #{:<3} {:<20} {}{when}",
+            message.id,
+            message.status.to_string(),
+            truncate_message(&message.message, 60)
+        );
+        if let Some(error) = &message.error {
+            println!("        Error: {error}");
+        } else if let Some(reason) = message.exit_reason.filter(|reason| !reason.is_finished()) {
+            println!("        Stopped: {reason}");
+        }
+        if let Some(response) = &message.response {
+            println!("        Reply: {}", truncate_message(response, 60));
+        }
+    }
+}
+
+fn print_fanout(results: &[fanout::FanoutResult]) {
+    println!(
+        "{:<36} {:<20} {:>7} {:>4}  Reply",
+        "Session", "Status", "Time", "Exit"
+    );
+    for result in results {
+        let status = if result.started {
+            format!("{} (started)", result.status)
+        } else {
+            result.status.to_string()
+        };
+        let reply = result
+            .error
+            .as_deref()
+            .or(result.response.as_deref())
+            .map(|text| truncate_message(text, 60))
+            .unwrap_or_default();
+        println!(
+            "{:<36} {:<20} {:>6.0}s {:>4}  {reply}",
+            result.session, status, result.elapsed_secs, result.exit_code
+        );
+        if let Some(dir) = &result.working_dir {
+            println!("  {}", dir.display());
+        }
+    }
+}
+
+/// Start `queue run` for the session in the background, detached from this
+/// terminal and logging to the queue directory
+fn spawn_queue_worker(session_name: &str, config_path: Option<&PathBuf>) -> anyhow::Result<()> {
+    let log_path = queue::queue_dir().join(format!("{session_name}.worker.log"));
+    let pid = spawn_detached(
+        &["queue", "run", "--session", session_name],
+        &log_path,
+        config_path,
+    )?;
+    tracing::info!(
+        "Started queue worker for session {} (pid {}), logging to {}",
+        session_name,
+        pid,
+        log_path.display()
+    );
+    Ok(())
+}
+
+/// Run this binary with `args` in the background, detached from this
+/// terminal and appending its output to `log_path`. Returns its pid.
+fn spawn_detached(
+    args: &[&str],
+    log_path: &std::path::Path,
+    config_path: Option<&PathBuf>,
+) -> anyhow::Result<u32> {
+    if let Some(parent) = log_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    if let Some(config_path) = config_path {
+        command.arg("--config").arg(config_path);
+    }
+    command
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    #[cfg(unix)]
+    {
+        // Keep Ctrl-C in this terminal from reaching the child
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    Ok(command.spawn()?.id())
+}
+
+/// Wait up to `timeout` for the daemon to be running, or to be gone
+fn wait_for_daemon(
+    running: bool,
+    timeout: std::time::Duration,
+) -> anyhow::Result<Option<daemon::DaemonStatus>> {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        let status = daemon::status()?;
+        if status.is_some() == running || std::time::Instant::now() >= deadline {
+            return Ok(status);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
+fn print_daemon_status(status: &daemon::DaemonStatus) {
+    println!(
+        "Daemon running (pid {}, started {})",
+        status.pid,
+        format_age(status.started_at)
+    );
+    println!("  Socket: {}", status.socket.display());
+    println!("  Events: {}", status.events.display());
+    if status.sessions.is_empty() {
+        println!("  No sessions to watch");
+        return;
+    }
+    println!("  Sessions:");
+    for tracked in &status.sessions {
+        let completed = tracked
+            .last_completed_at
+            .map(|time| format!(", last completed {}", format_age(time)))
+            .unwrap_or_default();
+        println!(
+            "    {:<30} {} since {}{completed}",
+            tracked.session,
+            tracked.state,
+            format_age(tracked.since)
+        );
+    }
+}
+
+/// Resolve the text to send from the argument, `-` (stdin) or `--file`
+fn read_message(message: Option<String>, file: Option<&PathBuf>) -> anyhow::Result<String> {
+    let message = match (message, file) {
+        (_, Some(path)) => std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Failed to read message file {}: {}", path.display(), e)
+        })?,
+        (Some(message), None) if message == "-" => {
+            let mut message = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut message)?;
+            message
+        }
+        (Some(message), None) => message,
+        (None, None) => String::new(),
+    };
+
+    // A trailing newline from a file or heredoc would otherwise be pasted before Enter
+    let message = message.trim_end_matches(['\n', '\r']).to_string();
+    if message.trim().is_empty() {
+        return Err(anyhow::anyhow!("Message is empty"));
+    }
+    Ok(message)
+}
+
+fn get_session_name_for_current_dir(
+    config: &Config,
+    explicit_session: Option<&str>,
+) -> anyhow::Result<String> {
     // If explicit session name provided, use it
     if let Some(session) = explicit_session {
         return Ok(session.to_string());
     }
-    
+
     // If directory sessions disabled, use default
     if !config.use_directory_sessions {
         return Ok(config.default_session_name.clone());
     }
-    
+
     // Generate directory-based session name
     let current_dir = std::env::current_dir()?;
     Ok(generate_directory_session_name(&current_dir))
 }
 
+/// Session name for a session started in `dir`, as the CLI would pick it
+/// when run from there
+fn session_name_for_dir(config: &Config, dir: Option<&std::path::Path>) -> String {
+    match dir {
+        Some(dir) if config.use_directory_sessions => generate_directory_session_name(dir),
+        _ => config.default_session_name.clone(),
+    }
+}
+
 fn load_config(config_path: Option<&PathBuf>) -> anyhow::Result<Config> {
     let config_file = if let Some(path) = config_path {
         path.clone()
@@ -143,7 +481,6 @@ fn create_default_config_file() -> anyhow::Result<PathBuf> {
     let config_json = serde_json::to_string_pretty(&default_config)?;
     std::fs::write(&config_file, config_json)?;
 
-    println!("Created default config file at: {}", config_file.display());
     Ok(config_file)
 }
 
@@ -158,42 +495,124 @@ fn get_config_path(config_path: Option<&PathBuf>) -> PathBuf {
     }
 }
 
+/// Parse `config set claude-args`: a JSON array, or words separated by whitespace
+fn parse_claude_args(value: &str) -> anyhow::Result<Vec<String>> {
+    if value.trim_start().starts_with('[') {
+        serde_json::from_str(value).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid claude-args '{}': {}. Use a JSON array of strings",
+                value,
+                e
+            )
+        })
+    } else {
+        Ok(value.split_whitespace().map(str::to_string).collect())
+    }
+}
+
+/// Parse `config set claude-env`: a JSON object, or KEY=VALUE pairs separated by whitespace
+fn parse_claude_env(value: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    if value.trim_start().starts_with('{') {
+        serde_json::from_str(value).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid claude-env '{}': {}. Use a JSON object of strings",
+                value,
+                e
+            )
+        })
+    } else {
+        value.split_whitespace().map(parse_env_var).collect()
+    }
+}
+
+/// Parse a `KEY=VALUE` environment assignment
+fn parse_env_var(assignment: &str) -> anyhow::Result<(String, String)> {
+    match assignment.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(anyhow::anyhow!(
+            "Invalid environment variable '{}'. Use KEY=VALUE",
+            assignment
+        )),
+    }
+}
+
 async fn handle_config_command(
     config_command: &ConfigCommands,
     config_path: Option<&PathBuf>,
+    output: OutputFormat,
 ) -> anyhow::Result<()> {
     match config_command {
         ConfigCommands::Show => {
             let config = load_config(config_path)?;
-            println!("Current configuration:");
-            println!("{}", serde_json::to_string_pretty(&config)?);
+            if output.is_text() {
+                println!("Current configuration:");
+                println!("{}", serde_json::to_string_pretty(&config)?);
+            } else {
+                output.print(&config)?;
+            }
         }
 
         ConfigCommands::Init => {
-            create_default_config_file()?;
+            let config_file = create_default_config_file()?;
+            if output.is_text() {
+                println!("Created default config file at: {}", config_file.display());
+            } else {
+                output.print(&serde_json::json!({ "path": config_file }))?;
+            }
         }
 
         ConfigCommands::Get { key } => {
             let config = load_config(config_path)?;
-            match key.as_str() {
-                "skip-permissions" | "skip_permissions" => {
-                    println!("{}", config.skip_permissions);
-                }
+            let (key, value) = match key.as_str() {
+                "skip-permissions" | "skip_permissions" => (
+                    "skip-permissions",
+                    serde_json::json!(config.skip_permissions),
+                ),
                 "default-timeout" | "default_timeout" => {
-                    println!("{}", config.default_timeout);
+                    ("default-timeout", serde_json::json!(config.default_timeout))
                 }
-                "default-session-name" | "default_session_name" => {
-                    println!("{}", config.default_session_name);
+                "default-session-name" | "default_session_name" => (
+                    "default-session-name",
+                    serde_json::json!(config.default_session_name),
+                ),
+                "use-directory-sessions" | "use_directory_sessions" => (
+                    "use-directory-sessions",
+                    serde_json::json!(config.use_directory_sessions),
+                ),
+                "backend" => ("backend", serde_json::json!(config.backend)),
+                "startup-timeout" | "startup_timeout" => {
+                    ("startup-timeout", serde_json::json!(config.startup_timeout))
                 }
-                "use-directory-sessions" | "use_directory_sessions" => {
-                    println!("{}", config.use_directory_sessions);
+                "claude-binary" | "claude_binary" => {
+                    ("claude-binary", serde_json::json!(config.claude_binary))
                 }
+                "claude-args" | "claude_args" => {
+                    ("claude-args", serde_json::json!(config.claude_args))
+                }
+                "claude-env" | "claude_env" => ("claude-env", serde_json::json!(config.claude_env)),
+                "log-max-size" | "log_max_size" => {
+                    ("log-max-size", serde_json::json!(config.log_max_size))
+                }
+                "log-keep" | "log_keep" => ("log-keep", serde_json::json!(config.log_keep)),
+                "log-retention-days" | "log_retention_days" => (
+                    "log-retention-days",
+                    serde_json::json!(config.log_retention_days),
+                ),
                 _ => {
                     return Err(anyhow::anyhow!(
-                        "Unknown config key: '{}'. Available keys: skip-permissions, default-timeout, default-session-name, use-directory-sessions", 
+                        "Unknown config key: '{}'. Available keys: skip-permissions, default-timeout, default-session-name, use-directory-sessions, backend, startup-timeout, claude-binary, claude-args, claude-env, log-max-size, log-keep, log-retention-days", 
                         key
                     ));
                 }
+            };
+
+            if output.is_text() {
+                match value {
+                    serde_json::Value::String(value) => println!("{value}"),
+                    value => println!("{value}"),
+                }
+            } else {
+                output.print(&serde_json::json!({ "key": key, "value": value }))?;
             }
         }
 
@@ -202,11 +621,13 @@ async fn handle_config_command(
             let mut config = if config_file.exists() {
                 load_config(config_path)?
             } else {
-                println!("Config file doesn't exist, creating new one...");
+                if output.is_text() {
+                    println!("Config file doesn't exist, creating new one...");
+                }
                 Config::default()
             };
 
-            match key.as_str() {
+            let (key, new_value) = match key.as_str() {
                 "skip-permissions" | "skip_permissions" => {
                     let bool_value = match value.to_lowercase().as_str() {
                         "true" | "1" | "yes" | "on" => true,
@@ -219,7 +640,10 @@ async fn handle_config_command(
                         }
                     };
                     config.skip_permissions = bool_value;
-                    println!("Set skip-permissions to: {}", config.skip_permissions);
+                    (
+                        "skip-permissions",
+                        serde_json::json!(config.skip_permissions),
+                    )
                 }
                 "default-timeout" | "default_timeout" => {
                     let timeout_value: u64 = value.parse().map_err(|_| {
@@ -229,14 +653,14 @@ async fn handle_config_command(
                         )
                     })?;
                     config.default_timeout = timeout_value;
-                    println!("Set default-timeout to: {}", config.default_timeout);
+                    ("default-timeout", serde_json::json!(config.default_timeout))
                 }
                 "default-session-name" | "default_session_name" => {
                     config.default_session_name = value.clone();
-                    println!(
-                        "Set default-session-name to: {}",
-                        config.default_session_name
-                    );
+                    (
+                        "default-session-name",
+                        serde_json::json!(config.default_session_name),
+                    )
                 }
                 "use-directory-sessions" | "use_directory_sessions" => {
                     let bool_value = match value.to_lowercase().as_str() {
@@ -250,15 +674,66 @@ async fn handle_config_command(
                         }
                     };
                     config.use_directory_sessions = bool_value;
-                    println!("Set use-directory-sessions to: {}", config.use_directory_sessions);
+                    (
+                        "use-directory-sessions",
+                        serde_json::json!(config.use_directory_sessions),
+                    )
+                }
+                "backend" => {
+                    config.backend = value.parse()?;
+                    ("backend", serde_json::json!(config.backend))
+                }
+                "startup-timeout" | "startup_timeout" => {
+                    let timeout_value: u64 = value.parse().map_err(|_| {
+                        anyhow::anyhow!(
+                            "Invalid timeout value '{}'. Must be a positive number",
+                            value
+                        )
+                    })?;
+                    config.startup_timeout = timeout_value;
+                    ("startup-timeout", serde_json::json!(config.startup_timeout))
+                }
+                "claude-binary" | "claude_binary" => {
+                    if value.is_empty() {
+                        return Err(anyhow::anyhow!("claude-binary cannot be empty"));
+                    }
+                    config.claude_binary = value.clone();
+                    ("claude-binary", serde_json::json!(config.claude_binary))
+                }
+                "claude-args" | "claude_args" => {
+                    config.claude_args = parse_claude_args(value)?;
+                    ("claude-args", serde_json::json!(config.claude_args))
+                }
+                "claude-env" | "claude_env" => {
+                    config.claude_env = parse_claude_env(value)?;
+                    ("claude-env", serde_json::json!(config.claude_env))
+                }
+                "log-max-size" | "log_max_size" => {
+                    config.log_max_size = logs::parse_size(value)?;
+                    ("log-max-size", serde_json::json!(config.log_max_size))
+                }
+                "log-keep" | "log_keep" => {
+                    config.log_keep = value.parse().map_err(|_| {
+                        anyhow::anyhow!("Invalid count '{}'. Must be a number", value)
+                    })?;
+                    ("log-keep", serde_json::json!(config.log_keep))
+                }
+                "log-retention-days" | "log_retention_days" => {
+                    config.log_retention_days = value.parse().map_err(|_| {
+                        anyhow::anyhow!("Invalid number of days '{}'. Must be a number", value)
+                    })?;
+                    (
+                        "log-retention-days",
+                        serde_json::json!(config.log_retention_days),
+                    )
                 }
                 _ => {
                     return Err(anyhow::anyhow!(
-                        "Unknown config key: '{}'. Available keys: skip-permissions, default-timeout, default-session-name, use-directory-sessions", 
+                        "Unknown config key: '{}'. Available keys: skip-permissions, default-timeout, default-session-name, use-directory-sessions, backend, startup-timeout, claude-binary, claude-args, claude-env, log-max-size, log-keep, log-retention-days", 
                         key
                     ));
                 }
-            }
+            };
 
             // Create config directory if it doesn't exist
             if let Some(parent) = config_file.parent() {
@@ -268,7 +743,20 @@ async fn handle_config_command(
             // Save the updated config
             let config_json = serde_json::to_string_pretty(&config)?;
             std::fs::write(&config_file, config_json)?;
-            println!("Configuration saved to: {}", config_file.display());
+
+            if output.is_text() {
+                match &new_value {
+                    serde_json::Value::String(value) => println!("Set {key} to: {value}"),
+                    value => println!("Set {key} to: {value}"),
+                }
+                println!("Configuration saved to: {}", config_file.display());
+            } else {
+                output.print(&serde_json::json!({
+                    "key": key,
+                    "value": new_value,
+                    "path": config_file,
+                }))?;
+            }
         }
     }
 
@@ -288,6 +776,10 @@ struct Cli {
     #[arg(long, global = true)]
     config: Option<PathBuf>,
 
+    /// Output format for command results
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -296,9 +788,9 @@ struct Cli {
 enum Commands {
     /// Start a new Claude Code session with a task
     Start {
-        /// The message or task to send to Claude Code
-        #[arg(short, long)]
-        message: String,
+        /// The message or task to send to Claude Code (optional when resuming)
+        #[arg(short, long, required_unless_present_any = ["continue_conversation", "resume"])]
+        message: Option<String>,
 
         /// Custom session name (optional)
         #[arg(short, long)]
@@ -309,17 +801,44 @@ enum Commands {
         working_dir: Option<PathBuf>,
 
         /// Wait for completion and return results
-        #[arg(short, long)]
+        #[arg(long, requires = "message")]
         wait: bool,
 
         /// Timeout in seconds (default: uses config)
         #[arg(short, long)]
         timeout: Option<u64>,
+
+        /// Return the entire pane capture instead of only Claude's reply
+        #[arg(long)]
+        full: bool,
+
+        /// Continue the most recent conversation in the working directory
+        #[arg(long = "continue", conflicts_with = "resume")]
+        continue_conversation: bool,
+
+        /// Resume a Claude Code conversation by its ID
+        #[arg(long, value_name = "CONVERSATION_ID")]
+        resume: Option<String>,
+
+        /// Claude Code executable to run (default: uses config)
+        #[arg(long, value_name = "PATH")]
+        claude_binary: Option<String>,
+
+        /// Extra argument for Claude Code, added after the configured ones (repeatable)
+        #[arg(long = "claude-arg", value_name = "ARG", allow_hyphen_values = true)]
+        claude_args: Vec<String>,
+
+        /// Environment variable for Claude Code (repeatable)
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+        env: Vec<(String, String)>,
     },
 
     /// List all active Claude Code sessions
     List,
 
+    /// Watch all sessions in an interactive terminal dashboard
+    Dashboard,
+
     /// Attach to an existing session
     Attach {
         /// Session name or ID
@@ -328,8 +847,17 @@ enum Commands {
 
     /// Send a message to a session (creates default session if none specified)
     Send {
-        /// Message to send
-        message: String,
+        /// Message to send ('-' reads it from stdin)
+        #[arg(
+            required_unless_present = "file",
+            conflicts_with = "file",
+            allow_hyphen_values = true
+        )]
+        message: Option<String>,
+
+        /// Read the message from a file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
 
         /// Session name or ID (default: creates/uses 'claude-default')
         #[arg(short, long)]
@@ -342,6 +870,85 @@ enum Commands {
         /// Timeout in seconds (default: uses config)
         #[arg(short, long)]
         timeout: Option<u64>,
+
+        /// Return the entire pane capture instead of only Claude's reply
+        #[arg(long)]
+        full: bool,
+    },
+
+    /// Send the same message to several sessions or directories and wait for all of them
+    Fanout {
+        /// Message to send (use - to read it from stdin)
+        #[arg(
+            required_unless_present = "file",
+            conflicts_with = "file",
+            allow_hyphen_values = true
+        )]
+        message: Option<String>,
+
+        /// Read the message from a file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Sessions to send to, comma-separated
+        #[arg(long, value_delimiter = ',', required_unless_present = "dirs")]
+        sessions: Vec<String>,
+
+        /// Directories to send to, as paths or glob patterns, comma-separated
+        /// or repeated; each gets its directory session, started if needed
+        #[arg(long, value_delimiter = ',')]
+        dirs: Vec<String>,
+
+        /// Most sessions to wait on at a time
+        #[arg(
+            short = 'j',
+            long,
+            default_value_t = 4,
+            value_parser = clap::value_parser!(u16).range(1..)
+        )]
+        concurrency: u16,
+
+        /// Timeout in seconds for each session (default: uses config)
+        #[arg(short, long)]
+        timeout: Option<u64>,
+    },
+
+    /// Approve the permission prompt a session is waiting on
+    Approve {
+        /// Session name or ID (default: directory-based session)
+        #[arg(short, long)]
+        session: Option<String>,
+
+        /// Also stop asking for similar tool uses in this session
+        #[arg(long)]
+        always: bool,
+
+        /// Don't wait for the session to finish its turn afterwards
+        #[arg(long)]
+        no_wait: bool,
+
+        /// Timeout in seconds (default: uses config)
+        #[arg(short, long)]
+        timeout: Option<u64>,
+
+        /// Return the entire pane capture instead of only Claude's reply
+        #[arg(long)]
+        full: bool,
+    },
+
+    /// Deny the permission prompt a session is waiting on
+    Deny {
+        /// Session name or ID (default: directory-based session)
+        #[arg(short, long)]
+        session: Option<String>,
+
+        /// Tell Claude what to do instead (waits for its reply)
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// Timeout in seconds when a message is given (default: uses config)
+        #[arg(short, long)]
+        timeout: Option<u64>,
     },
 
     /// Get the status and output of a session
@@ -355,15 +962,46 @@ enum Commands {
         lines: usize,
     },
 
-    /// Kill a Claude Code session
+    /// Kill a Claude Code session (it can be revived later unless forgotten)
     Kill {
         /// Session name or ID (default: directory-based session)
         #[arg(short, long)]
         session: Option<String>,
+
+        /// Also drop the session's record, so it cannot be revived
+        #[arg(long)]
+        forget: bool,
     },
 
     /// Kill all Claude Code sessions
-    KillAll,
+    KillAll {
+        /// Also drop the sessions' records, so they cannot be revived
+        #[arg(long)]
+        forget: bool,
+    },
+
+    /// Recreate an ended session and resume its last Claude Code conversation
+    Revive {
+        /// Session name or ID (default: directory-based session)
+        #[arg(short, long, conflicts_with = "all")]
+        session: Option<String>,
+
+        /// Revive every ended session
+        #[arg(long)]
+        all: bool,
+
+        /// Message to send once the conversation is resumed
+        #[arg(short, long, conflicts_with = "all")]
+        message: Option<String>,
+
+        /// Wait for completion of the message and return results
+        #[arg(long, requires = "message")]
+        wait: bool,
+
+        /// Timeout in seconds (default: uses config)
+        #[arg(short, long)]
+        timeout: Option<u64>,
+    },
 
     /// View session history
     History {
@@ -378,6 +1016,18 @@ enum Commands {
         /// Follow the history (like tail -f)
         #[arg(short, long)]
         follow: bool,
+
+        /// Show the terminal log instead of Claude Code's transcript
+        #[arg(long)]
+        raw: bool,
+
+        /// Show run N of the session, counting from 1 for the oldest
+        #[arg(long, value_name = "N", conflicts_with_all = ["follow", "all_runs"])]
+        run: Option<usize>,
+
+        /// Show every run of the session, oldest first
+        #[arg(long, conflicts_with = "follow")]
+        all_runs: bool,
     },
 
     /// Export session history to a file
@@ -387,12 +1037,86 @@ enum Commands {
         session: Option<String>,
 
         /// Output file path
-        #[arg(short, long)]
-        output: PathBuf,
+        #[arg(short = 'o', long)]
+        output_file: PathBuf,
 
-        /// Remove ANSI color codes for clean text output
+        /// File format (default: from the output file extension, else txt)
+        #[arg(short, long, value_enum)]
+        format: Option<ExportFormat>,
+
+        /// Replay the terminal log into plain text, without escape codes or redraws
         #[arg(short, long)]
         clean: bool,
+
+        /// Export the terminal log instead of Claude Code's transcript
+        #[arg(long)]
+        raw: bool,
+    },
+
+    /// Search the transcripts and logs of all sessions
+    Search {
+        /// Regular expression to look for
+        pattern: String,
+
+        /// Match regardless of case
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+
+        /// Only search this session (repeatable)
+        #[arg(short, long = "session", value_name = "SESSION")]
+        sessions: Vec<String>,
+
+        /// Only matches from this time on: a date, an RFC 3339 time, or an age such as 12h or 7d
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only matches up to this time (a date includes the whole day)
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Lines of context to show around each match
+        #[arg(short = 'C', long, default_value_t = 2)]
+        context: usize,
+
+        /// Search the terminal logs instead of Claude Code's transcripts
+        #[arg(long)]
+        raw: bool,
+    },
+
+    /// Line up messages for a session, sent one at a time as Claude finishes each
+    Queue {
+        #[command(subcommand)]
+        queue_command: QueueCommands,
+    },
+
+    /// Serve the session commands over a local HTTP/JSON API
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: std::net::IpAddr,
+
+        /// Port to listen on (0 picks a free one)
+        #[arg(short, long, default_value_t = 7878)]
+        port: u16,
+
+        /// Listen on this Unix socket instead of a TCP port
+        #[arg(long, conflicts_with_all = ["host", "port"])]
+        socket: Option<PathBuf>,
+    },
+
+    /// Run as a Model Context Protocol server on stdio, exposing sessions as tools
+    Mcp,
+
+    /// Run a background daemon that watches sessions and records their events
+    Daemon {
+        #[command(subcommand)]
+        daemon_command: DaemonCommands,
+    },
+
+    /// Manage session log files
+    Logs {
+        #[command(subcommand)]
+        logs_command: LogsCommands,
     },
 
     /// Configuration management
@@ -400,6 +1124,134 @@ enum Commands {
         #[command(subcommand)]
         config_command: ConfigCommands,
     },
+
+    /// Manage the Claude Code hooks used for completion detection
+    Hooks {
+        #[command(subcommand)]
+        hooks_command: HooksCommands,
+    },
+
+    /// Hook entry point invoked by Claude Code (installed by 'hooks install')
+    #[command(hide = true)]
+    Hook {
+        /// Hook event name (stop, subagent-stop or notification)
+        event: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum HooksCommands {
+    /// Add the completion hooks to the Claude Code settings
+    Install {
+        /// Claude settings file (default: ~/.claude/settings.json)
+        #[arg(long)]
+        settings: Option<PathBuf>,
+    },
+
+    /// Remove the completion hooks from the Claude Code settings
+    Uninstall {
+        /// Claude settings file (default: ~/.claude/settings.json)
+        #[arg(long)]
+        settings: Option<PathBuf>,
+    },
+
+    /// Check that the hooks are installed and record completion events
+    Doctor {
+        /// Claude settings file (default: ~/.claude/settings.json)
+        #[arg(long)]
+        settings: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum LogsCommands {
+    /// Rotate oversized logs and delete logs past the retention period
+    Prune {
+        /// Show what would be rotated or deleted without changing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Delete logs of ended sessions older than this many days (default: log-retention-days)
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum QueueCommands {
+    /// Queue messages and start a background worker to send them
+    Add {
+        /// Messages to queue, in order ("-" reads one from stdin)
+        #[arg(required_unless_present = "file")]
+        messages: Vec<String>,
+
+        /// Queue the contents of a file as one message
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Session name or ID (default: directory-based session)
+        #[arg(short, long)]
+        session: Option<String>,
+
+        /// Seconds to wait for Claude to finish each message (default: the configured timeout)
+        #[arg(short, long)]
+        timeout: Option<u64>,
+
+        /// Only queue the messages; send them later with 'queue run'
+        #[arg(long)]
+        no_start: bool,
+    },
+
+    /// Show queued, running and finished messages
+    List {
+        /// Session name or ID (default: directory-based session)
+        #[arg(short, long, conflicts_with = "all")]
+        session: Option<String>,
+
+        /// Show the queues of all sessions
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Remove pending and finished messages (a message already sent is kept)
+    Clear {
+        /// Session name or ID (default: directory-based session)
+        #[arg(short, long)]
+        session: Option<String>,
+    },
+
+    /// Send the queued messages in the foreground, as the background worker does
+    Run {
+        /// Session name or ID (default: directory-based session)
+        #[arg(short, long)]
+        session: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DaemonCommands {
+    /// Start the daemon in the background
+    Start,
+
+    /// Stop the running daemon
+    Stop,
+
+    /// Show whether the daemon is running and what it sees in each session
+    Status,
+
+    /// Run the daemon in the foreground
+    Run,
+
+    /// Show the session events the daemon recorded
+    Events {
+        /// Only show events of this session
+        #[arg(short, long)]
+        session: Option<String>,
+
+        /// Number of most recent events to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+    },
 }
 
 #[derive(Subcommand)]
@@ -425,19 +1277,187 @@ enum ConfigCommands {
     },
 }
 
+fn handle_hooks_command(hooks_command: &HooksCommands, output: OutputFormat) -> anyhow::Result<()> {
+    match hooks_command {
+        HooksCommands::Install { settings } => {
+            let settings = settings
+                .clone()
+                .unwrap_or_else(hooks::default_settings_path);
+            hooks::install(&settings)?;
+            let mut commands = serde_json::Map::new();
+            for kind in hooks::HookKind::ALL {
+                commands.insert(
+                    kind.event_name().to_string(),
+                    hooks::hook_command(kind)?.into(),
+                );
+            }
+            if output.is_text() {
+                println!("Installed hooks into: {}", settings.display());
+                for (event, command) in &commands {
+                    println!("  {event}: {}", command.as_str().unwrap_or_default());
+                }
+                println!("Restart running Claude Code sessions for the hooks to take effect.");
+            } else {
+                output.print(&serde_json::json!({
+                    "settings": settings,
+                    "hooks": commands,
+                }))?;
+            }
+        }
+
+        HooksCommands::Uninstall { settings } => {
+            let settings = settings
+                .clone()
+                .unwrap_or_else(hooks::default_settings_path);
+            let removed = hooks::uninstall(&settings)?;
+            if output.is_text() {
+                println!("Removed {removed} hook(s) from: {}", settings.display());
+            } else {
+                output.print(&serde_json::json!({
+                    "settings": settings,
+                    "removed": removed,
+                }))?;
+            }
+        }
+
+        HooksCommands::Doctor { settings } => {
+            let settings = settings
+                .clone()
+                .unwrap_or_else(hooks::default_settings_path);
+            let checks = hooks::doctor(&settings);
+            if output.is_text() {
+                for check in &checks {
+                    let mark = if check.ok { "✓" } else { "✗" };
+                    println!("{mark} {}: {}", check.name, check.detail);
+                }
+            } else {
+                output.print_all(&checks)?;
+            }
+
+            if checks.iter().any(|check| !check.ok) {
+                return Err(anyhow::anyhow!(
+                    "Hook checks failed; completion detection will fall back to heuristics"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the result of a wait in the selected format, failing unless the turn finished
+fn print_completion(report: &CompletionReport, output: OutputFormat) -> anyhow::Result<()> {
+    if output.is_text() {
+        if report.exit_reason != ExitReason::Timeout {
+            if let Some(response) = &report.response {
+                println!("{response}");
+            }
+        }
+    } else {
+        output.print(report)?;
+    }
+
+    match report.exit_reason {
+        ExitReason::Timeout => Err(anyhow::anyhow!(
+            "Timeout waiting for Claude completion in session '{}'",
+            report.session
+        )),
+        ExitReason::AwaitingPermission => {
+            let what = match (&report.permission_prompt, &report.event) {
+                (Some(prompt), _) => match &prompt.detail {
+                    Some(detail) => format!("{} ({detail})", prompt.tool),
+                    None => prompt.tool.clone(),
+                },
+                (None, Some(event)) => event.message.clone().unwrap_or_default(),
+                (None, None) => "permission prompt".to_string(),
+            };
+            Err(anyhow::anyhow!(
+                "Session '{}' is waiting for permission: {}. Answer with 'claude-code-manager approve -s {0}' or 'deny -s {0}'",
+                report.session,
+                what
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// `export` used to take its file as `--output`, before that name became the
+/// global output format. When a command line fails to parse and its `export`
+/// has no `-o`/`--output-file`, read `--output` as the file so existing
+/// scripts keep working.
+fn legacy_export_args(args: &[std::ffi::OsString]) -> Option<Vec<std::ffi::OsString>> {
+    let export = args.iter().position(|arg| arg == "export")?;
+    let rest = &args[export + 1..];
+    let has_file = rest.iter().any(|arg| {
+        let arg = arg.to_string_lossy();
+        arg == "--output-file" || arg.starts_with("--output-file=") || arg.starts_with("-o")
+    });
+    if has_file {
+        return None;
+    }
+
+    let mut rewritten = false;
+    let args = args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            let text = arg.to_string_lossy();
+            if i <= export {
+                arg.clone()
+            } else if text == "--output" {
+                rewritten = true;
+                "--output-file".into()
+            } else if let Some(value) = text.strip_prefix("--output=") {
+                rewritten = true;
+                format!("--output-file={value}").into()
+            } else {
+                arg.clone()
+            }
+        })
+        .collect();
+    rewritten.then_some(args)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
+    let args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let cli = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        Err(error) => legacy_export_args(&args)
+            .and_then(|args| Cli::try_parse_from(args).ok())
+            .unwrap_or_else(|| error.exit()),
+    };
 
-    let cli = Cli::parse();
+    // Initialize tracing (on stderr so structured output on stdout stays
+    // parseable, or in a file while the dashboard draws on the terminal)
+    if matches!(cli.command, Commands::Dashboard) {
+        let log = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dashboard::log_path()?)?;
+        tracing_subscriber::fmt()
+            .with_writer(std::sync::Mutex::new(log))
+            .with_ansi(false)
+            .init();
+    } else {
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .init();
+    }
+    let output = cli.output;
 
     // Handle config command early
     if let Commands::Config { config_command } = &cli.command {
-        handle_config_command(config_command, cli.config.as_ref()).await?;
+        handle_config_command(config_command, cli.config.as_ref(), output).await?;
         return Ok(());
     }
 
+    match &cli.command {
+        Commands::Hooks { hooks_command } => return handle_hooks_command(hooks_command, output),
+        Commands::Hook { event } => return hooks::run_hook(event),
+        _ => {}
+    }
+
     // Load configuration
     let mut config = load_config(cli.config.as_ref())?;
 
@@ -445,6 +1465,19 @@ async fn main() -> anyhow::Result<()> {
     if cli.skip_permissions {
         config.skip_permissions = true;
     }
+    if let Commands::Start {
+        claude_binary,
+        claude_args,
+        env,
+        ..
+    } = &cli.command
+    {
+        if let Some(claude_binary) = claude_binary {
+            config.claude_binary = claude_binary.clone();
+        }
+        config.claude_args.extend(claude_args.iter().cloned());
+        config.claude_env.extend(env.iter().cloned());
+    }
 
     let mut session_manager = SessionManager::new(config.clone());
 
@@ -455,6 +1488,10 @@ async fn main() -> anyhow::Result<()> {
             working_dir,
             wait,
             timeout,
+            full,
+            continue_conversation,
+            resume,
+            ..
         } => {
             // Determine session name and working directory
             let session_name = get_session_name_for_current_dir(&config, session_name.as_deref())?;
@@ -462,62 +1499,128 @@ async fn main() -> anyhow::Result<()> {
                 // If no explicit working dir, use current directory
                 std::env::current_dir().ok()
             });
-            
+            let conversation = match resume {
+                Some(id) => Conversation::Resume(id),
+                None if continue_conversation => Conversation::Continue,
+                None => Conversation::New,
+            };
+
             let session_name = session_manager
-                .start_session(message, Some(session_name), working_dir)
+                .start_session(message, Some(session_name), working_dir, conversation)
                 .await?;
 
-            println!("Started Claude Code session: {session_name}");
+            if output.is_text() {
+                println!("Started Claude Code session: {session_name}");
+            }
 
             if wait {
-                println!("Waiting for completion...");
+                if output.is_text() {
+                    println!("Waiting for completion...");
+                }
                 let timeout = timeout.unwrap_or(config.default_timeout);
-                let result = session_manager
-                    .wait_for_completion(&session_name, timeout)
+                let report = session_manager
+                    .wait_for_completion(&session_name, timeout, full)
                     .await?;
-                println!("Session completed:");
-                println!("{result}");
-            } else {
+                if output.is_text() && report.exit_reason.is_finished() {
+                    println!("Session completed:");
+                }
+                print_completion(&report, output)?;
+            } else if output.is_text() {
                 println!("Session started in background. Use 'claude-code-manager attach {session_name}' to connect.");
+            } else {
+                output.print(&CompletionReport::not_waited(&session_name))?;
             }
         }
 
         Commands::List => {
             let sessions = session_manager.list_sessions().await?;
-            if sessions.is_empty() {
+            if !output.is_text() {
+                output.print_all(&sessions)?;
+            } else if sessions.is_empty() {
                 println!("No active Claude Code sessions.");
             } else {
                 println!("Active Claude Code sessions:");
                 for session in sessions {
                     println!("  {} ({})", session.name, session.status);
+                    if let Some(dir) = &session.working_dir {
+                        println!("    Directory:    {}", dir.display());
+                    }
+                    println!(
+                        "    Created:      {} ({})",
+                        session.created_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                        format_age(session.created_at)
+                    );
+                    println!("    Permissions:  {}", session.permission_mode);
+                    if !session.command.is_empty() {
+                        let command: Vec<String> = session
+                            .command
+                            .iter()
+                            .map(|arg| backend::shell_quote(arg))
+                            .collect();
+                        println!("    Command:      {}", command.join(" "));
+                    }
+                    if let (Some(message), Some(sent_at)) =
+                        (&session.last_message, session.last_message_at)
+                    {
+                        println!(
+                            "    Last message: {} ({})",
+                            truncate_message(message, 60),
+                            format_age(sent_at)
+                        );
+                    }
+                    if let Some(completed_at) = session.last_completed_at {
+                        println!("    Completed:    {}", format_age(completed_at));
+                    }
+                    if let Some(conversation_id) = &session.conversation_id {
+                        println!("    Conversation: {conversation_id}");
+                    }
+                    if let Some(ended_at) = session.ended_at {
+                        println!(
+                            "    Ended:        {} (revive with 'claude-code-manager revive -s {}')",
+                            format_age(ended_at),
+                            session.name
+                        );
+                    }
                 }
             }
         }
 
+        Commands::Dashboard => {
+            dashboard::run(config).await?;
+        }
+
         Commands::Attach { session } => {
             session_manager.attach_session(&session).await?;
         }
 
         Commands::Send {
             message,
+            file,
             session,
             no_wait,
             timeout,
+            full,
         } => {
+            let message = read_message(message, file.as_ref())?;
             let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
 
             // Ensure the default session exists
             if !session_manager.session_exists(&session_name).await? {
-                println!("Creating default Claude Code session...");
+                if output.is_text() {
+                    println!("Creating default Claude Code session...");
+                }
                 let working_dir = std::env::current_dir().ok();
                 session_manager
                     .start_session(
-                        "Ready for commands".to_string(),
+                        Some("Ready for commands".to_string()),
                         Some(session_name.clone()),
                         working_dir,
+                        Conversation::New,
                     )
                     .await?;
-                println!("Default session '{session_name}' created.");
+                if output.is_text() {
+                    println!("Default session '{session_name}' created.");
+                }
             }
 
             session_manager
@@ -525,68 +1628,689 @@ async fn main() -> anyhow::Result<()> {
                 .await?;
 
             if no_wait {
-                println!("Message sent to session: {session_name}");
+                if output.is_text() {
+                    println!("Message sent to session: {session_name}");
+                } else {
+                    output.print(&CompletionReport::not_waited(&session_name))?;
+                }
+            } else {
+                if output.is_text() {
+                    println!("Waiting for completion...");
+                }
+                let timeout = timeout.unwrap_or(config.default_timeout);
+                let report = session_manager
+                    .wait_for_completion(&session_name, timeout, full)
+                    .await?;
+                print_completion(&report, output)?;
+            }
+        }
+
+        Commands::Fanout {
+            message,
+            file,
+            sessions,
+            dirs,
+            concurrency,
+            timeout,
+        } => {
+            let message = read_message(message, file.as_ref())?;
+            let targets = fanout::resolve_targets(&sessions, &dirs)?;
+            if output.is_text() {
+                println!(
+                    "Sending to {} session(s), {} at a time...",
+                    targets.len(),
+                    concurrency
+                );
+            }
+            let timeout = timeout.unwrap_or(config.default_timeout);
+            let results =
+                fanout::fan_out(&config, targets, &message, timeout, concurrency.into()).await?;
+
+            if output.is_text() {
+                print_fanout(&results);
+            } else {
+                output.print_all(&results)?;
+            }
+            let unfinished = results
+                .iter()
+                .filter(|result| result.exit_code != 0)
+                .count();
+            if unfinished > 0 {
+                return Err(anyhow::anyhow!(
+                    "{unfinished} of {} session(s) did not finish",
+                    results.len()
+                ));
+            }
+        }
+
+        Commands::Approve {
+            session,
+            always,
+            no_wait,
+            timeout,
+            full,
+        } => {
+            let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
+            let decision = if always {
+                PermissionDecision::ApproveAlways
+            } else {
+                PermissionDecision::Approve
+            };
+            let prompt = session_manager
+                .answer_permission(&session_name, decision)
+                .await?;
+
+            if output.is_text() {
+                println!(
+                    "{} {} in session: {session_name}",
+                    decision,
+                    prompt.detail.as_deref().unwrap_or(&prompt.tool)
+                );
+            }
+
+            if no_wait {
+                if !output.is_text() {
+                    output.print(&serde_json::json!({
+                        "session": session_name,
+                        "decision": decision,
+                        "prompt": prompt,
+                    }))?;
+                }
             } else {
-                println!("Waiting for completion...");
+                if output.is_text() {
+                    println!("Waiting for completion...");
+                }
+                let timeout = timeout.unwrap_or(config.default_timeout);
+                let report = session_manager
+                    .wait_for_completion(&session_name, timeout, full)
+                    .await?;
+                print_completion(&report, output)?;
+            }
+        }
+
+        Commands::Deny {
+            session,
+            message,
+            timeout,
+        } => {
+            let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
+            let prompt = session_manager
+                .answer_permission(&session_name, PermissionDecision::Deny)
+                .await?;
+
+            if output.is_text() {
+                println!(
+                    "Denied {} in session: {session_name}",
+                    prompt.detail.as_deref().unwrap_or(&prompt.tool)
+                );
+            }
+
+            if let Some(message) = message {
+                // Give Claude a moment to swap the dialog for the input box
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                session_manager
+                    .send_message(&session_name, &message)
+                    .await?;
+
+                if output.is_text() {
+                    println!("Waiting for completion...");
+                }
                 let timeout = timeout.unwrap_or(config.default_timeout);
-                let result = session_manager
-                    .wait_for_completion(&session_name, timeout)
+                let report = session_manager
+                    .wait_for_completion(&session_name, timeout, false)
                     .await?;
-                println!("{result}");
+                print_completion(&report, output)?;
+            } else if !output.is_text() {
+                output.print(&serde_json::json!({
+                    "session": session_name,
+                    "decision": PermissionDecision::Deny,
+                    "prompt": prompt,
+                }))?;
             }
         }
 
         Commands::Status { session, lines } => {
             let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
-            let status = session_manager.get_session_status(&session_name, lines).await?;
-            println!("Session status for '{session_name}':");
-            println!("{status}");
+            let status = session_manager
+                .get_session_status(&session_name, lines)
+                .await?;
+            let (state, prompt) = session_manager.get_session_state(&session_name).await?;
+            let tracked = daemon::tracked_session(&session_name);
+            if output.is_text() {
+                println!("Session status for '{session_name}': {state}");
+                if let Some(tracked) = &tracked {
+                    let completed = tracked
+                        .last_completed_at
+                        .map(|time| format!(", last completed {}", format_age(time)))
+                        .unwrap_or_default();
+                    println!(
+                        "Claude is {} since {}{completed}",
+                        tracked.state,
+                        format_age(tracked.since)
+                    );
+                }
+                if let Some(prompt) = &prompt {
+                    println!(
+                        "Waiting for permission to use {}{}",
+                        prompt.tool,
+                        prompt
+                            .detail
+                            .as_ref()
+                            .map(|detail| format!(": {detail}"))
+                            .unwrap_or_default()
+                    );
+                }
+                println!("{status}");
+            } else {
+                let mut document = serde_json::json!({
+                    "session": session_name,
+                    "status": state,
+                    "permission_prompt": prompt,
+                    "lines": lines,
+                    "output": status,
+                });
+                if let Some(tracked) = tracked {
+                    document["activity"] = serde_json::json!(tracked.state);
+                    document["since"] = serde_json::json!(tracked.since);
+                }
+                output.print(&document)?;
+            }
         }
 
-        Commands::Kill { session } => {
+        Commands::Kill { session, forget } => {
             let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
-            session_manager.kill_session(&session_name).await?;
-            println!("Killed session: {session_name}");
+            session_manager.kill_session(&session_name, forget).await?;
+            if output.is_text() {
+                println!("Killed session: {session_name}");
+            } else {
+                output.print(&serde_json::json!({ "session": session_name, "killed": true }))?;
+            }
         }
 
-        Commands::KillAll => {
-            let count = session_manager.kill_all_sessions().await?;
-            println!("Killed {count} session(s)");
+        Commands::KillAll { forget } => {
+            let killed = session_manager.kill_all_sessions(forget).await?;
+            if output.is_text() {
+                println!("Killed {} session(s)", killed.len());
+            } else {
+                output.print(&serde_json::json!({
+                    "killed": killed.len(),
+                    "sessions": killed,
+                }))?;
+            }
+        }
+
+        Commands::Revive {
+            session,
+            all,
+            message,
+            wait,
+            timeout,
+        } => {
+            let session_names = if all {
+                session_manager.ended_sessions().await?
+            } else {
+                vec![get_session_name_for_current_dir(
+                    &config,
+                    session.as_deref(),
+                )?]
+            };
+
+            let mut revived = Vec::new();
+            for session_name in &session_names {
+                let conversation_id = session_manager
+                    .revive_session(session_name, message.clone())
+                    .await?;
+                if output.is_text() {
+                    match &conversation_id {
+                        Some(id) => println!("Revived session {session_name} (conversation {id})"),
+                        None => println!(
+                            "Revived session {session_name} (continuing the most recent conversation in its directory)"
+                        ),
+                    }
+                }
+                revived.push(serde_json::json!({
+                    "session": session_name,
+                    "conversation_id": conversation_id,
+                }));
+            }
+
+            if wait {
+                let session_name = &session_names[0];
+                if output.is_text() {
+                    println!("Waiting for completion...");
+                }
+                let timeout = timeout.unwrap_or(config.default_timeout);
+                let report = session_manager
+                    .wait_for_completion(session_name, timeout, false)
+                    .await?;
+                print_completion(&report, output)?;
+            } else if output.is_text() {
+                if session_names.is_empty() {
+                    println!("No ended sessions to revive.");
+                }
+            } else if all {
+                output.print_all(&revived)?;
+            } else {
+                output.print(&revived[0])?;
+            }
         }
 
         Commands::History {
             session,
             lines,
             follow,
+            raw,
+            run,
+            all_runs,
         } => {
             let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
             if follow {
-                session_manager.follow_session_history(&session_name).await?;
+                session_manager
+                    .follow_session_history(&session_name)
+                    .await?;
+            } else if run.is_some() || all_runs {
+                let runs = match run {
+                    Some(number) => vec![logs::run(&session_name, number)?],
+                    None => session_manager.session_runs(&session_name)?,
+                };
+
+                let mut documents = Vec::new();
+                for run in &runs {
+                    let history = session_manager.get_run_history(run, lines, raw)?;
+                    if output.is_text() {
+                        let started = run
+                            .metadata
+                            .started_at
+                            .map(|time| {
+                                format!(", started {}", time.format("%Y-%m-%d %H:%M:%S UTC"))
+                            })
+                            .unwrap_or_default();
+                        let dir = run
+                            .metadata
+                            .working_dir
+                            .as_ref()
+                            .map(|dir| format!(" in {}", dir.display()))
+                            .unwrap_or_default();
+                        println!(
+                            "=== Session '{session_name}' run {}{started}{dir} ===",
+                            run.run
+                        );
+                        println!("{}", history.text(lines));
+                        println!();
+                    } else {
+                        let mut document = history.document(&session_name, lines);
+                        document["run"] = serde_json::json!(run.run);
+                        document["started_at"] = serde_json::json!(run.metadata.started_at);
+                        document["log"] = serde_json::json!(run.path);
+                        documents.push(document);
+                    }
+                }
+                if !output.is_text() {
+                    match run {
+                        Some(_) => output.print(&documents[0])?,
+                        None => output.print_all(&documents)?,
+                    }
+                }
             } else {
-                let history = session_manager.get_session_history(&session_name, lines).await?;
-                println!("Session history for '{session_name}':");
-                println!("{history}");
+                let history = session_manager
+                    .get_session_history(&session_name, lines, raw)
+                    .await?;
+                if output.is_text() {
+                    println!("Session history for '{session_name}':");
+                    println!("{}", history.text(lines));
+                } else {
+                    output.print(&history.document(&session_name, lines))?;
+                }
             }
         }
 
         Commands::Export {
             session,
-            output,
+            output_file,
+            format,
             clean,
+            raw,
         } => {
             let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
+            let format = format.unwrap_or_else(|| ExportFormat::from_path(&output_file));
             session_manager
-                .export_session_history(&session_name, &output, clean)
+                .export_session_history(&session_name, &output_file, format, clean, raw)
                 .await?;
-            println!(
-                "Exported session '{session_name}' history to: {}",
-                output.display()
-            );
+            if output.is_text() {
+                println!(
+                    "Exported session '{session_name}' history to: {}",
+                    output_file.display()
+                );
+            } else {
+                output.print(&serde_json::json!({
+                    "session": session_name,
+                    "path": output_file,
+                    "format": format.to_string(),
+                    "clean": clean,
+                }))?;
+            }
+        }
+
+        Commands::Search {
+            pattern,
+            ignore_case,
+            sessions,
+            since,
+            until,
+            context,
+            raw,
+        } => {
+            let query = SearchQuery {
+                pattern: regex::RegexBuilder::new(&pattern)
+                    .case_insensitive(ignore_case)
+                    .build()
+                    .map_err(|e| anyhow::anyhow!("Invalid pattern '{}': {}", pattern, e))?,
+                sessions,
+                since: since
+                    .as_deref()
+                    .map(|since| search::parse_time(since, false))
+                    .transpose()?,
+                until: until
+                    .as_deref()
+                    .map(|until| search::parse_time(until, true))
+                    .transpose()?,
+                context,
+                raw,
+            };
+            let hits = search::search(&query)?;
+
+            if !output.is_text() {
+                output.print_all(&hits)?;
+            } else if hits.is_empty() {
+                println!("No matches found.");
+            } else {
+                for hit in &hits {
+                    let mut heading = hit.session.clone();
+                    if let Some(run) = hit.run {
+                        heading.push_str(&format!(" · run {run}"));
+                    }
+                    heading.push_str(&format!(" · {}", hit.source));
+                    if let Some(timestamp) = hit.timestamp {
+                        heading
+                            .push_str(&format!(" · {}", timestamp.format("%Y-%m-%d %H:%M:%S UTC")));
+                    }
+                    println!("{heading}");
+                    for line in &hit.lines {
+                        let separator = if line.matched { ':' } else { '-' };
+                        println!("{}{separator} {}", line.number, line.text);
+                    }
+                    println!();
+                }
+                let matches: usize = hits
+                    .iter()
+                    .map(|hit| hit.lines.iter().filter(|line| line.matched).count())
+                    .sum();
+                let sessions: std::collections::HashSet<&str> =
+                    hits.iter().map(|hit| hit.session.as_str()).collect();
+                println!(
+                    "{matches} matching line(s) in {} session(s)",
+                    sessions.len()
+                );
+            }
+        }
+
+        Commands::Serve { host, port, socket } => {
+            let listen = match socket {
+                #[cfg(unix)]
+                Some(path) => server::Listen::Unix(path),
+                #[cfg(not(unix))]
+                Some(_) => anyhow::bail!("Unix sockets are not supported on this platform"),
+                None => server::Listen::Tcp(std::net::SocketAddr::new(host, port)),
+            };
+            server::serve(config, listen, output).await?;
+        }
+
+        Commands::Mcp => {
+            mcp::serve(config).await?;
         }
 
-        Commands::Config { .. } => {
-            // This should never be reached because Config is handled early
-            unreachable!("Config command should be handled before this match")
+        Commands::Daemon { daemon_command } => match daemon_command {
+            DaemonCommands::Start => {
+                let (status, started) = match daemon::status()? {
+                    Some(status) => (status, false),
+                    None => {
+                        let log_path = daemon::log_path();
+                        spawn_detached(&["daemon", "run"], &log_path, cli.config.as_ref())?;
+                        let status = wait_for_daemon(true, std::time::Duration::from_secs(5))?
+                            .ok_or_else(|| {
+                                anyhow::anyhow!(
+                                    "The daemon did not start, see {}",
+                                    log_path.display()
+                                )
+                            })?;
+                        (status, true)
+                    }
+                };
+                if !output.is_text() {
+                    output.print(&status)?;
+                } else if started {
+                    println!("Daemon started (pid {})", status.pid);
+                } else {
+                    println!("Daemon already running (pid {})", status.pid);
+                }
+            }
+
+            DaemonCommands::Stop => {
+                let stopped = daemon::request(&daemon::Request::Shutdown)?.is_some();
+                if stopped && wait_for_daemon(false, std::time::Duration::from_secs(10))?.is_some()
+                {
+                    return Err(anyhow::anyhow!("The daemon did not stop"));
+                }
+                if output.is_text() {
+                    println!(
+                        "{}",
+                        if stopped {
+                            "Daemon stopped"
+                        } else {
+                            "Daemon is not running"
+                        }
+                    );
+                } else {
+                    output.print(&serde_json::json!({ "stopped": stopped }))?;
+                }
+            }
+
+            DaemonCommands::Status => match daemon::status()? {
+                Some(status) if output.is_text() => print_daemon_status(&status),
+                Some(status) => output.print(&status)?,
+                None if output.is_text() => println!("Daemon is not running"),
+                None => output.print(&serde_json::json!({ "running": false }))?,
+            },
+
+            DaemonCommands::Run => {
+                daemon::run(config).await?;
+            }
+
+            DaemonCommands::Events { session, lines } => {
+                let events = daemon::read_events(session.as_deref());
+                let events = &events[events.len().saturating_sub(lines)..];
+                if !output.is_text() {
+                    output.print_all(events)?;
+                } else if events.is_empty() {
+                    println!("No events recorded. Start the daemon with 'claude-code-manager daemon start'");
+                } else {
+                    for event in events {
+                        println!("{event}");
+                    }
+                }
+            }
+        },
+
+        Commands::Logs { logs_command } => match logs_command {
+            LogsCommands::Prune {
+                dry_run,
+                older_than,
+            } => {
+                let actions = session_manager.prune_logs(dry_run, older_than)?;
+                if !output.is_text() {
+                    output.print_all(&actions)?;
+                } else if actions.is_empty() {
+                    println!("Nothing to prune.");
+                } else {
+                    for action in &actions {
+                        let verb = match (action.action, dry_run) {
+                            (LogActionKind::Rotated, false) => "Rotated",
+                            (LogActionKind::Removed, false) => "Removed",
+                            (LogActionKind::Rotated, true) => "Would rotate",
+                            (LogActionKind::Removed, true) => "Would remove",
+                        };
+                        println!(
+                            "{verb} {} ({}, {})",
+                            action.path.display(),
+                            logs::format_size(action.bytes),
+                            action.reason
+                        );
+                    }
+                    let freed: u64 = actions
+                        .iter()
+                        .filter(|action| action.action == LogActionKind::Removed)
+                        .map(|action| action.bytes)
+                        .sum();
+                    let verb = if dry_run { "Would free" } else { "Freed" };
+                    println!("{verb} {}", logs::format_size(freed));
+                }
+            }
+        },
+
+        Commands::Queue { queue_command } => match queue_command {
+            QueueCommands::Add {
+                messages,
+                file,
+                session,
+                timeout,
+                no_start,
+            } => {
+                let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
+                if !session_manager.session_exists(&session_name).await? {
+                    return Err(anyhow::anyhow!(
+                        "Session not found: {}. Start it before queueing messages",
+                        session_name
+                    ));
+                }
+
+                let mut texts = Vec::new();
+                for message in messages {
+                    texts.push(read_message(Some(message), None)?);
+                }
+                if let Some(file) = &file {
+                    texts.push(read_message(None, Some(file))?);
+                }
+                let (added, ahead) = MessageQueue::update(&session_name, |queue| {
+                    let ahead = queue
+                        .messages()
+                        .iter()
+                        .filter(|message| !message.status.is_finished())
+                        .count();
+                    let added: Vec<QueuedMessage> =
+                        texts.iter().map(|text| queue.push(text, timeout)).collect();
+                    (added, ahead)
+                })?;
+
+                let worker = if no_start {
+                    None
+                } else if queue::worker_running(&session_name) {
+                    Some("already running")
+                } else if daemon::request(&daemon::Request::RunQueue {
+                    session: session_name.clone(),
+                })?
+                .is_some()
+                {
+                    Some("started by the daemon")
+                } else {
+                    spawn_queue_worker(&session_name, cli.config.as_ref())?;
+                    Some("started")
+                };
+
+                if output.is_text() {
+                    for message in &added {
+                        println!(
+                            "Queued #{} for session '{session_name}': {}",
+                            message.id,
+                            truncate_message(&message.message, 60)
+                        );
+                    }
+                    if ahead > 0 {
+                        println!("{ahead} message(s) ahead in the queue");
+                    }
+                    match worker {
+                        Some(worker) => println!("Queue worker {worker}"),
+                        None => println!(
+                            "Send them with 'claude-code-manager queue run -s {session_name}'"
+                        ),
+                    }
+                } else {
+                    output.print_all(&added)?;
+                }
+            }
+
+            QueueCommands::List { session, all } => {
+                let sessions = if all {
+                    queue::queued_sessions()?
+                } else {
+                    vec![get_session_name_for_current_dir(
+                        &config,
+                        session.as_deref(),
+                    )?]
+                };
+
+                let mut messages = Vec::new();
+                for session_name in &sessions {
+                    let queue = MessageQueue::load(session_name)?;
+                    if output.is_text() {
+                        print_queue(session_name, queue.messages());
+                    }
+                    messages.extend(queue.messages().iter().cloned());
+                }
+                if !output.is_text() {
+                    output.print_all(&messages)?;
+                } else if all && sessions.is_empty() {
+                    println!("No queued messages.");
+                }
+            }
+
+            QueueCommands::Clear { session } => {
+                let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
+                let (cleared, kept) = MessageQueue::update(&session_name, |queue| {
+                    let cleared = queue.clear();
+                    (cleared, queue.messages().len())
+                })?;
+                if output.is_text() {
+                    println!(
+                        "Cleared {cleared} message(s) from the queue of session '{session_name}'"
+                    );
+                    if kept > 0 {
+                        println!("Kept the message Claude is working on");
+                    }
+                } else {
+                    output.print(&serde_json::json!({
+                        "session": session_name,
+                        "cleared": cleared,
+                    }))?;
+                }
+            }
+
+            QueueCommands::Run { session } => {
+                let session_name = get_session_name_for_current_dir(&config, session.as_deref())?;
+                let sent = session_manager.run_queue(&session_name).await?;
+                if output.is_text() {
+                    println!("Sent {sent} queued message(s) to session '{session_name}'");
+                } else {
+                    output.print(&serde_json::json!({
+                        "session": session_name,
+                        "sent": sent,
+                    }))?;
+                }
+            }
+        },
+
+        Commands::Config { .. } | Commands::Hooks { .. } | Commands::Hook { .. } => {
+            // This should never be reached because these are handled early
+            unreachable!("Config and hook commands should be handled before this match")
         }
     }
 
@@ -0,0 +1,526 @@
+//! Model Context Protocol server over stdio.
+//!
+//! `mcp` speaks newline-delimited JSON-RPC on stdin and stdout, so one Claude
+//! can start, drive and watch other Claude Code sessions as tools. Tool
+//! results are the same JSON documents the CLI prints with `--output json`.
+//! Each tool call runs on its own `SessionManager` on a blocking thread, so a
+//! long `wait_for_completion` does not hold up calls to other sessions.
+
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+use crate::claude::Conversation;
+use crate::session::{CompletionReport, SessionManager};
+use crate::Config;
+
+/// Protocol revisions this server speaks, newest first
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const INSTRUCTIONS: &str = "Manages Claude Code sessions running in tmux. \
+Start one with start_session, give it work with send_message and read the reply \
+from the result. For long tasks send with wait=false, do other work, then call \
+wait_for_completion. Sessions keep running until kill_session.";
+
+/// What to do with one incoming message
+#[derive(Debug, PartialEq)]
+enum Dispatch {
+    Respond(Value),
+    CallTool {
+        id: Value,
+        name: String,
+        arguments: Value,
+    },
+    Ignore,
+}
+
+#[derive(Debug, Deserialize)]
+struct StartArgs {
+    message: Option<String>,
+    session: Option<String>,
+    working_dir: Option<PathBuf>,
+    #[serde(default, rename = "continue")]
+    continue_conversation: bool,
+    resume: Option<String>,
+    #[serde(default)]
+    wait: bool,
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendArgs {
+    session: String,
+    message: String,
+    #[serde(default = "default_true")]
+    wait: bool,
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WaitArgs {
+    session: String,
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusArgs {
+    session: String,
+    #[serde(default = "default_status_lines")]
+    lines: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct KillArgs {
+    session: String,
+    #[serde(default)]
+    forget: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_status_lines() -> usize {
+    50
+}
+
+/// Serve MCP on stdin/stdout until stdin closes
+pub async fn serve(config: Config) -> Result<()> {
+    info!("MCP server started on stdio");
+
+    // Responses from concurrent tool calls go out one line at a time
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = receiver.recv().await {
+            stdout.write_all(format!("{message}\n").as_bytes()).await?;
+            stdout.flush().await?;
+        }
+        Ok::<_, std::io::Error>(())
+    });
+
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    let mut calls = tokio::task::JoinSet::new();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        debug!("MCP request: {}", line);
+
+        let dispatch = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => dispatch(&message),
+            Err(e) => Dispatch::Respond(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        match dispatch {
+            Dispatch::Respond(response) => {
+                let _ = sender.send(response);
+            }
+            Dispatch::CallTool {
+                id,
+                name,
+                arguments,
+            } => {
+                let config = config.clone();
+                let sender = sender.clone();
+                calls.spawn_blocking(move || {
+                    let manager = SessionManager::new(config.clone());
+                    let outcome = tokio::runtime::Handle::current()
+                        .block_on(call_tool(manager, &config, &name, arguments));
+                    let _ = sender.send(result_response(id, tool_result(&name, outcome)));
+                });
+            }
+            Dispatch::Ignore => {}
+        }
+    }
+
+    // Let calls in flight answer before exiting
+    while calls.join_next().await.is_some() {}
+    drop(sender);
+    writer.await??;
+    info!("MCP client closed stdin, exiting");
+    Ok(())
+}
+
+fn dispatch(message: &Value) -> Dispatch {
+    let Some(method) = message["method"].as_str() else {
+        // A response to a request we never make, or garbage
+        return match message.get("id") {
+            Some(id) if message.get("result").is_none() && message.get("error").is_none() => {
+                Dispatch::Respond(error_response(
+                    id.clone(),
+                    INVALID_REQUEST,
+                    "Missing method",
+                ))
+            }
+            _ => Dispatch::Ignore,
+        };
+    };
+    // Notifications have no id and get no response
+    let Some(id) = message.get("id").cloned() else {
+        debug!("MCP notification: {}", method);
+        return Dispatch::Ignore;
+    };
+    let params = &message["params"];
+
+    match method {
+        "initialize" => {
+            let requested = params["protocolVersion"].as_str().unwrap_or_default();
+            let version = PROTOCOL_VERSIONS
+                .into_iter()
+                .find(|version| *version == requested)
+                .unwrap_or(PROTOCOL_VERSIONS[0]);
+            Dispatch::Respond(result_response(
+                id,
+                json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                    "instructions": INSTRUCTIONS,
+                }),
+            ))
+        }
+        "ping" => Dispatch::Respond(result_response(id, json!({}))),
+        "tools/list" => Dispatch::Respond(result_response(id, json!({ "tools": tools() }))),
+        "tools/call" => {
+            let Some(name) = params["name"].as_str() else {
+                return Dispatch::Respond(error_response(id, INVALID_PARAMS, "Missing tool name"));
+            };
+            if !tools().iter().any(|tool| tool["name"] == name) {
+                return Dispatch::Respond(error_response(
+                    id,
+                    INVALID_PARAMS,
+                    &format!("Unknown tool: {name}"),
+                ));
+            }
+            Dispatch::CallTool {
+                id,
+                name: name.to_string(),
+                arguments: params
+                    .get("arguments")
+                    .cloned()
+                    .unwrap_or_else(|| json!({})),
+            }
+        }
+        _ => Dispatch::Respond(error_response(
+            id,
+            METHOD_NOT_FOUND,
+            &format!("Method not found: {method}"),
+        )),
+    }
+}
+
+fn result_response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// A tool's outcome as MCP content; failures are reported to the model
+/// rather than as protocol errors
+fn tool_result(name: &str, outcome: Result<Value>) -> Value {
+    match outcome {
+        Ok(document) => json!({
+            "content": [{
+                "type": "text",
+                "text": serde_json::to_string_pretty(&document).unwrap_or_default(),
+            }],
+            "isError": false,
+        }),
+        Err(e) => {
+            warn!("MCP tool {} failed: {:#}", name, e);
+            json!({
+                "content": [{ "type": "text", "text": format!("{e:#}") }],
+                "isError": true,
+            })
+        }
+    }
+}
+
+fn arguments<T: DeserializeOwned>(arguments: Value) -> Result<T> {
+    serde_json::from_value(arguments).map_err(|e| anyhow!("Invalid arguments: {}", e))
+}
+
+async fn call_tool(
+    mut manager: SessionManager,
+    config: &Config,
+    name: &str,
+    args: Value,
+) -> Result<Value> {
+    info!("MCP tool call: {}", name);
+    match name {
+        "start_session" => {
+            let args: StartArgs = arguments(args)?;
+            let conversation = match args.resume {
+                Some(id) => Conversation::Resume(id),
+                None if args.continue_conversation => Conversation::Continue,
+                None => Conversation::New,
+            };
+            if args.message.is_none() && conversation == Conversation::New {
+                return Err(anyhow!(
+                    "A message is required unless continuing or resuming a conversation"
+                ));
+            }
+            let working_dir = args.working_dir.or_else(|| std::env::current_dir().ok());
+            let session_name = args
+                .session
+                .unwrap_or_else(|| crate::session_name_for_dir(config, working_dir.as_deref()));
+
+            let session_name = manager
+                .start_session(args.message, Some(session_name), working_dir, conversation)
+                .await?;
+            if args.wait {
+                let timeout = args.timeout.unwrap_or(config.default_timeout);
+                to_document(
+                    manager
+                        .wait_for_completion(&session_name, timeout, false)
+                        .await?,
+                )
+            } else {
+                to_document(CompletionReport::not_waited(&session_name))
+            }
+        }
+        "send_message" => {
+            let args: SendArgs = arguments(args)?;
+            if !manager.session_exists(&args.session).await? {
+                return Err(anyhow!("Session not found: {}", args.session));
+            }
+            manager.send_message(&args.session, &args.message).await?;
+            if args.wait {
+                let timeout = args.timeout.unwrap_or(config.default_timeout);
+                to_document(
+                    manager
+                        .wait_for_completion(&args.session, timeout, false)
+                        .await?,
+                )
+            } else {
+                to_document(CompletionReport::not_waited(&args.session))
+            }
+        }
+        "wait_for_completion" => {
+            let args: WaitArgs = arguments(args)?;
+            let timeout = args.timeout.unwrap_or(config.default_timeout);
+            to_document(
+                manager
+                    .wait_for_completion(&args.session, timeout, false)
+                    .await?,
+            )
+        }
+        "get_session_status" => {
+            let args: StatusArgs = arguments(args)?;
+            let output = manager
+                .get_session_status(&args.session, args.lines)
+                .await?;
+            let (status, prompt) = manager.get_session_state(&args.session).await?;
+            Ok(json!({
+                "session": args.session,
+                "status": status,
+                "permission_prompt": prompt,
+                "lines": args.lines,
+                "output": output,
+            }))
+        }
+        "list_sessions" => to_document(manager.list_sessions().await?),
+        "kill_session" => {
+            let args: KillArgs = arguments(args)?;
+            manager.kill_session(&args.session, args.forget).await?;
+            Ok(json!({ "session": args.session, "killed": true }))
+        }
+        _ => Err(anyhow!("Unknown tool: {}", name)),
+    }
+}
+
+fn to_document(value: impl serde::Serialize) -> Result<Value> {
+    Ok(serde_json::to_value(value)?)
+}
+
+/// Tool definitions for `tools/list`
+fn tools() -> Vec<Value> {
+    let session = json!({ "type": "string", "description": "Session name" });
+    let timeout = json!({
+        "type": "integer",
+        "minimum": 1,
+        "description": "Seconds to wait for the turn to finish (default: the configured timeout)",
+    });
+
+    vec![
+        json!({
+            "name": "start_session",
+            "description": "Start a new Claude Code session in tmux, optionally with a first message. \
+                Returns the session name and, with wait, Claude's reply.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "message": { "type": "string", "description": "First message to send" },
+                    "session": {
+                        "type": "string",
+                        "description": "Session name (default: derived from the working directory)",
+                    },
+                    "working_dir": { "type": "string", "description": "Directory to run Claude Code in" },
+                    "continue": {
+                        "type": "boolean",
+                        "description": "Continue the most recent conversation in the directory",
+                    },
+                    "resume": { "type": "string", "description": "Conversation ID to resume" },
+                    "wait": { "type": "boolean", "description": "Wait for the first reply (default: false)" },
+                    "timeout": timeout,
+                },
+            },
+        }),
+        json!({
+            "name": "send_message",
+            "description": "Send a message to a running session. Unless wait is false, waits for \
+                Claude to finish its turn and returns its reply.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "session": session,
+                    "message": { "type": "string", "description": "Message to send" },
+                    "wait": { "type": "boolean", "description": "Wait for the reply (default: true)" },
+                    "timeout": timeout,
+                },
+                "required": ["session", "message"],
+            },
+        }),
+        json!({
+            "name": "wait_for_completion",
+            "description": "Wait for a session to finish its current turn and return Claude's reply, \
+                or report that it is blocked on a permission prompt.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "session": session, "timeout": timeout },
+                "required": ["session"],
+            },
+        }),
+        json!({
+            "name": "get_session_status",
+            "description": "Show what a session is doing and the last lines of its screen.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "session": session,
+                    "lines": { "type": "integer", "minimum": 1, "description": "Lines of output (default: 50)" },
+                },
+                "required": ["session"],
+            },
+        }),
+        json!({
+            "name": "list_sessions",
+            "description": "List Claude Code sessions, running and ended, with their directories and last messages.",
+            "inputSchema": { "type": "object", "properties": {} },
+        }),
+        json!({
+            "name": "kill_session",
+            "description": "Kill a session. Its conversation can be revived later unless forget is set.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "session": session,
+                    "forget": { "type": "boolean", "description": "Also forget the session's record" },
+                },
+                "required": ["session"],
+            },
+        }),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn respond(message: Value) -> Value {
+        match dispatch(&message) {
+            Dispatch::Respond(response) => response,
+            other => panic!("expected a response, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn initialize_negotiates_the_protocol_version() {
+        let response = respond(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": { "protocolVersion": "2025-03-26", "capabilities": {} },
+        }));
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+        assert!(response["result"]["capabilities"]["tools"].is_object());
+
+        let response = respond(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "initialize",
+            "params": { "protocolVersion": "1999-01-01" },
+        }));
+        assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSIONS[0]);
+    }
+
+    #[test]
+    fn tools_are_listed_and_dispatched_by_name() {
+        let response = respond(json!({ "jsonrpc": "2.0", "id": "a", "method": "tools/list" }));
+        let names: Vec<&str> = response["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "start_session",
+                "send_message",
+                "wait_for_completion",
+                "get_session_status",
+                "list_sessions",
+                "kill_session"
+            ]
+        );
+
+        let call = dispatch(&json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "tools/call",
+            "params": { "name": "list_sessions" },
+        }));
+        assert_eq!(
+            call,
+            Dispatch::CallTool {
+                id: json!(7),
+                name: "list_sessions".to_string(),
+                arguments: json!({}),
+            }
+        );
+
+        let unknown = respond(json!({
+            "jsonrpc": "2.0",
+            "id": 8,
+            "method": "tools/call",
+            "params": { "name": "rm_rf" },
+        }));
+        assert_eq!(unknown["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn notifications_are_ignored_and_unknown_methods_rejected() {
+        assert_eq!(
+            dispatch(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })),
+            Dispatch::Ignore
+        );
+        let response = respond(json!({ "jsonrpc": "2.0", "id": 3, "method": "resources/list" }));
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use serde::Serialize;
+
+/// How command results are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// A single pretty-printed JSON document
+    Json,
+    /// One compact JSON document per line
+    Jsonl,
+}
+
+impl OutputFormat {
+    pub fn is_text(&self) -> bool {
+        *self == OutputFormat::Text
+    }
+
+    /// Print a single structured document
+    pub fn print<T: Serialize>(&self, document: &T) -> Result<()> {
+        match self {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(document)?),
+            OutputFormat::Jsonl | OutputFormat::Text => {
+                println!("{}", serde_json::to_string(document)?)
+            }
+        }
+        Ok(())
+    }
+
+    /// Print a collection: a JSON array in `json` mode, one document per line in `jsonl` mode
+    pub fn print_all<T: Serialize>(&self, documents: &[T]) -> Result<()> {
+        match self {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(documents)?),
+            OutputFormat::Jsonl | OutputFormat::Text => {
+                for document in documents {
+                    println!("{}", serde_json::to_string(document)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+//! Per-session message queues.
+//!
+//! Queued messages live in `~/.claude-code-manager/queues/<session>.json`
+//! and are sent one at a time by a worker (`queue run`) that only moves on
+//! once Claude has finished the previous turn. Both `queue add` and the
+//! worker change the file, so every change happens under a lock.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::claude::ExitReason;
+use crate::open_lock;
+
+/// Where a queued message is in its life
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueStatus {
+    Pending,
+    /// Sent; Claude is working on it
+    Running,
+    /// Sent, and Claude is blocked on a permission prompt
+    AwaitingPermission,
+    Done,
+    /// Could not be sent, or Claude did not finish in time
+    Failed,
+}
+
+impl QueueStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, QueueStatus::Done | QueueStatus::Failed)
+    }
+}
+
+impl std::fmt::Display for QueueStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueStatus::Pending => write!(f, "pending"),
+            QueueStatus::Running => write!(f, "running"),
+            QueueStatus::AwaitingPermission => write!(f, "awaiting permission"),
+            QueueStatus::Done => write!(f, "done"),
+            QueueStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    pub id: u64,
+    pub session: String,
+    pub message: String,
+    pub status: QueueStatus,
+    /// Seconds to wait for Claude's turn (default: the configured timeout)
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    pub added_at: DateTime<Utc>,
+    #[serde(default)]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub exit_reason: Option<ExitReason>,
+    #[serde(default)]
+    pub response: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// A session's queue as stored on disk
+#[derive(Debug)]
+pub struct MessageQueue {
+    path: PathBuf,
+    session: String,
+    messages: Vec<QueuedMessage>,
+}
+
+pub fn queue_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home)
+        .join(".claude-code-manager")
+        .join("queues")
+}
+
+/// Sessions that have a queue file
+pub fn queued_sessions() -> Result<Vec<String>> {
+    let Ok(entries) = std::fs::read_dir(queue_dir()) else {
+        return Ok(Vec::new());
+    };
+    let mut sessions: Vec<String> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            name.strip_suffix(".json").map(str::to_string)
+        })
+        .collect();
+    sessions.sort();
+    Ok(sessions)
+}
+
+/// Take the worker lock of a session, or `None` if a worker already holds
+/// it. The lock is released when the file is dropped.
+pub fn try_lock_worker(session_name: &str) -> Result<Option<File>> {
+    let file = open_lock(&queue_dir().join(format!("{session_name}.worker.lock")))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(std::fs::TryLockError::WouldBlock) => Ok(None),
+        Err(std::fs::TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// Whether a worker is processing the session's queue
+pub fn worker_running(session_name: &str) -> bool {
+    matches!(try_lock_worker(session_name), Ok(None))
+}
+
+impl MessageQueue {
+    pub fn path(session_name: &str) -> PathBuf {
+        queue_dir().join(format!("{session_name}.json"))
+    }
+
+    pub fn load(session_name: &str) -> Result<Self> {
+        Self::load_from(&Self::path(session_name), session_name)
+    }
+
+    pub fn load_from(path: &Path, session_name: &str) -> Result<Self> {
+        let messages = match std::fs::read_to_string(path) {
+            Ok(content) if !content.trim().is_empty() => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse message queue: {}", path.display()))?,
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            session: session_name.to_string(),
+            messages,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if self.messages.is_empty() {
+            let _ = std::fs::remove_file(&self.path);
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(&self.messages)?)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        debug!("Saved message queue to: {}", self.path.display());
+        Ok(())
+    }
+
+    /// Load the session's queue, apply `f` and save it back, holding the
+    /// queue lock throughout
+    pub fn update<T>(session_name: &str, f: impl FnOnce(&mut MessageQueue) -> T) -> Result<T> {
+        let path = Self::path(session_name);
+        let lock = open_lock(&path.with_extension("lock"))?;
+        lock.lock()?;
+
+        let mut queue = Self::load_from(&path, session_name)?;
+        let result = f(&mut queue);
+        queue.save()?;
+        Ok(result)
+    }
+
+    pub fn messages(&self) -> &[QueuedMessage] {
+        &self.messages
+    }
+
+    /// Append a pending message
+    pub fn push(&mut self, message: &str, timeout: Option<u64>) -> QueuedMessage {
+        let id = self.messages.iter().map(|m| m.id).max().unwrap_or(0) + 1;
+        let queued = QueuedMessage {
+            id,
+            session: self.session.clone(),
+            message: message.to_string(),
+            status: QueueStatus::Pending,
+            timeout,
+            added_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            exit_reason: None,
+            response: None,
+            error: None,
+        };
+        self.messages.push(queued.clone());
+        queued
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.messages
+            .iter()
+            .any(|m| m.status == QueueStatus::Pending)
+    }
+
+    /// Mark the oldest pending message as running and return it
+    pub fn start_next(&mut self) -> Option<QueuedMessage> {
+        let next = self
+            .messages
+            .iter_mut()
+            .find(|m| m.status == QueueStatus::Pending)?;
+        next.status = QueueStatus::Running;
+        next.started_at = Some(Utc::now());
+        Some(next.clone())
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut QueuedMessage> {
+        self.messages.iter_mut().find(|m| m.id == id)
+    }
+
+    /// Record how a sent message ended
+    pub fn finish(&mut self, id: u64, outcome: Result<(ExitReason, Option<String>), String>) {
+        let Some(message) = self.get_mut(id) else {
+            return;
+        };
+        message.finished_at = Some(Utc::now());
+        match outcome {
+            Ok((reason, response)) => {
+                message.status = if reason.is_finished() {
+                    QueueStatus::Done
+                } else {
+                    QueueStatus::Failed
+                };
+                message.exit_reason = Some(reason);
+                message.response = response;
+            }
+            Err(error) => {
+                message.status = QueueStatus::Failed;
+                message.error = Some(error);
+            }
+        }
+    }
+
+    /// Drop pending and finished messages, keeping one that was already
+    /// sent. Returns how many were removed.
+    pub fn clear(&mut self) -> usize {
+        let before = self.messages.len();
+        self.messages.retain(|m| {
+            matches!(
+                m.status,
+                QueueStatus::Running | QueueStatus::AwaitingPermission
+            )
+        });
+        before - self.messages.len()
+    }
+
+    /// A message left running by a worker that died is failed, so the
+    /// queue can move on
+    pub fn fail_interrupted(&mut self) -> usize {
+        let mut failed = 0;
+        for message in &mut self.messages {
+            if matches!(
+                message.status,
+                QueueStatus::Running | QueueStatus::AwaitingPermission
+            ) {
+                message.status = QueueStatus::Failed;
+                message.finished_at = Some(Utc::now());
+                message.error = Some("The queue worker stopped before Claude finished".to_string());
+                failed += 1;
+            }
+        }
+        failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(dir: &Path) -> MessageQueue {
+        MessageQueue::load_from(&dir.join("dev.json"), "dev").unwrap()
+    }
+
+    #[test]
+    fn messages_are_started_in_order_and_results_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut q = queue(dir.path());
+        q.push("implement X", None);
+        q.push("write tests", Some(600));
+        q.save().unwrap();
+
+        let mut q = queue(dir.path());
+        let first = q.start_next().unwrap();
+        assert_eq!((first.id, first.message.as_str()), (1, "implement X"));
+        // Only one message is in flight at a time
+        q.finish(
+            first.id,
+            Ok((ExitReason::StopHook, Some("Done".to_string()))),
+        );
+        let second = q.start_next().unwrap();
+        assert_eq!((second.id, second.timeout), (2, Some(600)));
+        q.finish(second.id, Ok((ExitReason::Timeout, None)));
+        assert!(q.start_next().is_none());
+
+        let statuses: Vec<QueueStatus> = q.messages().iter().map(|m| m.status).collect();
+        assert_eq!(statuses, [QueueStatus::Done, QueueStatus::Failed]);
+        assert_eq!(q.messages()[0].response.as_deref(), Some("Done"));
+    }
+
+    #[test]
+    fn clear_keeps_the_message_in_flight() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut q = queue(dir.path());
+        q.push("one", None);
+        q.push("two", None);
+        q.push("three", None);
+        q.start_next();
+
+        assert_eq!(q.clear(), 2);
+        assert_eq!(q.messages().len(), 1);
+        assert_eq!(q.messages()[0].status, QueueStatus::Running);
+
+        // Ids carry on from the message that is left
+        assert_eq!(q.push("four", None).id, 2);
+
+        assert_eq!(q.fail_interrupted(), 1);
+        assert_eq!(q.clear(), 2);
+        q.save().unwrap();
+        assert!(!dir.path().join("dev.json").exists());
+    }
+}
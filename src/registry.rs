@@ -0,0 +1,325 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, info};
+
+use crate::open_lock;
+
+/// Saves made by this process, to give each its own temporary file
+static SAVES: AtomicU64 = AtomicU64::new(0);
+
+/// How Claude Code was launched for a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionMode {
+    #[default]
+    Default,
+    SkipPermissions,
+}
+
+impl std::fmt::Display for PermissionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionMode::Default => write!(f, "default"),
+            PermissionMode::SkipPermissions => write!(f, "skip-permissions"),
+        }
+    }
+}
+
+/// Metadata recorded for a managed session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub name: String,
+    pub working_dir: Option<PathBuf>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub initial_message: Option<String>,
+    #[serde(default)]
+    pub permission_mode: PermissionMode,
+    /// Claude Code program and arguments the session was started with
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub last_message: Option<String>,
+    #[serde(default)]
+    pub last_message_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_completed_at: Option<DateTime<Utc>>,
+    /// Claude Code conversation (session ID) running in the session, once known
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+    /// When the multiplexer session was found gone; the record is kept so
+    /// the conversation can be revived
+    #[serde(default)]
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl SessionRecord {
+    pub fn new(name: &str, working_dir: Option<PathBuf>, created_at: DateTime<Utc>) -> Self {
+        Self {
+            name: name.to_string(),
+            working_dir,
+            created_at,
+            initial_message: None,
+            permission_mode: PermissionMode::Default,
+            command: Vec::new(),
+            last_message: None,
+            last_message_at: None,
+            last_completed_at: None,
+            conversation_id: None,
+            ended_at: None,
+        }
+    }
+
+    /// Whether the session is gone but its conversation can be resumed
+    pub fn is_revivable(&self) -> bool {
+        self.conversation_id.is_some()
+    }
+}
+
+/// On-disk registry of managed sessions (~/.claude-code-manager/sessions.json)
+#[derive(Debug)]
+pub struct SessionRegistry {
+    path: PathBuf,
+    sessions: BTreeMap<String, SessionRecord>,
+}
+
+impl SessionRegistry {
+    pub fn default_path() -> PathBuf {
+        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+        PathBuf::from(home)
+            .join(".claude-code-manager")
+            .join("sessions.json")
+    }
+
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::default_path())
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let sessions = if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            if content.trim().is_empty() {
+                BTreeMap::new()
+            } else {
+                serde_json::from_str(&content).with_context(|| {
+                    format!("Failed to parse session registry: {}", path.display())
+                })?
+            }
+        } else {
+            debug!("No session registry found at: {}", path.display());
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            sessions,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary file first so a crash never leaves a truncated
+        // registry, named for this save so concurrent saves never share one
+        let tmp_path = self.path.with_extension(format!(
+            "json.{}.{}.tmp",
+            std::process::id(),
+            SAVES.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(&self.sessions)?)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        debug!("Saved session registry to: {}", self.path.display());
+        Ok(())
+    }
+
+    /// Load the registry, apply `f` and save it back, holding the registry
+    /// lock throughout so concurrent processes and threads never lose each
+    /// other's changes
+    pub fn update<T>(f: impl FnOnce(&mut SessionRegistry) -> T) -> Result<T> {
+        Self::update_at(&Self::default_path(), f)
+    }
+
+    fn update_at<T>(path: &Path, f: impl FnOnce(&mut SessionRegistry) -> T) -> Result<T> {
+        let lock = open_lock(&path.with_extension("lock"))?;
+        lock.lock()?;
+
+        let mut registry = Self::load_from(path)?;
+        let result = f(&mut registry);
+        registry.save()?;
+        Ok(result)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SessionRecord> {
+        self.sessions.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut SessionRecord> {
+        self.sessions.get_mut(name)
+    }
+
+    pub fn insert(&mut self, record: SessionRecord) {
+        self.sessions.insert(record.name.clone(), record);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<SessionRecord> {
+        self.sessions.remove(name)
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &SessionRecord> {
+        self.sessions.values()
+    }
+
+    /// Mark a session as gone, keeping its record only if it can be revived.
+    /// Returns whether the record was kept.
+    pub fn end(&mut self, name: &str) -> bool {
+        match self.sessions.get_mut(name) {
+            Some(record) if record.is_revivable() => {
+                record.ended_at.get_or_insert_with(Utc::now);
+                true
+            }
+            _ => {
+                self.sessions.remove(name);
+                false
+            }
+        }
+    }
+
+    /// Bring the registry in line with the sessions that actually exist.
+    ///
+    /// Records whose session is gone are marked ended if their conversation
+    /// can be revived and dropped otherwise, and live sessions without a
+    /// record are adopted using the metadata returned by `adopt`.
+    pub fn reconcile(
+        &mut self,
+        live_sessions: &[String],
+        mut adopt: impl FnMut(&str) -> SessionRecord,
+    ) {
+        let stale: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|(name, record)| !live_sessions.contains(name) && record.ended_at.is_none())
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in stale {
+            if self.end(&name) {
+                info!("Session ended, keeping it for revive: {}", name);
+            } else {
+                info!("Removing stale session from registry: {}", name);
+            }
+        }
+
+        for name in live_sessions {
+            match self.sessions.get_mut(name) {
+                Some(record) => record.ended_at = None,
+                None => {
+                    info!("Adopting untracked session into registry: {}", name);
+                    self.sessions.insert(name.clone(), adopt(name));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconcile_adopts_live_sessions_and_drops_stale_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut registry = SessionRegistry::load_from(&dir.path().join("sessions.json")).unwrap();
+        registry.insert(SessionRecord::new("claude-gone", None, Utc::now()));
+        registry.insert(SessionRecord::new("claude-kept", None, Utc::now()));
+
+        let live = vec!["claude-kept".to_string(), "claude-new".to_string()];
+        registry.reconcile(&live, |name| {
+            SessionRecord::new(name, Some(PathBuf::from("/work")), Utc::now())
+        });
+
+        let names: Vec<&str> = registry.records().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["claude-kept", "claude-new"]);
+        assert_eq!(
+            registry.get("claude-new").unwrap().working_dir,
+            Some(PathBuf::from("/work"))
+        );
+    }
+
+    #[test]
+    fn reconcile_keeps_ended_sessions_that_can_be_revived() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut registry = SessionRegistry::load_from(&dir.path().join("sessions.json")).unwrap();
+        let mut record = SessionRecord::new("claude-resumable", None, Utc::now());
+        record.conversation_id = Some("5f1c-conversation".to_string());
+        registry.insert(record);
+        registry.insert(SessionRecord::new("claude-plain", None, Utc::now()));
+
+        registry.reconcile(&[], |name| SessionRecord::new(name, None, Utc::now()));
+        let names: Vec<&str> = registry.records().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["claude-resumable"]);
+        assert!(registry.get("claude-resumable").unwrap().ended_at.is_some());
+
+        // Revived under the same name
+        let live = vec!["claude-resumable".to_string()];
+        registry.reconcile(&live, |name| SessionRecord::new(name, None, Utc::now()));
+        assert_eq!(registry.get("claude-resumable").unwrap().ended_at, None);
+    }
+
+    #[test]
+    fn concurrent_updates_are_all_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+
+        std::thread::scope(|scope| {
+            for writer in 0..8 {
+                let path = &path;
+                scope.spawn(move || {
+                    for n in 0..10 {
+                        SessionRegistry::update_at(path, |registry| {
+                            registry.insert(SessionRecord::new(
+                                &format!("claude-{writer}-{n}"),
+                                None,
+                                Utc::now(),
+                            ))
+                        })
+                        .unwrap();
+                    }
+                });
+            }
+        });
+
+        let registry = SessionRegistry::load_from(&path).unwrap();
+        assert_eq!(registry.records().count(), 80);
+        let leftovers: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty(), "{leftovers:?}");
+    }
+
+    #[test]
+    fn records_survive_a_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("sessions.json");
+
+        let mut registry = SessionRegistry::load_from(&path).unwrap();
+        let mut record = SessionRecord::new("claude-a", Some(PathBuf::from("/src")), Utc::now());
+        record.initial_message = Some("Fix the bug".to_string());
+        record.permission_mode = PermissionMode::SkipPermissions;
+        registry.insert(record);
+        registry.save().unwrap();
+
+        let loaded = SessionRegistry::load_from(&path).unwrap();
+        let record = loaded.get("claude-a").unwrap();
+        assert_eq!(record.initial_message.as_deref(), Some("Fix the bug"));
+        assert_eq!(record.permission_mode, PermissionMode::SkipPermissions);
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, error, info};
+
+use crate::backend::{self, LaunchCommand, SessionInfo, SpecialKey, TerminalBackend};
+use crate::logs;
+
+/// Pastes made by this process, to give each its own file
+static PASTES: AtomicU64 = AtomicU64::new(0);
+
+/// GNU screen backend for machines where tmux is unavailable
+pub struct ScreenManager;
+
+impl ScreenManager {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Run `screen -S <session> -X <command...>` against the session's first window
+    fn screen_command(&self, session_name: &str, args: &[&str]) -> Result<()> {
+        let output = Command::new("screen")
+            .args(["-S", session_name, "-p", "0", "-X"])
+            .args(args)
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            error!("screen {} failed: {}{}", args[0], stderr, stdout);
+            return Err(anyhow!(
+                "Failed to run screen {} for session {}: {}{}",
+                args[0],
+                session_name,
+                stderr.trim(),
+                stdout.trim()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Type `text` through a screen register, which unlike `stuff` does not
+    /// interpret `^X` or backslash escapes
+    fn paste_text(&self, session_name: &str, text: &str) -> Result<()> {
+        // Concurrent sends must never write or remove each other's file
+        let paste_file = std::env::temp_dir().join(format!(
+            "claude-code-manager-{}-{}-{}.paste",
+            session_name,
+            std::process::id(),
+            PASTES.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&paste_file, text)?;
+        let paste_path = paste_file.to_string_lossy().to_string();
+
+        let result = self
+            .screen_command(session_name, &["readreg", "p", &paste_path])
+            .and_then(|_| self.screen_command(session_name, &["paste", "p"]));
+
+        // The screen server reads the file asynchronously
+        std::thread::sleep(backend::PASTE_SETTLE_TIME);
+        let _ = std::fs::remove_file(&paste_file);
+        result
+    }
+}
+
+impl TerminalBackend for ScreenManager {
+    fn session_exists(&self, session_name: &str) -> Result<bool> {
+        debug!("Checking if screen session exists: {}", session_name);
+        Ok(self.list_sessions()?.iter().any(|s| s == session_name))
+    }
+
+    fn list_sessions(&self) -> Result<Vec<String>> {
+        debug!("Listing screen sessions");
+
+        // `screen -ls` exits non-zero even when sessions exist, so only the output matters
+        let output = Command::new("screen").arg("-ls").output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        // Session lines look like "\t12345.name\t(Detached)"
+        let sessions = stdout
+            .lines()
+            .filter(|line| line.starts_with('\t'))
+            .filter_map(|line| line.split_whitespace().next())
+            .filter_map(|id| id.split_once('.').map(|(_, name)| name.to_string()))
+            .collect();
+
+        Ok(sessions)
+    }
+
+    fn create_session(
+        &self,
+        session_name: &str,
+        working_dir: Option<&PathBuf>,
+        command: Option<&LaunchCommand>,
+    ) -> Result<()> {
+        info!("Creating screen session: {}", session_name);
+
+        if self.session_exists(session_name)? {
+            return Err(anyhow!("Session {} already exists", session_name));
+        }
+
+        let mut cmd = Command::new("screen");
+        cmd.args(["-dmS", session_name]);
+
+        if let Some(dir) = working_dir {
+            cmd.current_dir(dir);
+        }
+
+        if let Some(command) = command {
+            debug!("Session command: {}", command);
+            cmd.envs(&command.env);
+            cmd.arg(&command.program).args(&command.args);
+        }
+
+        let output = cmd.output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("Failed to create screen session: {}", stderr);
+            return Err(anyhow!("Failed to create screen session: {}", stderr));
+        }
+
+        info!("Successfully created screen session: {}", session_name);
+
+        self.enable_session_logging(session_name)?;
+
+        Ok(())
+    }
+
+    fn kill_session(&self, session_name: &str) -> Result<()> {
+        debug!("Killing screen session: {}", session_name);
+
+        if !self.session_exists(session_name)? {
+            debug!("Session {} doesn't exist", session_name);
+            return Ok(());
+        }
+
+        self.screen_command(session_name, &["quit"])?;
+        info!("Successfully killed screen session: {}", session_name);
+        Ok(())
+    }
+
+    fn send_keys(&self, session_name: &str, keys: &str) -> Result<()> {
+        debug!("Sending keys to screen session {}: {}", session_name, keys);
+        self.paste_text(session_name, keys)
+    }
+
+    fn send_special_key(&self, session_name: &str, key: SpecialKey) -> Result<()> {
+        debug!("Sending {:?} to screen session: {}", key, session_name);
+        self.screen_command(session_name, &["stuff", key.as_bytes()])
+    }
+
+    fn send_keys_with_enter(&self, session_name: &str, keys: &str) -> Result<()> {
+        debug!(
+            "Sending keys with Enter to screen session {}: {}",
+            session_name, keys
+        );
+
+        if backend::needs_paste(keys) {
+            // screen does not track bracketed-paste mode, but Claude Code always
+            // enables it, so mark the paste explicitly to keep newlines literal
+            self.paste_text(session_name, &format!("\x1b[200~{keys}\x1b[201~"))?;
+        } else {
+            self.paste_text(session_name, keys)?;
+        }
+
+        self.send_special_key(session_name, SpecialKey::Enter)
+    }
+
+    fn capture_pane(&self, session_name: &str, lines: Option<usize>) -> Result<String> {
+        debug!(
+            "Capturing window content from screen session: {}",
+            session_name
+        );
+
+        let dump_file = std::env::temp_dir().join(format!(
+            "claude-code-manager-{}-{}.hardcopy",
+            session_name,
+            std::process::id()
+        ));
+        let dump_path = dump_file.to_string_lossy().to_string();
+
+        // -h includes the scrollback buffer, matching tmux capture-pane -S
+        let args = if lines.is_some() {
+            vec!["hardcopy", "-h", dump_path.as_str()]
+        } else {
+            vec!["hardcopy", dump_path.as_str()]
+        };
+        self.screen_command(session_name, &args)?;
+
+        // hardcopy is written asynchronously by the screen server
+        let mut content = None;
+        for _ in 0..20 {
+            if let Ok(bytes) = std::fs::read(&dump_file) {
+                content = Some(String::from_utf8_lossy(&bytes).to_string());
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        let _ = std::fs::remove_file(&dump_file);
+
+        let content =
+            content.ok_or_else(|| anyhow!("Failed to capture screen session: {}", session_name))?;
+
+        // hardcopy pads the window with blank lines
+        let mut all_lines: Vec<&str> = content.lines().collect();
+        while all_lines.last().is_some_and(|line| line.trim().is_empty()) {
+            all_lines.pop();
+        }
+
+        let selected = match lines {
+            Some(n) if all_lines.len() > n => &all_lines[all_lines.len() - n..],
+            _ => &all_lines[..],
+        };
+
+        Ok(format!("{}\n", selected.join("\n")))
+    }
+
+    fn enable_session_logging(&self, session_name: &str) -> Result<()> {
+        debug!("Enabling logging for screen session: {}", session_name);
+
+        // Every run of the session gets a log of its own
+        let log_file = logs::new_run_log(session_name)?.display().to_string();
+
+        self.screen_command(session_name, &["logfile", &log_file])?;
+        self.screen_command(session_name, &["logfile", "flush", "1"])?;
+        self.screen_command(session_name, &["log", "on"])?;
+
+        info!(
+            "Enabled logging for session {} to: {}",
+            session_name, log_file
+        );
+        Ok(())
+    }
+
+    fn attach_session(&self, session_name: &str) -> Result<()> {
+        info!("Attaching to screen session: {}", session_name);
+
+        let status = Command::new("screen").args(["-r", session_name]).status()?;
+
+        if !status.success() {
+            error!("Failed to attach to screen session: {}", session_name);
+            return Err(anyhow!(
+                "Failed to attach to screen session: {}",
+                session_name
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn get_session_info(&self, session_name: &str) -> Result<SessionInfo> {
+        Err(anyhow!(
+            "Session info is not available from the screen backend: {}",
+            session_name
+        ))
+    }
+}
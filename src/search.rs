@@ -0,0 +1,420 @@
+//! Full-text search across the logs and transcripts of every session.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+use crate::logs::{self, RunLog};
+use crate::registry::SessionRegistry;
+use crate::terminal;
+use crate::transcript::{self, Transcript, TranscriptEntry};
+
+/// What to look for and where
+pub struct SearchQuery {
+    pub pattern: Regex,
+    /// Only search these sessions (all of them when empty)
+    pub sessions: Vec<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Lines shown before and after each match
+    pub context: usize,
+    /// Search terminal logs even where the conversation has a transcript
+    pub raw: bool,
+}
+
+impl SearchQuery {
+    fn in_range(&self, time: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time <= until)
+    }
+}
+
+/// Matching lines close together in one transcript or log, with their context
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub session: String,
+    /// Run of the session the text comes from, when known
+    pub run: Option<usize>,
+    /// `transcript` or `log`
+    pub source: &'static str,
+    pub path: PathBuf,
+    /// When the first matching line was written (for logs, when the run started)
+    pub timestamp: Option<DateTime<Utc>>,
+    pub lines: Vec<HitLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HitLine {
+    /// Line number in the rendered transcript or log, from 1
+    pub number: usize,
+    pub text: String,
+    pub matched: bool,
+}
+
+/// A line of rendered text and when it was written, if known
+type TimedLine = (Option<DateTime<Utc>>, String);
+
+/// Search every session's transcripts, and the logs of runs without one
+pub fn search(query: &SearchQuery) -> Result<Vec<SearchHit>> {
+    let registry = SessionRegistry::load()?;
+    let mut sessions = logs::logged_sessions()?;
+    sessions.extend(registry.records().map(|record| record.name.clone()));
+    sessions.sort();
+    sessions.dedup();
+    if !query.sessions.is_empty() {
+        sessions.retain(|session| query.sessions.contains(session));
+    }
+
+    let mut hits = Vec::new();
+    for session in &sessions {
+        debug!("Searching session: {}", session);
+        let mut searched = HashSet::new();
+
+        // Newest run first: a conversation resumed across runs is searched
+        // once, as part of the latest run it continued in
+        for run in logs::runs(session)?.iter().rev() {
+            if !query.raw {
+                if let Some(conversation_id) = &run.metadata.conversation_id {
+                    if !searched.insert(conversation_id.clone()) {
+                        continue;
+                    }
+                    if let Some(transcript) = load_transcript(
+                        conversation_id,
+                        run.metadata.working_dir.as_deref(),
+                        run.metadata.started_at.unwrap_or_default(),
+                    ) {
+                        hits.extend(search_transcript(
+                            query,
+                            session,
+                            Some(run.run),
+                            &transcript,
+                        ));
+                        continue;
+                    }
+                }
+            }
+            hits.extend(search_log(query, session, run));
+        }
+
+        // A conversation recorded before the session had per-run logs
+        if query.raw {
+            continue;
+        }
+        let Some(record) = registry.get(session) else {
+            continue;
+        };
+        if let Some(conversation_id) = &record.conversation_id {
+            if searched.insert(conversation_id.clone()) {
+                if let Some(transcript) = load_transcript(
+                    conversation_id,
+                    record.working_dir.as_deref(),
+                    record.created_at,
+                ) {
+                    hits.extend(search_transcript(query, session, None, &transcript));
+                }
+            }
+        }
+    }
+
+    hits.sort_by(|a, b| {
+        (&a.session, a.run, a.lines[0].number).cmp(&(&b.session, b.run, b.lines[0].number))
+    });
+    Ok(hits)
+}
+
+fn load_transcript(
+    conversation_id: &str,
+    working_dir: Option<&std::path::Path>,
+    since: DateTime<Utc>,
+) -> Option<Transcript> {
+    let path = transcript::find_transcript(
+        &transcript::projects_dir(),
+        Some(conversation_id),
+        working_dir,
+        since,
+    )?;
+    Transcript::load(&path)
+        .map_err(|e| warn!("Skipping transcript {}: {}", path.display(), e))
+        .ok()
+}
+
+fn search_transcript(
+    query: &SearchQuery,
+    session: &str,
+    run: Option<usize>,
+    transcript: &Transcript,
+) -> Vec<SearchHit> {
+    let lines = transcript_lines(&transcript.entries);
+    search_lines(query, &lines)
+        .into_iter()
+        .map(|(timestamp, lines)| SearchHit {
+            session: session.to_string(),
+            run,
+            source: "transcript",
+            path: transcript.path.clone(),
+            timestamp,
+            lines,
+        })
+        .collect()
+}
+
+fn search_log(query: &SearchQuery, session: &str, run: &RunLog) -> Vec<SearchHit> {
+    let started_at = run.metadata.started_at;
+    let last_written = std::fs::metadata(&run.path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::<Utc>::from);
+
+    // Log lines carry no time of their own, so the run must overlap the range
+    if query
+        .until
+        .is_some_and(|until| started_at.is_some_and(|started| started > until))
+        || query
+            .since
+            .is_some_and(|since| last_written.is_some_and(|written| written < since))
+    {
+        return Vec::new();
+    }
+
+    let rendered = match logs::render(&run.path, terminal::DEFAULT_ROWS, None) {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            warn!("Skipping log {}: {}", run.path.display(), e);
+            return Vec::new();
+        }
+    };
+    let lines: Vec<TimedLine> = rendered
+        .lines()
+        .map(|line| (None, line.to_string()))
+        .collect();
+
+    search_lines(query, &lines)
+        .into_iter()
+        .map(|(_, lines)| SearchHit {
+            session: session.to_string(),
+            run: Some(run.run),
+            source: "log",
+            path: run.path.clone(),
+            timestamp: started_at,
+            lines,
+        })
+        .collect()
+}
+
+/// The transcript as searchable lines: messages, tool calls and the full
+/// tool results, each line stamped with its entry's time
+fn transcript_lines(entries: &[TranscriptEntry]) -> Vec<TimedLine> {
+    let mut lines = Vec::new();
+    for entry in entries {
+        let text = match entry {
+            TranscriptEntry::User(message) => format!("> {}", message.text),
+            TranscriptEntry::Assistant(message) => format!("⏺ {}", message.text),
+            TranscriptEntry::ToolCall(call) => format!("⏺ {}", transcript::tool_call_summary(call)),
+            TranscriptEntry::ToolResult(result) => format!("  ⎿  {}", result.content),
+        };
+        lines.extend(
+            text.lines()
+                .map(|line| (entry.timestamp(), line.to_string())),
+        );
+    }
+    lines
+}
+
+/// Matching lines grouped with their context; overlapping windows are merged.
+/// Each group comes with the time of its first match.
+fn search_lines(
+    query: &SearchQuery,
+    lines: &[TimedLine],
+) -> Vec<(Option<DateTime<Utc>>, Vec<HitLine>)> {
+    let matches: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (time, text))| {
+            time.is_none_or(|time| query.in_range(time)) && query.pattern.is_match(text)
+        })
+        .map(|(index, _)| index)
+        .collect();
+    let matched: HashSet<usize> = matches.iter().copied().collect();
+
+    let mut windows: Vec<(usize, usize, usize)> = Vec::new();
+    for &index in &matches {
+        let start = index.saturating_sub(query.context);
+        let end = (index + query.context).min(lines.len() - 1);
+        match windows.last_mut() {
+            Some((_, last_end, _)) if start <= *last_end + 1 => *last_end = end,
+            _ => windows.push((start, end, index)),
+        }
+    }
+
+    windows
+        .into_iter()
+        .map(|(start, end, first_match)| {
+            let hit_lines = (start..=end)
+                .map(|index| HitLine {
+                    number: index + 1,
+                    text: lines[index].1.clone(),
+                    matched: matched.contains(&index),
+                })
+                .collect();
+            (lines[first_match].0, hit_lines)
+        })
+        .collect()
+}
+
+/// Parse `--since`/`--until`: an RFC 3339 time, a local date, or an age such
+/// as `90m`, `12h`, `7d` or `2w`. A date used as an upper bound covers the
+/// whole day.
+pub fn parse_time(value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let date = if end_of_day {
+            date.succ_opt().unwrap_or(date)
+        } else {
+            date
+        };
+        if let Some(time) = Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+            .earliest()
+        {
+            return Ok(time.with_timezone(&Utc));
+        }
+    }
+
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => 0,
+    };
+    match amount.parse::<i64>() {
+        Ok(amount) if seconds_per_unit > 0 => {
+            Ok(Utc::now() - chrono::Duration::seconds(amount * seconds_per_unit))
+        }
+        _ => Err(anyhow!(
+            "Invalid time '{}'. Use a date (2025-06-01), an RFC 3339 time or an age such as 12h or 7d",
+            value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::{AssistantMessage, UserMessage};
+
+    fn query(pattern: &str, context: usize) -> SearchQuery {
+        SearchQuery {
+            pattern: Regex::new(pattern).unwrap(),
+            sessions: Vec::new(),
+            since: None,
+            until: None,
+            context,
+            raw: false,
+        }
+    }
+
+    fn untimed(lines: &[&str]) -> Vec<TimedLine> {
+        lines.iter().map(|line| (None, line.to_string())).collect()
+    }
+
+    #[test]
+    fn nearby_matches_share_one_hit_with_context() {
+        let lines = untimed(&[
+            "a",
+            "error one",
+            "b",
+            "error two",
+            "c",
+            "d",
+            "e",
+            "error three",
+        ]);
+        let hits = search_lines(&query("error", 1), &lines);
+
+        assert_eq!(hits.len(), 2);
+        let numbers: Vec<usize> = hits[0].1.iter().map(|line| line.number).collect();
+        assert_eq!(numbers, [1, 2, 3, 4, 5]);
+        assert!(hits[0].1[1].matched && !hits[0].1[2].matched);
+        let numbers: Vec<usize> = hits[1].1.iter().map(|line| line.number).collect();
+        assert_eq!(numbers, [7, 8]);
+    }
+
+    #[test]
+    fn logs_are_searched_through_their_rotated_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("20250601T100000.000Z.log");
+        std::fs::write(&log, "checkout the migration branch\r\n").unwrap();
+        logs::rotate(&log, 5).unwrap();
+        std::fs::write(&log, "run the migration\r\n").unwrap();
+        let run = RunLog {
+            run: 1,
+            path: log,
+            metadata: Default::default(),
+        };
+
+        let hits = search_log(&query("migration", 0), "claude-rotated", &run);
+        assert_eq!(hits.len(), 1);
+        let lines: Vec<&str> = hits[0]
+            .lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect();
+        assert_eq!(
+            lines,
+            ["checkout the migration branch", "run the migration"]
+        );
+    }
+
+    #[test]
+    fn transcript_matches_outside_the_time_range_are_skipped() {
+        let old = "2025-06-01T10:00:00Z".parse().ok();
+        let new = "2025-06-03T10:00:00Z".parse().ok();
+        let entries = vec![
+            TranscriptEntry::User(UserMessage {
+                timestamp: old,
+                text: "fix src/main.rs".to_string(),
+            }),
+            TranscriptEntry::Assistant(AssistantMessage {
+                timestamp: new,
+                model: None,
+                text: "Edited src/main.rs\nDone".to_string(),
+            }),
+        ];
+        let mut query = query("main\\.rs", 0);
+        query.since = Some(parse_time("2025-06-02T00:00:00Z", false).unwrap());
+
+        let hits = search_lines(&query, &transcript_lines(&entries));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0, new);
+        assert_eq!(hits[0].1[0].text, "⏺ Edited src/main.rs");
+    }
+
+    #[test]
+    fn times_accept_dates_and_ages() {
+        let start = parse_time("2025-06-01", false).unwrap();
+        let end = parse_time("2025-06-01", true).unwrap();
+        assert_eq!(end - start, chrono::Duration::days(1));
+
+        let week_ago = parse_time("7d", false).unwrap();
+        let age = Utc::now() - week_ago;
+        assert!(
+            age >= chrono::Duration::days(7)
+                && age < chrono::Duration::days(7) + chrono::Duration::minutes(1)
+        );
+
+        assert!(parse_time("last tuesday", false).is_err());
+    }
+}
@@ -0,0 +1,489 @@
+//! Local HTTP API over the session manager.
+//!
+//! `serve` exposes start, send, wait, status, history and kill as JSON
+//! endpoints whose bodies match the CLI's `--output json` documents. Each
+//! request runs on its own `SessionManager` on a blocking thread, since
+//! waiting for a turn polls the multiplexer with blocking sleeps. Requests
+//! that start, send to or wait on a session hold that session's lock
+//! throughout, so two of them never type into the same pane or clear each
+//! other's hook events; requests to different sessions run in parallel.
+//!
+//! `GET /sessions/{name}/events` streams the session as server-sent events:
+//! output as it is replayed, state changes and completed turns, for watching
+//! a run in a browser or following up the moment it finishes.
+//!
+//! The API has no authentication. It binds to localhost by default, and a
+//! Unix socket is created readable by its owner only.
+
+use anyhow::{Context, Result};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::Stream;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, error, info, warn};
+
+use crate::claude::Conversation;
+use crate::logs;
+use crate::output::OutputFormat;
+use crate::session::{CompletionReport, SessionManager};
+use crate::stream::{self, StreamEvent};
+use crate::Config;
+
+/// Where the API listens
+pub enum Listen {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+#[derive(Clone)]
+struct AppState {
+    config: Config,
+    sessions: SessionLocks,
+}
+
+/// One lock per session, held across a send and the wait for its turn
+#[derive(Clone, Default)]
+struct SessionLocks(Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>);
+
+impl SessionLocks {
+    async fn lock(&self, session_name: &str) -> tokio::sync::OwnedMutexGuard<()> {
+        let lock = self
+            .0
+            .lock()
+            .unwrap()
+            .entry(session_name.to_string())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+}
+
+/// An error response: `{"error": "..."}` with a matching status code
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(session_name: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            format!("Session not found: {session_name}"),
+        )
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        // The session manager reports a missing session with this message
+        let message = format!("{error:#}");
+        let status = if message.starts_with("Session not found") {
+            StatusCode::NOT_FOUND
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
+        Self::new(status, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.message }));
+        (self.status, body).into_response()
+    }
+}
+
+type ApiResult<T> = std::result::Result<Json<T>, ApiError>;
+
+#[derive(Debug, Deserialize)]
+struct StartRequest {
+    message: Option<String>,
+    session: Option<String>,
+    working_dir: Option<PathBuf>,
+    #[serde(default, rename = "continue")]
+    continue_conversation: bool,
+    resume: Option<String>,
+    #[serde(default)]
+    wait: bool,
+    timeout: Option<u64>,
+    #[serde(default)]
+    full: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendRequest {
+    message: String,
+    #[serde(default = "default_true")]
+    wait: bool,
+    timeout: Option<u64>,
+    #[serde(default)]
+    full: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct WaitRequest {
+    timeout: Option<u64>,
+    #[serde(default)]
+    full: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusQuery {
+    #[serde(default = "default_status_lines")]
+    lines: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct KillQuery {
+    #[serde(default)]
+    forget: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    lines: Option<usize>,
+    #[serde(default)]
+    raw: bool,
+    run: Option<usize>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_status_lines() -> usize {
+    50
+}
+
+/// Serve the API until interrupted, printing where it listens
+pub async fn serve(config: Config, listen: Listen, output: OutputFormat) -> Result<()> {
+    let app = router(config);
+
+    match listen {
+        Listen::Tcp(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("Failed to listen on {addr}"))?;
+            let url = format!("http://{}", listener.local_addr()?);
+            announce(&url, output)?;
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?;
+        }
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            use std::os::unix::fs::PermissionsExt;
+
+            // A socket left behind by a server that did not shut down cleanly
+            if path.exists() {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+            }
+            let listener = tokio::net::UnixListener::bind(&path)
+                .with_context(|| format!("Failed to listen on {}", path.display()))?;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+            announce(&format!("unix:{}", path.display()), output)?;
+            let served = axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal())
+                .await;
+            let _ = std::fs::remove_file(&path);
+            served?;
+        }
+    }
+
+    info!("API server stopped");
+    Ok(())
+}
+
+fn announce(url: &str, output: OutputFormat) -> Result<()> {
+    info!("API server listening on {}", url);
+    if output.is_text() {
+        println!("Listening on {url} (Ctrl-C to stop)");
+    } else {
+        output.print(&serde_json::json!({ "url": url }))?;
+    }
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("Failed to listen for Ctrl-C: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
+fn router(config: Config) -> Router {
+    Router::new()
+        .route("/sessions", get(list_sessions).post(start_session))
+        .route("/sessions/{name}", get(session_status).delete(kill_session))
+        .route("/sessions/{name}/messages", post(send_message))
+        .route("/sessions/{name}/wait", post(wait_for_completion))
+        .route("/sessions/{name}/history", get(session_history))
+        .route("/sessions/{name}/events", get(session_events))
+        .with_state(AppState {
+            config,
+            sessions: SessionLocks::default(),
+        })
+}
+
+/// Run `f` with a fresh session manager on a blocking thread
+async fn with_manager<T, F, Fut>(state: AppState, f: F) -> ApiResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(SessionManager, Config) -> Fut + Send + 'static,
+    Fut: Future<Output = std::result::Result<T, ApiError>>,
+{
+    tokio::task::spawn_blocking(move || {
+        let manager = SessionManager::new(state.config.clone());
+        tokio::runtime::Handle::current().block_on(f(manager, state.config))
+    })
+    .await
+    .map_err(|e| ApiError::from(anyhow::Error::new(e)))?
+    .map(Json)
+}
+
+async fn require_session(
+    manager: &mut SessionManager,
+    session_name: &str,
+) -> std::result::Result<(), ApiError> {
+    if manager.session_exists(session_name).await? {
+        Ok(())
+    } else {
+        Err(ApiError::not_found(session_name))
+    }
+}
+
+async fn list_sessions(State(state): State<AppState>) -> ApiResult<Vec<crate::session::Session>> {
+    with_manager(state, move |mut manager, _| async move {
+        Ok(manager.list_sessions().await?)
+    })
+    .await
+}
+
+async fn start_session(
+    State(state): State<AppState>,
+    Json(request): Json<StartRequest>,
+) -> ApiResult<CompletionReport> {
+    let sessions = state.sessions.clone();
+    with_manager(state, move |mut manager, config| async move {
+        let conversation = match request.resume {
+            Some(id) => Conversation::Resume(id),
+            None if request.continue_conversation => Conversation::Continue,
+            None => Conversation::New,
+        };
+        if request.message.is_none() && matches!(conversation, Conversation::New) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "A message is required unless continuing or resuming a conversation",
+            ));
+        }
+
+        // Name the session the way the CLI would from inside the directory
+        let working_dir = match request.working_dir {
+            Some(dir) => Some(dir),
+            None => std::env::current_dir().ok(),
+        };
+        let session_name = request
+            .session
+            .unwrap_or_else(|| crate::session_name_for_dir(&config, working_dir.as_deref()));
+        let _lock = sessions.lock(&session_name).await;
+        if manager.session_exists(&session_name).await? {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                format!("Session {session_name} already exists"),
+            ));
+        }
+
+        let session_name = manager
+            .start_session(
+                request.message,
+                Some(session_name),
+                working_dir,
+                conversation,
+            )
+            .await?;
+
+        if request.wait {
+            let timeout = request.timeout.unwrap_or(config.default_timeout);
+            Ok(manager
+                .wait_for_completion(&session_name, timeout, request.full)
+                .await?)
+        } else {
+            Ok(CompletionReport::not_waited(&session_name))
+        }
+    })
+    .await
+}
+
+async fn session_status(
+    State(state): State<AppState>,
+    Path(session_name): Path<String>,
+    Query(query): Query<StatusQuery>,
+) -> ApiResult<serde_json::Value> {
+    with_manager(state, move |mut manager, _| async move {
+        require_session(&mut manager, &session_name).await?;
+        let status = manager
+            .get_session_status(&session_name, query.lines)
+            .await?;
+        let (state, prompt) = manager.get_session_state(&session_name).await?;
+        Ok(serde_json::json!({
+            "session": session_name,
+            "status": state,
+            "permission_prompt": prompt,
+            "lines": query.lines,
+            "output": status,
+        }))
+    })
+    .await
+}
+
+async fn send_message(
+    State(state): State<AppState>,
+    Path(session_name): Path<String>,
+    Json(request): Json<SendRequest>,
+) -> ApiResult<CompletionReport> {
+    let sessions = state.sessions.clone();
+    with_manager(state, move |mut manager, config| async move {
+        // Claude is not typed to until the turn of an earlier request ends
+        let _lock = sessions.lock(&session_name).await;
+        require_session(&mut manager, &session_name).await?;
+        manager
+            .send_message(&session_name, &request.message)
+            .await?;
+
+        if request.wait {
+            let timeout = request.timeout.unwrap_or(config.default_timeout);
+            Ok(manager
+                .wait_for_completion(&session_name, timeout, request.full)
+                .await?)
+        } else {
+            Ok(CompletionReport::not_waited(&session_name))
+        }
+    })
+    .await
+}
+
+async fn wait_for_completion(
+    State(state): State<AppState>,
+    Path(session_name): Path<String>,
+    request: Option<Json<WaitRequest>>,
+) -> ApiResult<CompletionReport> {
+    let Json(request) = request.unwrap_or_default();
+    let sessions = state.sessions.clone();
+    with_manager(state, move |mut manager, config| async move {
+        let _lock = sessions.lock(&session_name).await;
+        require_session(&mut manager, &session_name).await?;
+        let timeout = request.timeout.unwrap_or(config.default_timeout);
+        Ok(manager
+            .wait_for_completion(&session_name, timeout, request.full)
+            .await?)
+    })
+    .await
+}
+
+async fn session_history(
+    State(state): State<AppState>,
+    Path(session_name): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> ApiResult<serde_json::Value> {
+    with_manager(state, move |mut manager, _| async move {
+        match query.run {
+            Some(number) => {
+                let run = logs::run(&session_name, number)
+                    .map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e.to_string()))?;
+                let history = manager.get_run_history(&run, query.lines, query.raw)?;
+                let mut document = history.document(&session_name, query.lines);
+                document["run"] = serde_json::json!(run.run);
+                document["started_at"] = serde_json::json!(run.metadata.started_at);
+                document["log"] = serde_json::json!(run.path);
+                Ok(document)
+            }
+            None => {
+                let history = manager
+                    .get_session_history(&session_name, query.lines, query.raw)
+                    .await?;
+                Ok(history.document(&session_name, query.lines))
+            }
+        }
+    })
+    .await
+}
+
+async fn kill_session(
+    State(state): State<AppState>,
+    Path(session_name): Path<String>,
+    Query(query): Query<KillQuery>,
+) -> ApiResult<serde_json::Value> {
+    with_manager(state, move |mut manager, _| async move {
+        manager.kill_session(&session_name, query.forget).await?;
+        Ok(serde_json::json!({ "session": session_name, "killed": true }))
+    })
+    .await
+}
+
+async fn session_events(
+    State(state): State<AppState>,
+    Path(session_name): Path<String>,
+) -> std::result::Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>, ApiError>
+{
+    let watcher = with_manager(state, move |mut manager, _| async move {
+        require_session(&mut manager, &session_name).await?;
+        Ok(manager.watch_session(&session_name))
+    })
+    .await?
+    .0;
+
+    // Poll on a blocking thread until the session exits or the client leaves
+    let (sender, receiver) = tokio::sync::mpsc::channel::<StreamEvent>(64);
+    tokio::task::spawn_blocking(move || {
+        let mut watcher = watcher;
+        loop {
+            let events = match watcher.poll() {
+                Ok(events) => events,
+                Err(e) => {
+                    warn!("Failed to poll session: {}", e);
+                    break;
+                }
+            };
+            for event in events {
+                if sender.blocking_send(event).is_err() {
+                    debug!("Event stream client disconnected");
+                    return;
+                }
+            }
+            if watcher.finished() || sender.is_closed() {
+                break;
+            }
+            std::thread::sleep(stream::POLL_INTERVAL);
+        }
+    });
+
+    let events = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let event = receiver.recv().await?;
+        let sse = Event::default()
+            .event(event.kind())
+            .json_data(&event)
+            .unwrap_or_else(|_| Event::default().comment("unserializable event"));
+        Some((Ok(sse), receiver))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
@@ -2,10 +2,20 @@ use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::Arc;
 use tracing::{debug, error, info, warn};
 
-use crate::claude::ClaudeCodeManager;
-use crate::tmux::TmuxManager;
+use crate::backend::{self, LaunchCommand, TerminalBackend};
+use crate::claude::{self, ClaudeCodeManager, Conversation, ExitReason};
+use crate::export::{self, ExportFormat};
+use crate::hooks::{self, HookEvent};
+use crate::logs::{self, LogAction, RunLog};
+use crate::permissions::{PermissionDecision, PermissionPrompt};
+use crate::queue::{self, MessageQueue, QueueStatus, QueuedMessage};
+use crate::registry::{PermissionMode, SessionRecord, SessionRegistry};
+use crate::stream::{self, Activity, SessionWatcher};
+use crate::terminal;
+use crate::transcript::{self, Transcript};
 use crate::Config;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,12 +25,45 @@ pub struct Session {
     pub working_dir: Option<PathBuf>,
     pub created_at: DateTime<Utc>,
     pub status: SessionStatus,
+    pub initial_message: Option<String>,
+    pub permission_mode: PermissionMode,
+    pub command: Vec<String>,
+    pub last_message: Option<String>,
+    pub last_message_at: Option<DateTime<Utc>>,
+    pub last_completed_at: Option<DateTime<Utc>>,
+    pub conversation_id: Option<String>,
+    pub ended_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Session {
+    fn from_record(record: &SessionRecord, status: SessionStatus) -> Self {
+        Self {
+            id: record.name.clone(),
+            name: record.name.clone(),
+            working_dir: record.working_dir.clone(),
+            created_at: record.created_at,
+            status,
+            initial_message: record.initial_message.clone(),
+            permission_mode: record.permission_mode,
+            command: record.command.clone(),
+            last_message: record.last_message.clone(),
+            last_message_at: record.last_message_at,
+            last_completed_at: record.last_completed_at,
+            conversation_id: record.conversation_id.clone(),
+            ended_at: record.ended_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SessionStatus {
     Active,
     Idle,
+    /// Blocked on a permission prompt until it is approved or denied
+    AwaitingPermission,
+    /// The multiplexer session is gone; `revive` resumes its conversation
+    Ended,
     Failed,
 }
 
@@ -29,32 +72,124 @@ impl std::fmt::Display for SessionStatus {
         match self {
             SessionStatus::Active => write!(f, "active"),
             SessionStatus::Idle => write!(f, "idle"),
+            SessionStatus::AwaitingPermission => write!(f, "awaiting permission"),
+            SessionStatus::Ended => write!(f, "ended"),
             SessionStatus::Failed => write!(f, "failed"),
         }
     }
 }
 
+/// Outcome of sending a message or waiting for a session to finish its turn
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionReport {
+    pub session: String,
+    pub status: SessionStatus,
+    pub exit_reason: ExitReason,
+    pub elapsed_secs: f64,
+    pub response: Option<String>,
+    /// Hook event that ended the wait, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<HookEvent>,
+    /// Permission dialog the session is blocked on, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permission_prompt: Option<PermissionPrompt>,
+}
+
+impl CompletionReport {
+    /// Report for a message that was sent without waiting for the reply
+    pub fn not_waited(session_name: &str) -> Self {
+        Self {
+            session: session_name.to_string(),
+            status: SessionStatus::Active,
+            exit_reason: ExitReason::NotWaited,
+            elapsed_secs: 0.0,
+            response: None,
+            event: None,
+            permission_prompt: None,
+        }
+    }
+}
+
+/// A session's history, from Claude Code's transcript when it can be found
+/// and from the terminal log otherwise
+pub enum SessionHistory {
+    Transcript(Transcript),
+    Log(String),
+}
+
+impl SessionHistory {
+    pub fn source(&self) -> &'static str {
+        match self {
+            SessionHistory::Transcript(_) => "transcript",
+            SessionHistory::Log(_) => "log",
+        }
+    }
+
+    /// Readable text, limited to the last `lines` lines of a transcript
+    /// (logs are already limited when read)
+    pub fn text(&self, lines: Option<usize>) -> String {
+        match self {
+            SessionHistory::Transcript(transcript) => {
+                let text = transcript.render_text();
+                match lines {
+                    Some(lines) => {
+                        let all: Vec<&str> = text.lines().collect();
+                        all[all.len().saturating_sub(lines)..].join("\n")
+                    }
+                    None => text,
+                }
+            }
+            SessionHistory::Log(log) => log.clone(),
+        }
+    }
+
+    /// Structured form, as printed by `history --output json`
+    pub fn document(&self, session_name: &str, lines: Option<usize>) -> serde_json::Value {
+        let mut document = serde_json::json!({
+            "session": session_name,
+            "source": self.source(),
+            "lines": lines,
+            "history": self.text(lines),
+        });
+        if let SessionHistory::Transcript(transcript) = self {
+            document["conversation_id"] = serde_json::json!(transcript.conversation_id);
+            document["transcript"] = serde_json::json!(transcript.path);
+            document["entries"] = serde_json::json!(transcript.entries);
+        }
+        document
+    }
+}
+
+/// How far back in the pane scrollback to look for the prompt echo
+const RESPONSE_SCROLLBACK_LINES: usize = 2000;
+
 pub struct SessionManager {
     claude: ClaudeCodeManager,
-    tmux: TmuxManager,
-    #[allow(dead_code)]
+    backend: Arc<dyn TerminalBackend>,
     config: Config,
 }
 
 impl SessionManager {
     pub fn new(config: Config) -> Self {
+        let backend = backend::create_backend(config.backend);
+        Self::with_backend(config, backend)
+    }
+
+    /// Create a session manager on top of an explicit terminal backend
+    pub fn with_backend(config: Config, backend: Arc<dyn TerminalBackend>) -> Self {
         Self {
-            claude: ClaudeCodeManager::new(config.clone()),
-            tmux: TmuxManager::new(),
+            claude: ClaudeCodeManager::with_backend(config.clone(), backend.clone()),
+            backend,
             config,
         }
     }
 
     pub async fn start_session(
         &mut self,
-        message: String,
+        message: Option<String>,
         session_name: Option<String>,
         working_dir: Option<PathBuf>,
+        conversation: Conversation,
     ) -> Result<String> {
         // Generate session name
         let session_name = session_name.unwrap_or_else(|| {
@@ -64,13 +199,50 @@ impl SessionManager {
 
         info!("Starting new Claude Code session: {}", session_name);
 
+        if self.backend.session_exists(&session_name)? {
+            return Err(anyhow!(
+                "Session {} already exists. Send it a message, or kill it first to start over",
+                session_name
+            ));
+        }
+
+        // Starting is rare enough to also keep logs of long-running sessions
+        // from growing without bound when no daemon is running
+        if let Err(e) = self.rotate_logs() {
+            warn!("Failed to rotate session logs: {}", e);
+        }
+
         // Start the Claude Code session
-        match self
-            .claude
-            .start_claude_session(&session_name, working_dir.as_ref(), &message)
-        {
+        let base_command = self.claude.launch_command();
+        let mut command = base_command.clone();
+        command.args.extend(conversation.args());
+
+        match self.claude.start_claude_session(
+            &session_name,
+            working_dir.as_ref(),
+            &command,
+            message.as_deref(),
+        ) {
             Ok(_) => {
                 info!("Successfully started Claude Code session: {}", session_name);
+                let conversation_id = match conversation {
+                    Conversation::Resume(id) => Some(id),
+                    _ => None,
+                };
+                self.record_run(
+                    &session_name,
+                    working_dir.clone(),
+                    &command,
+                    message.as_deref(),
+                    conversation_id.clone(),
+                );
+                self.record_started_session(
+                    &session_name,
+                    working_dir,
+                    message.as_deref(),
+                    &base_command,
+                    conversation_id,
+                )?;
                 Ok(session_name)
             }
             Err(e) => {
@@ -84,44 +256,269 @@ impl SessionManager {
         debug!("Listing all Claude Code sessions");
 
         let claude_sessions = self.claude.list_claude_sessions()?;
+        for session_name in &claude_sessions {
+            self.record_conversation(session_name)?;
+        }
+
+        // Reconcile the registry with what tmux actually has running
+        let backend = self.backend.as_ref();
+        let registry = SessionRegistry::update(|registry| {
+            registry.reconcile(&claude_sessions, |name| Self::adopt_session(backend, name));
+            registry.records().cloned().collect::<Vec<_>>()
+        })?;
+
         let mut sessions = Vec::new();
 
-        for session_name in claude_sessions {
+        for record in registry {
             // Get tmux session info if available
-            let status = if self.tmux.session_exists(&session_name)? {
-                SessionStatus::Active
+            let status = if self.backend.session_exists(&record.name)? {
+                self.live_status(&record.name)
+            } else if record.ended_at.is_some() {
+                SessionStatus::Ended
             } else {
                 SessionStatus::Failed
             };
 
-            let session = Session {
-                id: session_name.clone(),
-                name: session_name,
-                working_dir: None,      // We don't track this for existing sessions
-                created_at: Utc::now(), // We don't have the real creation time
-                status,
-            };
-
-            sessions.push(session);
+            sessions.push(Session::from_record(&record, status));
         }
 
         Ok(sessions)
     }
 
+    /// Status of a running session, as far as its pane shows
+    fn live_status(&self, session_name: &str) -> SessionStatus {
+        match self.claude.pending_permission_prompt(session_name) {
+            Ok(Some(_)) => SessionStatus::AwaitingPermission,
+            _ => SessionStatus::Active,
+        }
+    }
+
+    /// Build a registry record for a session that was not started by this tool
+    fn adopt_session(backend: &dyn TerminalBackend, session_name: &str) -> SessionRecord {
+        match backend.get_session_info(session_name) {
+            Ok(info) => {
+                let created_at = DateTime::<Utc>::from_timestamp(info.created as i64, 0)
+                    .unwrap_or_else(Utc::now);
+                SessionRecord::new(session_name, Some(info.path), created_at)
+            }
+            Err(e) => {
+                warn!("Failed to get info for session {}: {}", session_name, e);
+                SessionRecord::new(session_name, None, Utc::now())
+            }
+        }
+    }
+
+    fn record_started_session(
+        &self,
+        session_name: &str,
+        working_dir: Option<PathBuf>,
+        message: Option<&str>,
+        command: &LaunchCommand,
+        conversation_id: Option<String>,
+    ) -> Result<()> {
+        let mut record = SessionRecord::new(session_name, working_dir, Utc::now());
+        record.initial_message = message.map(str::to_string);
+        record.permission_mode = if self.config.skip_permissions {
+            PermissionMode::SkipPermissions
+        } else {
+            PermissionMode::Default
+        };
+        // Without the conversation arguments, so revive can pick its own
+        record.command = command.argv();
+        record.conversation_id = conversation_id;
+        if let Some(message) = message {
+            record.last_message = Some(message.to_string());
+            record.last_message_at = Some(record.created_at);
+        }
+
+        SessionRegistry::update(|registry| registry.insert(record))
+    }
+
+    /// Describe the run that just started next to its log
+    fn record_run(
+        &self,
+        session_name: &str,
+        working_dir: Option<PathBuf>,
+        command: &LaunchCommand,
+        message: Option<&str>,
+        conversation_id: Option<String>,
+    ) {
+        let result = logs::update_current_run(session_name, |run| {
+            run.session = session_name.to_string();
+            run.working_dir = working_dir;
+            run.command = command.argv();
+            run.initial_message = message.map(str::to_string);
+            run.conversation_id = conversation_id;
+        });
+        if let Err(e) = result {
+            warn!(
+                "Failed to record the run of session {}: {}",
+                session_name, e
+            );
+        }
+    }
+
+    /// Remember the Claude Code conversation reported by hook events the
+    /// session has recorded since its last completed wait
+    fn record_conversation(&self, session_name: &str) -> Result<()> {
+        match hooks::read_events(session_name)
+            .into_iter()
+            .rev()
+            .find_map(|event| event.claude_session_id)
+        {
+            Some(conversation_id) => self.remember_conversation(session_name, conversation_id),
+            None => Ok(()),
+        }
+    }
+
+    fn remember_conversation(&self, session_name: &str, conversation_id: String) -> Result<()> {
+        logs::update_current_run(session_name, |run| {
+            run.conversation_id = Some(conversation_id.clone());
+        })?;
+        SessionRegistry::update(|registry| {
+            if let Some(record) = registry.get_mut(session_name) {
+                if record.conversation_id.as_deref() != Some(conversation_id.as_str()) {
+                    debug!(
+                        "Session {} is in conversation {}",
+                        session_name, conversation_id
+                    );
+                    record.conversation_id = Some(conversation_id);
+                }
+            }
+        })
+    }
+
+    /// Recreate an ended session and resume its last conversation, or the
+    /// most recent conversation in its directory if none was recorded.
+    /// Returns the conversation ID resumed, if known.
+    pub async fn revive_session(
+        &mut self,
+        session_name: &str,
+        message: Option<String>,
+    ) -> Result<Option<String>> {
+        info!("Reviving session: {}", session_name);
+
+        let record = SessionRegistry::load()?
+            .get(session_name)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "No record of session {}. Use 'start --resume <conversation-id>' to resume a conversation by ID",
+                    session_name
+                )
+            })?;
+
+        if self.backend.session_exists(session_name)? {
+            return Err(anyhow!("Session {} is still running", session_name));
+        }
+
+        let mut command = match record.command.split_first() {
+            Some((program, args)) => LaunchCommand {
+                program: program.clone(),
+                args: args.to_vec(),
+                env: self.config.claude_env.clone(),
+            },
+            None => self.claude.launch_command(),
+        };
+        let conversation = match &record.conversation_id {
+            Some(id) => Conversation::Resume(id.clone()),
+            None => Conversation::Continue,
+        };
+        command.args.extend(conversation.args());
+
+        match self.claude.start_claude_session(
+            session_name,
+            record.working_dir.as_ref(),
+            &command,
+            message.as_deref(),
+        ) {
+            Ok(_) => {
+                info!("Revived session: {}", session_name);
+                self.record_run(
+                    session_name,
+                    record.working_dir.clone(),
+                    &command,
+                    message.as_deref(),
+                    record.conversation_id.clone(),
+                );
+                SessionRegistry::update(|registry| {
+                    if let Some(record) = registry.get_mut(session_name) {
+                        record.ended_at = None;
+                        if let Some(message) = &message {
+                            record.last_message = Some(message.clone());
+                            record.last_message_at = Some(Utc::now());
+                        }
+                    }
+                })?;
+                Ok(record.conversation_id)
+            }
+            Err(e) => {
+                error!("Failed to revive session {}: {}", session_name, e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Names of ended sessions that `revive` can bring back
+    pub async fn ended_sessions(&mut self) -> Result<Vec<String>> {
+        let live = self.claude.list_claude_sessions()?;
+        let backend = self.backend.as_ref();
+        SessionRegistry::update(|registry| {
+            registry.reconcile(&live, |name| Self::adopt_session(backend, name));
+            registry
+                .records()
+                .filter(|record| record.ended_at.is_some())
+                .map(|record| record.name.clone())
+                .collect()
+        })
+    }
+
+    /// Names of the Claude Code sessions that are running
+    pub fn running_sessions(&self) -> Result<Vec<String>> {
+        self.claude.list_claude_sessions()
+    }
+
+    /// What Claude Code is doing in the session, as far as its pane shows
+    pub fn session_activity(
+        &self,
+        session_name: &str,
+    ) -> Result<(Activity, Option<PermissionPrompt>)> {
+        if !self.backend.session_exists(session_name)?
+            || self.backend.command_exited(session_name)?
+        {
+            return Ok((Activity::Exited, None));
+        }
+        let pane = self.backend.capture_pane(session_name, None)?;
+        Ok(stream::pane_activity(&pane))
+    }
+
+    /// Whether Claude Code has exited in a session the backend kept open
+    pub fn claude_exited(&self, session_name: &str) -> Result<bool> {
+        self.backend.command_exited(session_name)
+    }
+
     pub async fn session_exists(&mut self, session_name: &str) -> Result<bool> {
-        self.tmux.session_exists(session_name)
+        self.backend.session_exists(session_name)
     }
 
     pub async fn send_message(&mut self, session_name: &str, message: &str) -> Result<()> {
         info!("Sending message to session {}: {}", session_name, message);
 
         // Check if session exists
-        if !self.tmux.session_exists(session_name)? {
+        if !self.backend.session_exists(session_name)? {
             return Err(anyhow!("Session not found: {}", session_name));
         }
 
         match self.claude.send_message_to_claude(session_name, message) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                SessionRegistry::update(|registry| {
+                    if let Some(record) = registry.get_mut(session_name) {
+                        record.last_message = Some(message.to_string());
+                        record.last_message_at = Some(Utc::now());
+                    }
+                })?;
+                Ok(())
+            }
             Err(e) => {
                 error!("Failed to send message to session {}: {}", session_name, e);
                 Err(e)
@@ -129,26 +526,86 @@ impl SessionManager {
         }
     }
 
+    /// Wait for the session to finish its turn.
+    ///
+    /// Unless `full_output` is set, the report's response holds only Claude's
+    /// reply to the last message sent rather than the whole pane.
     pub async fn wait_for_completion(
         &mut self,
         session_name: &str,
         timeout: u64,
-    ) -> Result<String> {
+        full_output: bool,
+    ) -> Result<CompletionReport> {
+        self.wait_for_turn(session_name, timeout, full_output, None)
+            .await
+    }
+
+    /// Wait for the turn of a message sent at `sent_at`, which may already
+    /// have ended by the time the wait begins
+    pub async fn wait_for_turn(
+        &mut self,
+        session_name: &str,
+        timeout: u64,
+        full_output: bool,
+        sent_at: Option<DateTime<Utc>>,
+    ) -> Result<CompletionReport> {
         info!(
             "Waiting for completion of session {} (timeout: {}s)",
             session_name, timeout
         );
 
         // Check if session exists
-        if !self.tmux.session_exists(session_name)? {
+        if !self.backend.session_exists(session_name)? {
             return Err(anyhow!("Session not found: {}", session_name));
         }
 
+        let start_time = std::time::Instant::now();
+
         match self
             .claude
-            .wait_for_claude_completion(session_name, timeout)
+            .wait_for_claude_completion(session_name, timeout, sent_at)
         {
-            Ok(output) => Ok(output),
+            Ok(completion) => {
+                let status = if completion.reason == ExitReason::Timeout {
+                    error!("Session {} did not complete within timeout", session_name);
+                    SessionStatus::Active
+                } else if completion.reason == ExitReason::AwaitingPermission {
+                    warn!("Session {} is waiting for permission", session_name);
+                    SessionStatus::AwaitingPermission
+                } else {
+                    SessionRegistry::update(|registry| {
+                        if let Some(record) = registry.get_mut(session_name) {
+                            record.last_completed_at = Some(Utc::now());
+                        }
+                    })?;
+                    SessionStatus::Idle
+                };
+                if let Some(conversation_id) = completion
+                    .event
+                    .as_ref()
+                    .and_then(|event| event.claude_session_id.clone())
+                {
+                    self.remember_conversation(session_name, conversation_id)?;
+                }
+
+                // Without a finished turn there is no reply to isolate yet
+                let response = if full_output || !completion.reason.is_finished() {
+                    completion.output
+                } else {
+                    self.extract_last_response(session_name)
+                        .unwrap_or(completion.output)
+                };
+
+                Ok(CompletionReport {
+                    session: session_name.to_string(),
+                    status,
+                    exit_reason: completion.reason,
+                    elapsed_secs: start_time.elapsed().as_secs_f64(),
+                    response: Some(response),
+                    event: completion.event,
+                    permission_prompt: completion.permission_prompt,
+                })
+            }
             Err(e) => {
                 error!(
                     "Session {} did not complete within timeout: {}",
@@ -159,11 +616,76 @@ impl SessionManager {
         }
     }
 
+    /// Isolate Claude's reply to the last recorded message from the pane scrollback
+    fn extract_last_response(&self, session_name: &str) -> Option<String> {
+        let last_message = SessionRegistry::load()
+            .ok()?
+            .get(session_name)
+            .and_then(|record| record.last_message.clone())
+            .unwrap_or_default();
+
+        let output = self
+            .claude
+            .get_claude_output(session_name, Some(RESPONSE_SCROLLBACK_LINES))
+            .ok()?;
+
+        // A long reply pushes the prompt out of the scrollback, but the log
+        // still has it
+        let response = claude::extract_response(&output, &last_message).or_else(|| {
+            let log = self.backend.render_session_log(session_name, None).ok()?;
+            claude::extract_response(&log, &last_message)
+        });
+        if response.is_none() {
+            warn!(
+                "Could not isolate Claude's response in session {}, returning full output",
+                session_name
+            );
+        }
+        response
+    }
+
+    /// Answer the permission prompt the session is blocked on
+    pub async fn answer_permission(
+        &mut self,
+        session_name: &str,
+        decision: PermissionDecision,
+    ) -> Result<PermissionPrompt> {
+        info!(
+            "Answering permission prompt in session {}: {}",
+            session_name, decision
+        );
+
+        // Check if session exists
+        if !self.backend.session_exists(session_name)? {
+            return Err(anyhow!("Session not found: {}", session_name));
+        }
+
+        self.claude.answer_permission_prompt(session_name, decision)
+    }
+
+    /// Current status and pending permission prompt of a running session
+    pub async fn get_session_state(
+        &mut self,
+        session_name: &str,
+    ) -> Result<(SessionStatus, Option<PermissionPrompt>)> {
+        if !self.backend.session_exists(session_name)? {
+            return Ok((SessionStatus::Failed, None));
+        }
+
+        let prompt = self.claude.pending_permission_prompt(session_name)?;
+        let status = if prompt.is_some() {
+            SessionStatus::AwaitingPermission
+        } else {
+            SessionStatus::Active
+        };
+        Ok((status, prompt))
+    }
+
     pub async fn get_session_status(&mut self, session_name: &str, lines: usize) -> Result<String> {
         debug!("Getting status for session: {}", session_name);
 
         // Check if session exists
-        if !self.tmux.session_exists(session_name)? {
+        if !self.backend.session_exists(session_name)? {
             return Err(anyhow!("Session not found: {}", session_name));
         }
 
@@ -180,7 +702,7 @@ impl SessionManager {
         info!("Attaching to session: {}", session_name);
 
         // Check if session exists
-        if !self.tmux.session_exists(session_name)? {
+        if !self.backend.session_exists(session_name)? {
             return Err(anyhow!("Session not found: {}", session_name));
         }
 
@@ -193,16 +715,34 @@ impl SessionManager {
         }
     }
 
-    pub async fn kill_session(&mut self, session_name: &str) -> Result<()> {
+    /// Kill the session. Its record is kept for `revive` when the conversation
+    /// is known, unless `forget` is set; forgetting also works on an ended session.
+    pub async fn kill_session(&mut self, session_name: &str, forget: bool) -> Result<()> {
         info!("Killing session: {}", session_name);
 
         // Check if session exists
-        if !self.tmux.session_exists(session_name)? {
+        if !self.backend.session_exists(session_name)? {
+            let forgotten = forget
+                && SessionRegistry::update(|registry| registry.remove(session_name))?.is_some();
+            if forgotten {
+                return Ok(());
+            }
             return Err(anyhow!("Session not found: {}", session_name));
         }
 
+        self.record_conversation(session_name)?;
+
         match self.claude.kill_claude_session(session_name) {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                SessionRegistry::update(|registry| {
+                    if forget {
+                        registry.remove(session_name);
+                    } else {
+                        registry.end(session_name);
+                    }
+                })?;
+                Ok(())
+            }
             Err(e) => {
                 error!("Failed to kill session {}: {}", session_name, e);
                 Err(e)
@@ -210,55 +750,136 @@ impl SessionManager {
         }
     }
 
-    pub async fn kill_all_sessions(&mut self) -> Result<usize> {
+    pub async fn kill_all_sessions(&mut self, forget: bool) -> Result<Vec<String>> {
         info!("Killing all Claude Code sessions");
 
         let claude_sessions = self.claude.list_claude_sessions()?;
-        let mut killed_count = 0;
+        let mut killed = Vec::new();
 
         for session_name in claude_sessions {
-            if self.kill_session(&session_name).await.is_ok() {
-                killed_count += 1;
+            if self.kill_session(&session_name, forget).await.is_ok() {
+                killed.push(session_name);
             }
         }
 
-        Ok(killed_count)
+        Ok(killed)
+    }
+
+    /// Claude Code's transcript of the conversation in the session, if found
+    pub fn find_session_transcript(&self, session_name: &str) -> Option<Transcript> {
+        let record = SessionRegistry::load().ok()?.get(session_name)?.clone();
+        let path = transcript::find_transcript(
+            &transcript::projects_dir(),
+            record.conversation_id.as_deref(),
+            record.working_dir.as_deref(),
+            record.created_at,
+        )?;
+        Self::load_transcript(session_name, &path)
+    }
+
+    /// Claude Code's transcript of the conversation recorded for one run
+    fn find_run_transcript(&self, run: &RunLog) -> Option<Transcript> {
+        let path = transcript::find_transcript(
+            &transcript::projects_dir(),
+            Some(run.metadata.conversation_id.as_deref()?),
+            run.metadata.working_dir.as_deref(),
+            run.metadata.started_at.unwrap_or_default(),
+        )?;
+        Self::load_transcript(&run.metadata.session, &path)
+    }
+
+    fn load_transcript(session_name: &str, path: &std::path::Path) -> Option<Transcript> {
+        match Transcript::load(path) {
+            Ok(transcript) => Some(transcript),
+            Err(e) => {
+                warn!("Ignoring transcript of session {}: {}", session_name, e);
+                None
+            }
+        }
     }
 
+    /// The session's conversation from its transcript, or the terminal log
+    /// when there is no transcript or `raw` is set
     pub async fn get_session_history(
         &mut self,
         session_name: &str,
         lines: Option<usize>,
-    ) -> Result<String> {
+        raw: bool,
+    ) -> Result<SessionHistory> {
         debug!("Getting history for session: {}", session_name);
 
+        if !raw {
+            if let Some(transcript) = self.find_session_transcript(session_name) {
+                debug!("Using transcript: {}", transcript.path.display());
+                return Ok(SessionHistory::Transcript(transcript));
+            }
+        }
+
         // Check if session exists
-        if !self.tmux.session_exists(session_name)? {
+        if !self.backend.session_exists(session_name)? {
             return Err(anyhow!("Session not found: {}", session_name));
         }
 
-        // Try to read from log file first, then fall back to current pane content
-        match self.tmux.read_session_log(session_name, lines) {
-            Ok(history) => Ok(history),
+        // Try to replay the log file first, then fall back to current pane content
+        match self.backend.render_session_log(session_name, lines) {
+            Ok(history) => Ok(SessionHistory::Log(history)),
             Err(e) => {
                 debug!(
                     "Failed to read log file, falling back to pane capture: {}",
                     e
                 );
-                self.claude.get_claude_output(session_name, lines)
+                self.claude
+                    .get_claude_output(session_name, lines)
+                    .map(SessionHistory::Log)
             }
         }
     }
 
+    /// Every logged run of the session, oldest first
+    pub fn session_runs(&self, session_name: &str) -> Result<Vec<RunLog>> {
+        let runs = logs::runs(session_name)?;
+        if runs.is_empty() {
+            return Err(anyhow!("No logged runs for session: {}", session_name));
+        }
+        Ok(runs)
+    }
+
+    /// History of one run of the session: the transcript of the conversation
+    /// recorded for it, or its terminal log when there is none or `raw` is set
+    pub fn get_run_history(
+        &self,
+        run: &RunLog,
+        lines: Option<usize>,
+        raw: bool,
+    ) -> Result<SessionHistory> {
+        if !raw {
+            if let Some(transcript) = self.find_run_transcript(run) {
+                debug!("Using transcript: {}", transcript.path.display());
+                return Ok(SessionHistory::Transcript(transcript));
+            }
+        }
+
+        let rows = self
+            .backend
+            .pane_rows(&run.metadata.session)
+            .unwrap_or(terminal::DEFAULT_ROWS);
+        logs::render(&run.path, rows, lines).map(SessionHistory::Log)
+    }
+
+    /// Watch the session's output, state and completions as they happen
+    pub fn watch_session(&self, session_name: &str) -> SessionWatcher {
+        SessionWatcher::new(session_name, self.backend.clone())
+    }
+
     pub async fn follow_session_history(&mut self, session_name: &str) -> Result<()> {
         info!("Following history for session: {}", session_name);
 
         // Check if session exists
-        if !self.tmux.session_exists(session_name)? {
+        if !self.backend.session_exists(session_name)? {
             return Err(anyhow!("Session not found: {}", session_name));
         }
 
-        let log_file = self.tmux.get_log_file_path(session_name);
+        let log_file = self.backend.get_log_file_path(session_name);
 
         if std::path::Path::new(&log_file).exists() {
             // Use tail -f on the log file
@@ -282,26 +903,38 @@ impl SessionManager {
         &mut self,
         session_name: &str,
         output_path: &std::path::Path,
+        format: ExportFormat,
         clean: bool,
+        raw: bool,
     ) -> Result<()> {
         info!(
-            "Exporting history for session {} to: {}",
+            "Exporting history for session {} to: {} ({})",
             session_name,
-            output_path.display()
+            output_path.display(),
+            format
         );
 
-        // Check if session exists
-        if !self.tmux.session_exists(session_name)? {
-            return Err(anyhow!("Session not found: {}", session_name));
-        }
-
-        // Get full session history
-        let mut history = self.get_session_history(session_name, None).await?;
+        let transcript = if raw {
+            None
+        } else {
+            self.find_session_transcript(session_name)
+        };
 
-        // Strip ANSI codes if clean output requested
-        if clean {
-            history = self.strip_ansi_codes(&history);
-        }
+        let history = match transcript {
+            Some(transcript) => export::render_transcript(format, session_name, &transcript)?,
+            None => {
+                if !self.backend.session_exists(session_name)? {
+                    return Err(anyhow!("Session not found: {}", session_name));
+                }
+                // Without --clean the log keeps every escape sequence, colours included
+                let log = if clean {
+                    self.backend.render_session_log(session_name, None)?
+                } else {
+                    self.backend.read_session_log(session_name, None)?
+                };
+                export::render_log(format, session_name, &log)?
+            }
+        };
 
         // Create output directory if it doesn't exist
         if let Some(parent) = output_path.parent() {
@@ -318,6 +951,156 @@ impl SessionManager {
         Ok(())
     }
 
+    /// Send the session's queued messages one at a time, each only once
+    /// Claude has finished the one before. Stops when the queue is empty, or
+    /// at a message Claude did not finish so nothing is typed into a busy
+    /// session. Returns how many messages were sent.
+    pub async fn run_queue(&mut self, session_name: &str) -> Result<usize> {
+        let mut sent = 0;
+        loop {
+            let Some(worker) = queue::try_lock_worker(session_name)? else {
+                info!(
+                    "A queue worker is already running for session {}",
+                    session_name
+                );
+                return Ok(sent);
+            };
+            let interrupted = MessageQueue::update(session_name, |q| q.fail_interrupted())?;
+            if interrupted > 0 {
+                warn!(
+                    "Marked {} message(s) left running by an earlier worker as failed",
+                    interrupted
+                );
+            }
+
+            while let Some(queued) = MessageQueue::update(session_name, |q| q.start_next())? {
+                info!(
+                    "Sending queued message #{} to session {}",
+                    queued.id, session_name
+                );
+                sent += 1;
+                let outcome = self.deliver_queued(&queued).await;
+                let finished = matches!(&outcome, Ok((reason, _)) if reason.is_finished());
+                MessageQueue::update(session_name, |q| {
+                    q.finish(queued.id, outcome.map_err(|e| format!("{e:#}")))
+                })?;
+                if !finished {
+                    warn!(
+                        "Claude did not finish queued message #{} in session {}, stopping the queue",
+                        queued.id, session_name
+                    );
+                    return Ok(sent);
+                }
+            }
+
+            // A message added while this worker held the lock did not start
+            // another one, so look again after letting go
+            drop(worker);
+            if !MessageQueue::load(session_name)?.has_pending() {
+                return Ok(sent);
+            }
+        }
+    }
+
+    /// Send one queued message and wait out its turn, including any
+    /// permission prompts someone answers in the meantime
+    async fn deliver_queued(
+        &mut self,
+        queued: &QueuedMessage,
+    ) -> Result<(ExitReason, Option<String>)> {
+        let session_name = &queued.session;
+        let timeout = queued.timeout.unwrap_or(self.config.default_timeout);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(timeout);
+
+        // Claude may still be busy with a message sent outside the queue
+        if !self.wait_until_idle(session_name, deadline)? {
+            return Ok((ExitReason::Timeout, None));
+        }
+
+        let sent_at = Utc::now();
+        self.send_message(session_name, &queued.message).await?;
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                return Ok((ExitReason::Timeout, None));
+            }
+
+            let report = self
+                .wait_for_turn(
+                    session_name,
+                    remaining.as_secs().max(1),
+                    false,
+                    Some(sent_at),
+                )
+                .await?;
+            let status = match report.exit_reason {
+                ExitReason::AwaitingPermission => QueueStatus::AwaitingPermission,
+                _ => QueueStatus::Running,
+            };
+            MessageQueue::update(session_name, |q| {
+                if let Some(message) = q.get_mut(queued.id) {
+                    message.status = status;
+                }
+            })?;
+            if status != QueueStatus::AwaitingPermission {
+                return Ok((report.exit_reason, report.response));
+            }
+        }
+    }
+
+    /// Wait until Claude is idle in the session, returning `false` if it is
+    /// still busy at `deadline`
+    fn wait_until_idle(&self, session_name: &str, deadline: std::time::Instant) -> Result<bool> {
+        loop {
+            match self.session_activity(session_name)?.0 {
+                Activity::Idle => return Ok(true),
+                Activity::Exited => {
+                    return Err(anyhow!(
+                        "Claude Code has exited in session {}",
+                        session_name
+                    ))
+                }
+                Activity::Working | Activity::WaitingForPermission => {}
+            }
+            if std::time::Instant::now() >= deadline {
+                warn!(
+                    "Session {} stayed busy, not sending the queued message",
+                    session_name
+                );
+                return Ok(false);
+            }
+            debug!(
+                "Session {} is busy, holding the queued message",
+                session_name
+            );
+            std::thread::sleep(stream::POLL_INTERVAL);
+        }
+    }
+
+    /// Rotate session logs that have grown past the configured size
+    pub fn rotate_logs(&self) -> Result<Vec<LogAction>> {
+        logs::rotate_oversized(&logs::log_dir(), &self.config.log_policy())
+    }
+
+    /// Rotate oversized logs and delete the logs of sessions that are gone
+    /// once they are older than the retention period (or `older_than` days)
+    pub fn prune_logs(&self, dry_run: bool, older_than: Option<u64>) -> Result<Vec<LogAction>> {
+        let mut policy = self.config.log_policy();
+        if let Some(days) = older_than {
+            policy.retention_days = days;
+        }
+        info!("Pruning session logs (dry run: {})", dry_run);
+
+        logs::prune(
+            &logs::log_dir(),
+            &policy,
+            // Keep the log when it is unclear whether its session still runs
+            |session| self.backend.session_exists(session).unwrap_or(true),
+            std::time::SystemTime::now(),
+            dry_run,
+        )
+    }
+
     #[allow(dead_code)]
     pub async fn enable_logging_for_existing_sessions(&mut self) -> Result<()> {
         info!("Enabling logging for existing sessions");
@@ -325,7 +1108,7 @@ impl SessionManager {
         let claude_sessions = self.claude.list_claude_sessions()?;
 
         for session_name in claude_sessions {
-            if let Err(e) = self.tmux.enable_session_logging(&session_name) {
+            if let Err(e) = self.backend.enable_session_logging(&session_name) {
                 warn!(
                     "Failed to enable logging for session {}: {}",
                     session_name, e
@@ -335,28 +1118,37 @@ impl SessionManager {
 
         Ok(())
     }
+}
 
-    fn strip_ansi_codes(&self, text: &str) -> String {
-        // Remove ANSI escape sequences using regex-like pattern matching
-        let mut result = String::new();
-        let mut chars = text.chars().peekable();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBackend;
 
-        while let Some(ch) = chars.next() {
-            if ch == '\x1b' {
-                // Found escape character, skip until we find 'm' (end of color code)
-                if chars.peek() == Some(&'[') {
-                    chars.next(); // consume '['
-                    for next_ch in chars.by_ref() {
-                        if next_ch == 'm' {
-                            break;
-                        }
-                    }
-                }
-            } else {
-                result.push(ch);
-            }
-        }
+    #[tokio::test]
+    async fn queued_messages_wait_for_claude_to_be_idle() {
+        let backend = Arc::new(FakeBackend::new().with_stop_hook());
+        let session = format!("claude-unit-{}-queue", std::process::id());
+        backend.create_session(&session, None, None).unwrap();
+        hooks::clear_events(&session);
+        let mut manager = SessionManager::with_backend(Config::default(), backend.clone());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut queue = MessageQueue::load_from(&dir.path().join("queue.json"), &session).unwrap();
+        let queued = queue.push("next task", Some(1));
+
+        // Still working on a message sent with `send --no-wait`
+        backend.set_pane(
+            &session,
+            "> earlier task\n\n✻ Thinking… (esc to interrupt)\n",
+        );
+        let outcome = manager.deliver_queued(&queued).await.unwrap();
+        assert_eq!(outcome.0, ExitReason::Timeout);
+        assert!(backend.sent_to(&session).is_empty());
 
-        result
+        backend.set_pane(&session, "> earlier task\n\n⏺ Done\n\n│ > │\n");
+        let outcome = manager.deliver_queued(&queued).await.unwrap();
+        assert_eq!(outcome.0, ExitReason::StopHook);
+        assert_eq!(backend.sent_to(&session), ["next task"]);
     }
 }
@@ -0,0 +1,395 @@
+//! Live events of a running session.
+//!
+//! A `SessionWatcher` polls what the other commands already look at: the
+//! replayed terminal log for output, the pane for what Claude Code is doing,
+//! and the hook events file. Each poll returns what changed since the last
+//! one, so a caller can push the events to a browser or another program
+//! without a terminal to `tail -f` in.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::backend::TerminalBackend;
+use crate::claude;
+use crate::hooks::{self, HookEvent, HookKind};
+use crate::permissions::{self, PermissionPrompt};
+use crate::registry::SessionRegistry;
+use crate::terminal::{self, Replay};
+
+/// How often `serve` polls a watched session
+pub const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// What Claude Code is doing in a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Activity {
+    /// Claude is working on a turn
+    Working,
+    /// A permission dialog is waiting for an answer
+    WaitingForPermission,
+    /// The input box is waiting for a message
+    Idle,
+    /// The session is gone
+    Exited,
+}
+
+impl std::fmt::Display for Activity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Activity::Working => write!(f, "working"),
+            Activity::WaitingForPermission => write!(f, "waiting for permission"),
+            Activity::Idle => write!(f, "idle"),
+            Activity::Exited => write!(f, "exited"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// Replayed output: `lines` replace everything from line `from` on, since
+    /// Claude Code redraws the bottom of the screen as it works
+    Output { from: usize, lines: Vec<String> },
+    /// The session changed what it is doing
+    State {
+        state: Activity,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        permission_prompt: Option<PermissionPrompt>,
+    },
+    /// A Claude Code hook fired
+    Hook { event: HookEvent },
+    /// Claude finished its turn
+    Completed {
+        /// Claude's reply to the last message sent, when it could be isolated
+        response: Option<String>,
+        /// The Stop hook event, when it was the first sign of completion
+        #[serde(skip_serializing_if = "Option::is_none")]
+        event: Option<HookEvent>,
+    },
+}
+
+impl StreamEvent {
+    /// Short name of the event, e.g. the SSE event type
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StreamEvent::Output { .. } => "output",
+            StreamEvent::State { .. } => "state",
+            StreamEvent::Hook { .. } => "hook",
+            StreamEvent::Completed { .. } => "completed",
+        }
+    }
+}
+
+/// What Claude Code is doing, judging by its pane, and the permission
+/// dialog it shows if any
+pub fn pane_activity(pane: &str) -> (Activity, Option<PermissionPrompt>) {
+    let permission_prompt = permissions::detect_permission_prompt(pane);
+    let state = if permission_prompt.is_some() {
+        Activity::WaitingForPermission
+    } else if claude::looks_busy(pane) {
+        Activity::Working
+    } else {
+        Activity::Idle
+    };
+    (state, permission_prompt)
+}
+
+struct LogReplay {
+    log: PathBuf,
+    /// Bytes of the log fed to the terminal
+    offset: u64,
+    terminal: Replay,
+}
+
+pub struct SessionWatcher {
+    session: String,
+    backend: Arc<dyn TerminalBackend>,
+    /// Whether to replay the log and report output
+    follow_output: bool,
+    /// Replayed output sent so far
+    lines: Vec<String>,
+    /// Size of the log when it was last replayed
+    log_len: Option<u64>,
+    /// The log replayed so far, fed only what is appended to it
+    replay: Option<LogReplay>,
+    state: Option<Activity>,
+    /// When the latest hook event already reported fired
+    last_hook_at: Option<DateTime<Utc>>,
+    /// Whether Claude was seen working on a turn not yet reported as completed
+    in_turn: bool,
+    /// Whether the latest turn was reported as completed
+    turn_reported: bool,
+}
+
+impl SessionWatcher {
+    pub fn new(session_name: &str, backend: Arc<dyn TerminalBackend>) -> Self {
+        Self {
+            session: session_name.to_string(),
+            backend,
+            follow_output: true,
+            lines: Vec::new(),
+            log_len: None,
+            replay: None,
+            state: None,
+            // Events from earlier turns are not news
+            last_hook_at: hooks::read_events(session_name)
+                .last()
+                .map(|event| event.timestamp),
+            in_turn: false,
+            turn_reported: true,
+        }
+    }
+
+    /// Only report state changes, hooks and completions, skipping the log
+    /// replay that output events need
+    pub fn without_output(mut self) -> Self {
+        self.follow_output = false;
+        self
+    }
+
+    /// Whether the session has exited, after which there is nothing more to watch
+    pub fn finished(&self) -> bool {
+        self.state == Some(Activity::Exited)
+    }
+
+    /// Everything that happened since the last poll. The first poll reports
+    /// the output so far and the current state.
+    pub fn poll(&mut self) -> Result<Vec<StreamEvent>> {
+        let mut events = Vec::new();
+
+        if !self.backend.session_exists(&self.session)? {
+            if !self.finished() {
+                self.state = Some(Activity::Exited);
+                events.push(StreamEvent::State {
+                    state: Activity::Exited,
+                    permission_prompt: None,
+                });
+            }
+            return Ok(events);
+        }
+
+        let changed = if !self.follow_output {
+            self.poll_log_len()
+        } else if let Some(output) = self.poll_output()? {
+            events.push(output);
+            true
+        } else {
+            false
+        };
+        // Output after the first poll means something happened, even when
+        // the turn was too quick to be seen working
+        if changed && self.state.is_some() {
+            self.turn_reported = false;
+        }
+
+        let mut completion = None;
+        for event in self.poll_hooks() {
+            if event.hook == HookKind::Stop && completion.is_none() {
+                completion = Some(event.clone());
+            }
+            events.push(StreamEvent::Hook { event });
+        }
+
+        let pane = self.backend.capture_pane(&self.session, None)?;
+        let (state, permission_prompt) = pane_activity(&pane);
+        if self.state != Some(state) {
+            debug!("Session {} is now {}", self.session, state);
+            self.state = Some(state);
+            events.push(StreamEvent::State {
+                state,
+                permission_prompt,
+            });
+        }
+        if state == Activity::Working {
+            self.in_turn = true;
+            self.turn_reported = false;
+        }
+
+        // The Stop hook and the idle input box both end a turn; whichever
+        // comes first reports it
+        let stopped = completion.is_some() && !self.turn_reported;
+        if stopped || (state == Activity::Idle && self.in_turn) {
+            self.in_turn = false;
+            self.turn_reported = true;
+            events.push(StreamEvent::Completed {
+                response: self.last_response(),
+                event: completion,
+            });
+        }
+
+        Ok(events)
+    }
+
+    /// Whether the log changed size since the last poll
+    fn poll_log_len(&mut self) -> bool {
+        let log = self.backend.get_log_file_path(&self.session);
+        let log_len = std::fs::metadata(Path::new(&log))
+            .ok()
+            .map(|meta| meta.len());
+        if log_len.is_some() && log_len == self.log_len {
+            return false;
+        }
+        self.log_len = log_len;
+        true
+    }
+
+    /// Output lines that changed since the last poll
+    fn poll_output(&mut self) -> Result<Option<StreamEvent>> {
+        if !self.poll_log_len() {
+            return Ok(None);
+        }
+
+        let log = PathBuf::from(self.backend.get_log_file_path(&self.session));
+        let rendered = if log.exists() {
+            self.replay_log(log)?
+        } else {
+            self.replay = None;
+            self.backend.render_session_log(&self.session, None)?
+        };
+        let lines: Vec<String> = rendered.lines().map(str::to_string).collect();
+        let from = self
+            .lines
+            .iter()
+            .zip(&lines)
+            .take_while(|(old, new)| old == new)
+            .count();
+        if from == self.lines.len() && from == lines.len() {
+            return Ok(None);
+        }
+
+        let changed = lines[from..].to_vec();
+        self.lines = lines;
+        Ok(Some(StreamEvent::Output {
+            from,
+            lines: changed,
+        }))
+    }
+
+    /// Feed the bytes appended to `log` since the last poll to the replay,
+    /// starting over when the log shrank (it was rotated) or a new run's log
+    /// took its place
+    fn replay_log(&mut self, log: PathBuf) -> Result<String> {
+        let mut file = std::fs::File::open(&log)?;
+        let len = file.metadata()?.len();
+        let mut replay = match self.replay.take() {
+            Some(replay) if replay.log == log && replay.offset <= len => replay,
+            _ => {
+                debug!("Replaying log from the start: {}", log.display());
+                let rows = self
+                    .backend
+                    .pane_rows(&self.session)
+                    .unwrap_or(terminal::DEFAULT_ROWS);
+                LogReplay {
+                    log,
+                    offset: 0,
+                    terminal: Replay::new(rows),
+                }
+            }
+        };
+
+        file.seek(SeekFrom::Start(replay.offset))?;
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended)?;
+        replay.offset += appended.len() as u64;
+        replay.terminal.feed(&appended);
+
+        let rendered = replay.terminal.text();
+        self.replay = Some(replay);
+        Ok(rendered)
+    }
+
+    /// Hook events recorded since the last poll. Waiting for a turn clears
+    /// the file, so events are told apart by when they fired, not by count.
+    fn poll_hooks(&mut self) -> Vec<HookEvent> {
+        let new: Vec<HookEvent> = hooks::read_events(&self.session)
+            .into_iter()
+            .filter(|event| self.last_hook_at.is_none_or(|seen| event.timestamp > seen))
+            .collect();
+        if let Some(last) = new.last() {
+            self.last_hook_at = Some(last.timestamp);
+        }
+        new
+    }
+
+    fn last_response(&self) -> Option<String> {
+        let last_message = SessionRegistry::load()
+            .ok()?
+            .get(&self.session)
+            .and_then(|record| record.last_message.clone())
+            .unwrap_or_default();
+        let output = if self.follow_output {
+            self.lines.join("\n")
+        } else {
+            self.backend.render_session_log(&self.session, None).ok()?
+        };
+        claude::extract_response(&output, &last_message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeBackend;
+
+    fn unique_session(label: &str) -> String {
+        format!("ccm-stream-test-{}-{label}", std::process::id())
+    }
+
+    #[test]
+    fn output_state_and_completion_are_reported_as_they_change() {
+        let backend = Arc::new(FakeBackend::new());
+        let session = unique_session("turn");
+        backend.set_pane(&session, "> hello\n\n✻ Thinking… (esc to interrupt)\n");
+
+        let mut watcher = SessionWatcher::new(&session, backend.clone());
+        let events = watcher.poll().unwrap();
+        assert!(matches!(
+            &events[..],
+            [
+                StreamEvent::Output { from: 0, lines },
+                StreamEvent::State { state: Activity::Working, .. },
+            ] if lines.len() == 3
+        ));
+        assert!(watcher.poll().unwrap().is_empty());
+
+        backend.set_pane(&session, "> hello\n\n⏺ Hi there!\n\n│ > │\n");
+        let events = watcher.poll().unwrap();
+        assert!(matches!(
+            &events[..],
+            [
+                StreamEvent::Output { from: 2, lines },
+                StreamEvent::State { state: Activity::Idle, .. },
+                StreamEvent::Completed { event: None, .. },
+            ] if lines[0] == "⏺ Hi there!"
+        ));
+
+        backend.kill_session(&session).unwrap();
+        let events = watcher.poll().unwrap();
+        assert!(matches!(
+            &events[..],
+            [StreamEvent::State {
+                state: Activity::Exited,
+                ..
+            }]
+        ));
+        assert!(watcher.finished());
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn an_idle_session_is_not_reported_as_completed() {
+        let backend = Arc::new(FakeBackend::new());
+        let session = unique_session("idle");
+        backend.set_pane(&session, "│ > │\n  ? for shortcuts\n");
+
+        let mut watcher = SessionWatcher::new(&session, backend);
+        let events = watcher.poll().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].kind(), "state");
+    }
+}
@@ -0,0 +1,218 @@
+//! Replay of raw terminal output into the text a terminal would show.
+//!
+//! The pipe-pane log records every byte Claude Code wrote: colours, cursor
+//! movement, line erases, title changes and the carriage-return redraws of
+//! its spinner. Feeding that stream through a virtual terminal and reading
+//! back the screen and its scrollback gives the lines a user actually saw.
+
+/// Rows of a detached tmux session, used when the real pane size is unknown
+pub const DEFAULT_ROWS: u16 = 24;
+
+/// Columns of the virtual terminal. Wider than most panes so lines are not
+/// re-wrapped; the row count is what has to match for relative cursor moves.
+const REPLAY_COLS: u16 = 250;
+
+/// Bytes replayed before scrolled-off lines are collected and the virtual
+/// terminal restarted, which keeps memory bounded for long logs
+const REPLAY_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Render `bytes` as a terminal `rows` high would have shown them, including
+/// everything that scrolled off the top. Trailing whitespace and blank lines
+/// are dropped.
+pub fn replay(bytes: &[u8], rows: u16) -> String {
+    replay_in_chunks(bytes, rows, REPLAY_CHUNK_BYTES)
+}
+
+fn replay_in_chunks(bytes: &[u8], rows: u16, chunk_bytes: usize) -> String {
+    let mut replay = Replay::with_chunk_bytes(rows, chunk_bytes);
+    replay.feed(bytes);
+    replay.text()
+}
+
+/// A virtual terminal fed output as it arrives, so a growing log is only
+/// replayed from where it was last read
+pub struct Replay {
+    rows: u16,
+    chunk_bytes: usize,
+    parser: vt100::Parser,
+    /// Lines collected from the scrollback before the last restart
+    scrolled: Vec<String>,
+    /// Bytes processed since the virtual terminal was last restarted
+    processed: usize,
+}
+
+impl Replay {
+    pub fn new(rows: u16) -> Self {
+        Self::with_chunk_bytes(rows, REPLAY_CHUNK_BYTES)
+    }
+
+    fn with_chunk_bytes(rows: u16, chunk_bytes: usize) -> Self {
+        let rows = rows.max(1);
+        Self {
+            rows,
+            chunk_bytes,
+            parser: Self::parser(rows, chunk_bytes),
+            scrolled: Vec::new(),
+            processed: 0,
+        }
+    }
+
+    fn parser(rows: u16, chunk_bytes: usize) -> vt100::Parser {
+        // Output scrolls at most a line per byte printed, so a chunk fits in this
+        vt100::Parser::new(rows, REPLAY_COLS, chunk_bytes + usize::from(rows))
+    }
+
+    /// Process more output. It may end in the middle of an escape sequence
+    /// or character; the rest is expected in the next call.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let room = self.chunk_bytes.saturating_sub(self.processed);
+            let (chunk, remainder) = rest.split_at(chunk_end(rest, room));
+            self.parser.process(chunk);
+            self.processed += chunk.len();
+            rest = remainder;
+
+            if self.processed >= self.chunk_bytes && chunk.ends_with(b"\n") {
+                self.restart();
+            }
+        }
+    }
+
+    /// Collect the scrollback and start over with only the visible screen,
+    /// which keeps memory bounded for long logs. Only done just after a
+    /// newline, so no escape sequence or character is split.
+    fn restart(&mut self) {
+        take_scrollback(self.parser.screen_mut(), &mut self.scrolled);
+        let state = self.parser.screen().state_formatted();
+        self.parser = Self::parser(self.rows, self.chunk_bytes);
+        self.parser.process(&state);
+        self.processed = 0;
+    }
+
+    /// Everything shown so far, as `replay` returns it
+    pub fn text(&mut self) -> String {
+        let mut lines = self.scrolled.clone();
+        take_scrollback(self.parser.screen_mut(), &mut lines);
+        push_rows(self.parser.screen(), usize::from(self.rows), &mut lines);
+
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines.join("\n")
+    }
+}
+
+/// End of the next chunk: just after a newline, so no escape sequence or
+/// UTF-8 character is split between two virtual terminals
+fn chunk_end(bytes: &[u8], chunk_bytes: usize) -> usize {
+    if bytes.len() <= chunk_bytes {
+        return bytes.len();
+    }
+    bytes[chunk_bytes..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |offset| chunk_bytes + offset + 1)
+}
+
+/// Append the lines that scrolled off the screen, oldest first
+fn take_scrollback(screen: &mut vt100::Screen, lines: &mut Vec<String>) {
+    screen.set_scrollback(usize::MAX);
+    let scrolled = screen.scrollback();
+    let rows = usize::from(screen.size().0);
+
+    let mut start = 0;
+    while start < scrolled {
+        // With the view scrolled back by `offset`, the first visible rows
+        // are the scrollback lines from `scrolled - offset` on
+        screen.set_scrollback(scrolled - start);
+        push_rows(screen, rows.min(scrolled - start), lines);
+        start += rows;
+    }
+
+    screen.set_scrollback(0);
+}
+
+/// Append the first `count` visible rows, joining rows that soft-wrapped
+fn push_rows(screen: &vt100::Screen, count: usize, lines: &mut Vec<String>) {
+    let mut continues = false;
+    for (index, row) in screen.rows(0, REPLAY_COLS).take(count).enumerate() {
+        let row = row.trim_end().to_string();
+        match lines.last_mut() {
+            Some(line) if continues => line.push_str(&row),
+            _ => lines.push(row),
+        }
+        continues = screen.row_wrapped(index as u16);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_titles_and_spinner_redraws_are_resolved() {
+        let log = concat!(
+            "\x1b]0;✳ Claude Code\x07",
+            "> \x1b[1mhello\x1b[0m\r\n",
+            "\x1b[38;5;174m✻ Thinking…\x1b[39m",
+            "\r\x1b[2K\x1b[38;5;174m✽ Thinking…\x1b[39m",
+            "\r\x1b[2K⏺ Hi there!\r\n",
+        );
+
+        assert_eq!(replay(log.as_bytes(), 24), "> hello\n⏺ Hi there!");
+    }
+
+    #[test]
+    fn cursor_movement_rewrites_earlier_lines() {
+        // Ink redraws its frame by moving up and erasing the old lines
+        let log = concat!(
+            "⏺ Working\r\n",
+            "╭────╮\r\n│ >  │\r\n╰────╯\r\n",
+            "\x1b[4A\x1b[2K⏺ Done\r\n\x1b[J",
+            "╭────╮\r\n│ >  │\r\n╰────╯\r\n",
+        );
+
+        assert_eq!(replay(log.as_bytes(), 24), "⏺ Done\n╭────╮\n│ >  │\n╰────╯");
+    }
+
+    #[test]
+    fn lines_that_scrolled_off_are_kept_in_order() {
+        let log: String = (1..=500).map(|n| format!("line {n}\r\n")).collect();
+
+        // Small chunks force several restarts of the virtual terminal
+        let replayed = replay_in_chunks(log.as_bytes(), 10, 256);
+        let lines: Vec<&str> = replayed.lines().collect();
+        assert_eq!(lines.len(), 500);
+        assert_eq!(lines[0], "line 1");
+        assert_eq!(lines[499], "line 500");
+        assert!(lines
+            .iter()
+            .enumerate()
+            .all(|(n, line)| *line == format!("line {}", n + 1)));
+    }
+
+    #[test]
+    fn output_fed_in_pieces_replays_like_the_whole_log() {
+        let log: String = (1..=300)
+            .map(|n| format!("\x1b[1mline {n}\x1b[0m ✻\r\n"))
+            .collect();
+        let bytes = log.as_bytes();
+
+        // Pieces split escape sequences and multi-byte characters
+        let mut replay = Replay::with_chunk_bytes(10, 256);
+        for piece in bytes.chunks(7) {
+            replay.feed(piece);
+        }
+        assert_eq!(replay.text(), replay_in_chunks(bytes, 10, 256));
+        assert_eq!(replay.text().lines().count(), 300);
+    }
+
+    #[test]
+    fn soft_wrapped_lines_are_joined() {
+        let long = "x".repeat(usize::from(REPLAY_COLS) + 10);
+        let log = format!("{long}\r\nnext\r\n");
+
+        assert_eq!(replay(log.as_bytes(), 5), format!("{long}\nnext"));
+    }
+}
@@ -0,0 +1,165 @@
+//! In-memory terminal backend for unit tests.
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::backend::{LaunchCommand, SessionInfo, SpecialKey, TerminalBackend};
+use crate::hooks::{self, HookEvent, HookKind};
+
+/// Fake multiplexer whose sessions are strings in memory.
+///
+/// Every line sent with Enter is answered like Claude Code would: the prompt is
+/// echoed, followed by a reply built from the configured template and a fresh
+/// input box.
+pub struct FakeBackend {
+    reply_template: String,
+    /// Whether answering a line also records a Stop hook event
+    stop_hook: bool,
+    state: Mutex<FakeState>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    panes: BTreeMap<String, String>,
+    sent: Vec<(String, String)>,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        Self::with_reply("Echo: {prompt}")
+    }
+
+    /// Reply with `template`, where `{prompt}` is replaced by the message
+    pub fn with_reply(template: &str) -> Self {
+        Self {
+            reply_template: template.to_string(),
+            stop_hook: false,
+            state: Mutex::new(FakeState::default()),
+        }
+    }
+
+    /// Record a Stop hook event whenever a line is answered, like Claude
+    /// Code with the hooks installed
+    pub fn with_stop_hook(mut self) -> Self {
+        self.stop_hook = true;
+        self
+    }
+
+    pub fn set_pane(&self, session_name: &str, content: &str) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .panes
+            .insert(session_name.to_string(), content.to_string());
+    }
+
+    /// Everything sent to `session_name`, in order
+    pub fn sent_to(&self, session_name: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .sent
+            .iter()
+            .filter(|(session, _)| session == session_name)
+            .map(|(_, keys)| keys.clone())
+            .collect()
+    }
+
+    fn input_box() -> &'static str {
+        "╭──────────────────────────╮\n│ >                        │\n╰──────────────────────────╯\n  ? for shortcuts\n"
+    }
+}
+
+impl TerminalBackend for FakeBackend {
+    fn session_exists(&self, session_name: &str) -> Result<bool> {
+        Ok(self.state.lock().unwrap().panes.contains_key(session_name))
+    }
+
+    fn list_sessions(&self) -> Result<Vec<String>> {
+        Ok(self.state.lock().unwrap().panes.keys().cloned().collect())
+    }
+
+    fn create_session(
+        &self,
+        session_name: &str,
+        _working_dir: Option<&PathBuf>,
+        _command: Option<&LaunchCommand>,
+    ) -> Result<()> {
+        let banner = "╭──────────────────────────╮\n│ ✻ Welcome to Claude Code! │\n╰──────────────────────────╯\n\n";
+        self.set_pane(session_name, &format!("{banner}{}", Self::input_box()));
+        Ok(())
+    }
+
+    fn kill_session(&self, session_name: &str) -> Result<()> {
+        self.state.lock().unwrap().panes.remove(session_name);
+        Ok(())
+    }
+
+    fn send_keys(&self, session_name: &str, keys: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .sent
+            .push((session_name.to_string(), keys.to_string()));
+        Ok(())
+    }
+
+    /// Recorded as `<Enter>` or `<Escape>`
+    fn send_special_key(&self, session_name: &str, key: SpecialKey) -> Result<()> {
+        self.send_keys(session_name, &format!("<{key:?}>"))
+    }
+
+    fn send_keys_with_enter(&self, session_name: &str, keys: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .sent
+            .push((session_name.to_string(), keys.to_string()));
+
+        let reply = self.reply_template.replace("{prompt}", keys);
+        let pane = state
+            .panes
+            .get_mut(session_name)
+            .ok_or_else(|| anyhow!("Session not found: {}", session_name))?;
+        pane.push_str(&format!("> {keys}\n\n⏺ {reply}\n\n{}", Self::input_box()));
+        if self.stop_hook {
+            hooks::append_event(&HookEvent::from_payload(
+                session_name,
+                HookKind::Stop,
+                &serde_json::json!({}),
+            ))?;
+        }
+        Ok(())
+    }
+
+    fn capture_pane(&self, session_name: &str, lines: Option<usize>) -> Result<String> {
+        let state = self.state.lock().unwrap();
+        let pane = state
+            .panes
+            .get(session_name)
+            .ok_or_else(|| anyhow!("Session not found: {}", session_name))?;
+
+        let all_lines: Vec<&str> = pane.lines().collect();
+        let selected = match lines {
+            Some(n) if all_lines.len() > n => &all_lines[all_lines.len() - n..],
+            _ => &all_lines[..],
+        };
+        Ok(format!("{}\n", selected.join("\n")))
+    }
+
+    fn enable_session_logging(&self, _session_name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn attach_session(&self, _session_name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_session_info(&self, session_name: &str) -> Result<SessionInfo> {
+        Ok(SessionInfo {
+            name: session_name.to_string(),
+            created: 0,
+            windows: 1,
+            attached: false,
+            path: PathBuf::from("/tmp"),
+        })
+    }
+}
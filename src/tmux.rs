@@ -1,71 +1,55 @@
 use anyhow::{anyhow, Result};
+use std::io::Write;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
 use tracing::{debug, error, info, warn};
 
-pub struct TmuxManager;
+use crate::backend::{self, LaunchCommand, SessionInfo, SpecialKey, TerminalBackend};
+use crate::logs;
 
-impl TmuxManager {
-    pub fn new() -> Self {
-        Self
-    }
+/// Environment variable selecting a dedicated tmux server socket (`tmux -L`)
+pub const TMUX_SOCKET_ENV: &str = "CLAUDE_CODE_MANAGER_TMUX_SOCKET";
 
-    pub fn session_exists(&self, session_name: &str) -> Result<bool> {
-        debug!("Checking if tmux session exists: {}", session_name);
-
-        let output = Command::new("tmux")
-            .args(["has-session", "-t", session_name])
-            .output()?;
+/// Pastes made by this process, to give each its own tmux buffer
+static PASTES: AtomicU64 = AtomicU64::new(0);
 
-        Ok(output.status.success())
-    }
-
-    pub fn list_sessions(&self) -> Result<Vec<String>> {
-        debug!("Listing tmux sessions");
-
-        let output = Command::new("tmux")
-            .args(["list-sessions", "-F", "#{session_name}"])
-            .output()?;
+pub struct TmuxManager {
+    socket_name: Option<String>,
+}
 
-        if !output.status.success() {
-            // No sessions exist
-            return Ok(vec![]);
+impl TmuxManager {
+    pub fn new() -> Self {
+        Self {
+            socket_name: std::env::var(TMUX_SOCKET_ENV)
+                .ok()
+                .filter(|socket| !socket.is_empty()),
         }
-
-        let sessions = String::from_utf8(output.stdout)?
-            .lines()
-            .map(|line| line.trim().to_string())
-            .filter(|line| !line.is_empty())
-            .collect();
-
-        Ok(sessions)
     }
 
-    pub fn create_session(
-        &self,
-        session_name: &str,
-        working_dir: Option<&PathBuf>,
-        command: Option<&str>,
-    ) -> Result<()> {
-        self.create_session_with_logging(session_name, working_dir, command, true)
+    /// A `tmux` command bound to the configured server socket
+    fn tmux(&self) -> Command {
+        let mut cmd = Command::new("tmux");
+        if let Some(socket_name) = &self.socket_name {
+            cmd.args(["-L", socket_name]);
+        }
+        cmd
     }
 
     pub fn create_session_with_logging(
         &self,
         session_name: &str,
         working_dir: Option<&PathBuf>,
-        command: Option<&str>,
+        command: Option<&LaunchCommand>,
         enable_logging: bool,
     ) -> Result<()> {
         info!("Creating tmux session: {}", session_name);
 
-        // Kill existing session if it exists
         if self.session_exists(session_name)? {
-            warn!("Session {} already exists, killing it first", session_name);
-            self.kill_session(session_name)?;
+            return Err(anyhow!("Session {} already exists", session_name));
         }
 
-        let mut cmd = Command::new("tmux");
+        let mut cmd = self.tmux();
         cmd.args(["new-session", "-d", "-s", session_name]);
 
         if let Some(dir) = working_dir {
@@ -73,7 +57,28 @@ impl TmuxManager {
         }
 
         if let Some(command) = command {
-            cmd.arg(command);
+            debug!("Session command: {}", command);
+            for (key, value) in &command.env {
+                cmd.args(["-e", &tmux_arg(&format!("{key}={value}"))]);
+            }
+            cmd.arg("--");
+            if command.args.is_empty() {
+                // tmux hands a lone argument to the shell, so quote it
+                cmd.arg(tmux_arg(&backend::shell_quote(&command.program)));
+            } else {
+                cmd.args(command.argv().iter().map(|arg| tmux_arg(arg)));
+            }
+            // Keep the pane if the command dies during startup so its error can be read.
+            // Chained in the same invocation so it applies before the command can exit.
+            cmd.args([
+                ";",
+                "set-option",
+                "-w",
+                "-t",
+                session_name,
+                "remain-on-exit",
+                "on",
+            ]);
         }
 
         let output = cmd.output()?;
@@ -88,16 +93,154 @@ impl TmuxManager {
 
         // Enable logging if requested
         if enable_logging {
-            self.enable_session_logging(session_name)?;
+            if let Err(e) = self.enable_session_logging(session_name) {
+                // A command that died immediately is reported by the caller, with its output
+                if !self.command_exited(session_name).unwrap_or(false) {
+                    return Err(e);
+                }
+                warn!(
+                    "Not logging session {}: its command already exited",
+                    session_name
+                );
+            }
         }
 
         Ok(())
     }
 
-    pub fn kill_session(&self, session_name: &str) -> Result<()> {
+    #[allow(dead_code)]
+    pub fn send_enter(&self, session_name: &str) -> Result<()> {
+        debug!("Sending Enter to tmux session: {}", session_name);
+
+        let output = self
+            .tmux()
+            .args(["send-keys", "-t", session_name, "C-m"])
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("Failed to send Enter to tmux session: {}", stderr);
+            return Err(anyhow!("Failed to send Enter to tmux session: {}", stderr));
+        }
+
+        Ok(())
+    }
+
+    /// Paste `text` through a tmux buffer. `paste-buffer -p` wraps it in
+    /// bracketed-paste markers when the application asked for them, so
+    /// embedded newlines are inserted instead of submitting the prompt.
+    fn paste_text(&self, session_name: &str, text: &str) -> Result<()> {
+        // Buffers are global to the tmux server, so concurrent sends must
+        // never share one
+        let buffer = format!(
+            "claude-code-manager-{}-{}-{}",
+            std::process::id(),
+            PASTES.fetch_add(1, Ordering::Relaxed),
+            session_name
+        );
+
+        let mut child = self
+            .tmux()
+            .args(["load-buffer", "-b", &buffer, "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("Failed to load tmux paste buffer: {}", stderr);
+            return Err(anyhow!("Failed to load tmux paste buffer: {}", stderr));
+        }
+
+        let output = self
+            .tmux()
+            .args([
+                "paste-buffer",
+                "-p",
+                "-d",
+                "-b",
+                &buffer,
+                "-t",
+                session_name,
+            ])
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            error!("Failed to paste into tmux session: {}", stderr);
+            return Err(anyhow!("Failed to paste into tmux session: {}", stderr));
+        }
+
+        Ok(())
+    }
+}
+
+/// Escape an argument tmux would otherwise read as a `;` command separator
+fn tmux_arg(arg: &str) -> String {
+    match arg.strip_suffix(';') {
+        Some(rest) => format!("{rest}\\;"),
+        None => arg.to_string(),
+    }
+}
+
+/// Shell command `pipe-pane` runs to append the pane's output to `log_file`
+fn log_pipe_command(log_file: &str) -> String {
+    format!("cat >> {}", backend::shell_quote(log_file))
+}
+
+impl TerminalBackend for TmuxManager {
+    fn session_exists(&self, session_name: &str) -> Result<bool> {
+        debug!("Checking if tmux session exists: {}", session_name);
+
+        let output = self
+            .tmux()
+            .args(["has-session", "-t", session_name])
+            .output()?;
+
+        Ok(output.status.success())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<String>> {
+        debug!("Listing tmux sessions");
+
+        let output = self
+            .tmux()
+            .args(["list-sessions", "-F", "#{session_name}"])
+            .output()?;
+
+        if !output.status.success() {
+            // No sessions exist
+            return Ok(vec![]);
+        }
+
+        let sessions = String::from_utf8(output.stdout)?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        Ok(sessions)
+    }
+
+    fn create_session(
+        &self,
+        session_name: &str,
+        working_dir: Option<&PathBuf>,
+        command: Option<&LaunchCommand>,
+    ) -> Result<()> {
+        self.create_session_with_logging(session_name, working_dir, command, true)
+    }
+
+    fn kill_session(&self, session_name: &str) -> Result<()> {
         debug!("Killing tmux session: {}", session_name);
 
-        let output = Command::new("tmux")
+        let output = self
+            .tmux()
             .args(["kill-session", "-t", session_name])
             .output()?;
 
@@ -119,12 +262,13 @@ impl TmuxManager {
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn send_keys(&self, session_name: &str, keys: &str) -> Result<()> {
+    fn send_keys(&self, session_name: &str, keys: &str) -> Result<()> {
         debug!("Sending keys to tmux session {}: {}", session_name, keys);
 
-        let output = Command::new("tmux")
-            .args(["send-keys", "-t", session_name, keys])
+        // -l sends the text literally; -- stops text starting with '-' being read as a flag
+        let output = self
+            .tmux()
+            .args(["send-keys", "-t", session_name, "-l", "--", keys])
             .output()?;
 
         if !output.status.success() {
@@ -136,44 +280,47 @@ impl TmuxManager {
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn send_enter(&self, session_name: &str) -> Result<()> {
-        debug!("Sending Enter to tmux session: {}", session_name);
+    fn send_special_key(&self, session_name: &str, key: SpecialKey) -> Result<()> {
+        debug!("Sending {:?} to tmux session: {}", key, session_name);
 
-        let output = Command::new("tmux")
-            .args(["send-keys", "-t", session_name, "C-m"])
+        let output = self
+            .tmux()
+            .args(["send-keys", "-t", session_name, key.tmux_name()])
             .output()?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            error!("Failed to send Enter to tmux session: {}", stderr);
-            return Err(anyhow!("Failed to send Enter to tmux session: {}", stderr));
+            error!("Failed to send {:?} to tmux session: {}", key, stderr);
+            return Err(anyhow!(
+                "Failed to send {:?} to tmux session: {}",
+                key,
+                stderr
+            ));
         }
 
         Ok(())
     }
 
-    pub fn send_keys_with_enter(&self, session_name: &str, keys: &str) -> Result<()> {
-        debug!("Sending keys with Enter to tmux session {}: {}", session_name, keys);
-
-        // Send the keys and Enter in a single command to avoid timing issues
-        let output = Command::new("tmux")
-            .args(["send-keys", "-t", session_name, keys, "C-m"])
-            .output()?;
+    fn send_keys_with_enter(&self, session_name: &str, keys: &str) -> Result<()> {
+        debug!(
+            "Sending keys with Enter to tmux session {}: {}",
+            session_name, keys
+        );
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            error!("Failed to send keys with Enter to tmux session: {}", stderr);
-            return Err(anyhow!("Failed to send keys with Enter to tmux session: {}", stderr));
+        if backend::needs_paste(keys) {
+            self.paste_text(session_name, keys)?;
+            std::thread::sleep(backend::PASTE_SETTLE_TIME);
+        } else {
+            self.send_keys(session_name, keys)?;
         }
 
-        Ok(())
+        self.send_special_key(session_name, SpecialKey::Enter)
     }
 
-    pub fn capture_pane(&self, session_name: &str, lines: Option<usize>) -> Result<String> {
+    fn capture_pane(&self, session_name: &str, lines: Option<usize>) -> Result<String> {
         debug!("Capturing pane content from tmux session: {}", session_name);
 
-        let mut cmd = Command::new("tmux");
+        let mut cmd = self.tmux();
         cmd.args(["capture-pane", "-t", session_name, "-p"]);
 
         if let Some(lines) = lines {
@@ -195,10 +342,11 @@ impl TmuxManager {
         Ok(content)
     }
 
-    pub fn attach_session(&self, session_name: &str) -> Result<()> {
+    fn attach_session(&self, session_name: &str) -> Result<()> {
         info!("Attaching to tmux session: {}", session_name);
 
-        let output = Command::new("tmux")
+        let output = self
+            .tmux()
             .args(["attach-session", "-t", session_name])
             .status()?;
 
@@ -213,17 +361,79 @@ impl TmuxManager {
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn get_session_info(&self, session_name: &str) -> Result<SessionInfo> {
+    fn command_exited(&self, session_name: &str) -> Result<bool> {
+        let output = self
+            .tmux()
+            .args(["display-message", "-p", "-t", session_name, "#{pane_dead}"])
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!(
+                "Failed to get pane state for {}: {}",
+                session_name,
+                stderr
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim() == "1")
+    }
+
+    fn pane_rows(&self, session_name: &str) -> Option<u16> {
+        let output = self
+            .tmux()
+            .args([
+                "display-message",
+                "-p",
+                "-t",
+                session_name,
+                "#{pane_height}",
+            ])
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    }
+
+    fn close_on_exit(&self, session_name: &str) -> Result<()> {
+        let output = self
+            .tmux()
+            .args([
+                "set-option",
+                "-w",
+                "-t",
+                session_name,
+                "remain-on-exit",
+                "off",
+            ])
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!(
+                "Failed to reset remain-on-exit for {}: {}",
+                session_name,
+                stderr
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn get_session_info(&self, session_name: &str) -> Result<SessionInfo> {
         debug!("Getting session info for: {}", session_name);
 
-        let output = Command::new("tmux")
+        let output = self.tmux()
             .args([
                 "display-message",
                 "-t",
                 session_name,
                 "-p",
-                "#{session_name}:#{session_created}:#{session_windows}:#{session_attached}",
+                "#{session_name}:#{session_created}:#{session_windows}:#{session_attached}:#{session_path}",
             ])
             .output()?;
 
@@ -234,9 +444,10 @@ impl TmuxManager {
         }
 
         let info_str = String::from_utf8(output.stdout)?;
-        let parts: Vec<&str> = info_str.trim().split(':').collect();
+        // The session path comes last since it may itself contain ':'
+        let parts: Vec<&str> = info_str.trim_end().splitn(5, ':').collect();
 
-        if parts.len() != 4 {
+        if parts.len() != 5 {
             return Err(anyhow!("Unexpected session info format: {}", info_str));
         }
 
@@ -245,27 +456,24 @@ impl TmuxManager {
             created: parts[1].parse().unwrap_or(0),
             windows: parts[2].parse().unwrap_or(0),
             attached: parts[3] == "1",
+            path: PathBuf::from(parts[4]),
         })
     }
 
-    pub fn enable_session_logging(&self, session_name: &str) -> Result<()> {
+    fn enable_session_logging(&self, session_name: &str) -> Result<()> {
         debug!("Enabling logging for tmux session: {}", session_name);
 
-        let log_dir = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        let log_file = format!("{log_dir}/.claude-code-manager/logs/{session_name}.log");
-
-        // Create log directory if it doesn't exist
-        if let Some(parent) = std::path::Path::new(&log_file).parent() {
-            std::fs::create_dir_all(parent)?;
-        }
+        // Every run of the session gets a log of its own
+        let log_file = logs::new_run_log(session_name)?.display().to_string();
 
         // Enable tmux logging for the session
-        let output = Command::new("tmux")
+        let output = self
+            .tmux()
             .args([
                 "pipe-pane",
                 "-t",
                 session_name,
-                &format!("cat >> '{log_file}'"),
+                &log_pipe_command(&log_file),
             ])
             .output()?;
 
@@ -284,46 +492,27 @@ impl TmuxManager {
         );
         Ok(())
     }
-
-    pub fn get_log_file_path(&self, session_name: &str) -> String {
-        let log_dir = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        format!("{log_dir}/.claude-code-manager/logs/{session_name}.log")
-    }
-
-    pub fn read_session_log(&self, session_name: &str, lines: Option<usize>) -> Result<String> {
-        let log_file = self.get_log_file_path(session_name);
-
-        if !std::path::Path::new(&log_file).exists() {
-            debug!("Log file does not exist for session: {}", session_name);
-            // Fall back to capturing current pane content
-            return self.capture_pane(session_name, lines);
-        }
-
-        debug!("Reading log file: {}", log_file);
-
-        if let Some(lines) = lines {
-            // Read only the last N lines
-            let output = Command::new("tail")
-                .args(["-n", &lines.to_string(), &log_file])
-                .output()?;
-
-            if !output.status.success() {
-                return Err(anyhow!("Failed to read log file: {}", log_file));
-            }
-
-            Ok(String::from_utf8(output.stdout)?)
-        } else {
-            // Read entire file
-            Ok(std::fs::read_to_string(&log_file)?)
-        }
-    }
 }
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-pub struct SessionInfo {
-    pub name: String,
-    pub created: u64,
-    pub windows: u32,
-    pub attached: bool,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_paths_with_quotes_are_piped_to_verbatim() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("it's $(touch pwned); a log");
+
+        let mut child = Command::new("sh")
+            .args(["-c", &log_pipe_command(&log.display().to_string())])
+            .current_dir(dir.path())
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"output").unwrap();
+        assert!(child.wait().unwrap().success());
+
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "output");
+        assert!(!dir.path().join("pwned").exists());
+    }
 }
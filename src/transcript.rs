@@ -0,0 +1,468 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Tool result lines shown in the text rendering before it is cut short
+const TOOL_RESULT_PREVIEW_LINES: usize = 10;
+
+/// Longest tool call summary shown in the text rendering
+const TOOL_SUMMARY_CHARS: usize = 80;
+
+/// Something the user typed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UserMessage {
+    pub timestamp: Option<DateTime<Utc>>,
+    pub text: String,
+}
+
+/// Text Claude wrote back
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AssistantMessage {
+    pub timestamp: Option<DateTime<Utc>>,
+    pub model: Option<String>,
+    pub text: String,
+}
+
+/// A tool Claude invoked, with its raw input
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolCall {
+    pub timestamp: Option<DateTime<Utc>>,
+    pub id: String,
+    pub name: String,
+    pub input: Value,
+}
+
+/// What a tool call returned
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolResult {
+    pub timestamp: Option<DateTime<Utc>>,
+    pub tool_use_id: String,
+    pub content: String,
+    pub is_error: bool,
+}
+
+/// One step of a conversation, in transcript order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TranscriptEntry {
+    User(UserMessage),
+    Assistant(AssistantMessage),
+    ToolCall(ToolCall),
+    ToolResult(ToolResult),
+}
+
+impl TranscriptEntry {
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        match self {
+            TranscriptEntry::User(message) => message.timestamp,
+            TranscriptEntry::Assistant(message) => message.timestamp,
+            TranscriptEntry::ToolCall(call) => call.timestamp,
+            TranscriptEntry::ToolResult(result) => result.timestamp,
+        }
+    }
+}
+
+/// A Claude Code conversation read from its JSONL transcript under
+/// `~/.claude/projects/`
+#[derive(Debug, Clone, Serialize)]
+pub struct Transcript {
+    pub conversation_id: String,
+    pub path: PathBuf,
+    pub entries: Vec<TranscriptEntry>,
+}
+
+/// A line of the transcript file. Only the fields needed for the
+/// conversation are read; summaries, snapshots and other bookkeeping
+/// lines are skipped.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawLine {
+    #[serde(rename = "type")]
+    kind: String,
+    timestamp: Option<DateTime<Utc>>,
+    message: Option<RawMessage>,
+    /// Context Claude Code injects, such as system reminders
+    #[serde(default)]
+    is_meta: bool,
+    /// Subagent conversations running inside the main one
+    #[serde(default)]
+    is_sidechain: bool,
+}
+
+#[derive(Deserialize)]
+struct RawMessage {
+    model: Option<String>,
+    content: RawContent,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawContent {
+    Text(String),
+    Blocks(Vec<RawBlock>),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RawBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        #[serde(default)]
+        input: Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        #[serde(default)]
+        content: Value,
+        #[serde(default)]
+        is_error: bool,
+    },
+    /// Thinking, images and anything newer
+    #[serde(other)]
+    Other,
+}
+
+/// Where Claude Code keeps its per-project transcripts
+pub fn projects_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".claude").join("projects")
+}
+
+/// Directory name Claude Code uses for a project: the absolute path with
+/// every character other than a letter or digit replaced by `-`
+pub fn project_dir_name(working_dir: &Path) -> String {
+    working_dir
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// Locate the transcript of a conversation.
+///
+/// With a conversation ID the file is looked up by name, first in the
+/// working directory's project and then in every project. Without one, the
+/// most recently written transcript of the working directory's project is
+/// used, as long as it was written after `since`.
+pub fn find_transcript(
+    projects_dir: &Path,
+    conversation_id: Option<&str>,
+    working_dir: Option<&Path>,
+    since: DateTime<Utc>,
+) -> Option<PathBuf> {
+    let project_dir = working_dir.map(|dir| projects_dir.join(project_dir_name(dir)));
+
+    if let Some(conversation_id) = conversation_id {
+        let file_name = format!("{conversation_id}.jsonl");
+        if let Some(path) = project_dir
+            .as_ref()
+            .map(|dir| dir.join(&file_name))
+            .filter(|path| path.exists())
+        {
+            return Some(path);
+        }
+
+        // The project directory name may be encoded differently; search them all
+        return std::fs::read_dir(projects_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join(&file_name))
+            .find(|path| path.exists());
+    }
+
+    let since = std::time::SystemTime::from(since);
+    std::fs::read_dir(project_dir?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|path| {
+            let modified = path.metadata().ok()?.modified().ok()?;
+            (modified >= since).then_some((modified, path))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+impl Transcript {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read transcript: {}", path.display()))?;
+        debug!("Reading transcript: {}", path.display());
+
+        let conversation_id = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Ok(Self {
+            conversation_id,
+            path: path.to_path_buf(),
+            entries: parse_entries(&content),
+        })
+    }
+
+    /// The conversation as it reads in the terminal, without the screen noise
+    pub fn render_text(&self) -> String {
+        let mut blocks = Vec::new();
+
+        for entry in &self.entries {
+            match entry {
+                TranscriptEntry::User(message) => {
+                    blocks.push(format!("> {}", indent_continuation(&message.text, "  ")))
+                }
+                TranscriptEntry::Assistant(message) => {
+                    blocks.push(format!("⏺ {}", indent_continuation(&message.text, "  ")))
+                }
+                TranscriptEntry::ToolCall(call) => {
+                    blocks.push(format!("⏺ {}", tool_call_summary(call)))
+                }
+                TranscriptEntry::ToolResult(result) => {
+                    let preview = preview_lines(&result.content, TOOL_RESULT_PREVIEW_LINES);
+                    let marker = if result.is_error {
+                        "  ⎿  Error: "
+                    } else {
+                        "  ⎿  "
+                    };
+                    let rendered = format!("{marker}{}", indent_continuation(&preview, "     "));
+                    // Results belong to the call above them
+                    match blocks.last_mut() {
+                        Some(last) => {
+                            last.push('\n');
+                            last.push_str(&rendered);
+                        }
+                        None => blocks.push(rendered),
+                    }
+                }
+            }
+        }
+
+        let mut text = blocks.join("\n\n");
+        text.push('\n');
+        text
+    }
+}
+
+fn parse_entries(content: &str) -> Vec<TranscriptEntry> {
+    let mut entries = Vec::new();
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let raw: RawLine = match serde_json::from_str(line) {
+            Ok(raw) => raw,
+            Err(e) => {
+                warn!("Skipping malformed transcript line: {}", e);
+                continue;
+            }
+        };
+
+        let is_user = match raw.kind.as_str() {
+            "user" => true,
+            "assistant" => false,
+            _ => continue,
+        };
+        if raw.is_meta || raw.is_sidechain {
+            continue;
+        }
+        let Some(message) = raw.message else {
+            continue;
+        };
+
+        let blocks = match message.content {
+            RawContent::Text(text) => vec![RawBlock::Text { text }],
+            RawContent::Blocks(blocks) => blocks,
+        };
+
+        for block in blocks {
+            let timestamp = raw.timestamp;
+            let entry = match block {
+                RawBlock::Text { text } if text.trim().is_empty() => continue,
+                RawBlock::Text { text } if is_user => {
+                    TranscriptEntry::User(UserMessage { timestamp, text })
+                }
+                RawBlock::Text { text } => TranscriptEntry::Assistant(AssistantMessage {
+                    timestamp,
+                    model: message.model.clone(),
+                    text,
+                }),
+                RawBlock::ToolUse { id, name, input } => TranscriptEntry::ToolCall(ToolCall {
+                    timestamp,
+                    id,
+                    name,
+                    input,
+                }),
+                RawBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    is_error,
+                } => TranscriptEntry::ToolResult(ToolResult {
+                    timestamp,
+                    tool_use_id,
+                    content: result_text(&content),
+                    is_error,
+                }),
+                RawBlock::Other => continue,
+            };
+            entries.push(entry);
+        }
+    }
+
+    entries
+}
+
+/// Text of a tool result, which is either a string or a list of content blocks
+fn result_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|block| block["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// `Tool(main argument)`, the way Claude Code shows a tool call
+pub fn tool_call_summary(call: &ToolCall) -> String {
+    const KEY_FIELDS: [&str; 8] = [
+        "command",
+        "file_path",
+        "path",
+        "pattern",
+        "url",
+        "query",
+        "description",
+        "prompt",
+    ];
+
+    let argument = KEY_FIELDS
+        .iter()
+        .find_map(|field| call.input[field].as_str())
+        .map(|argument| {
+            let first_line = argument.lines().next().unwrap_or_default();
+            if first_line.chars().count() > TOOL_SUMMARY_CHARS || argument.lines().count() > 1 {
+                let truncated: String = first_line.chars().take(TOOL_SUMMARY_CHARS).collect();
+                format!("{truncated}…")
+            } else {
+                first_line.to_string()
+            }
+        });
+
+    match argument {
+        Some(argument) => format!("{}({argument})", call.name),
+        None => call.name.clone(),
+    }
+}
+
+fn indent_continuation(text: &str, indent: &str) -> String {
+    text.trim_end().replace('\n', &format!("\n{indent}"))
+}
+
+fn preview_lines(text: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = text.trim_end().lines().collect();
+    if lines.len() <= max_lines {
+        return lines.join("\n");
+    }
+    format!(
+        "{}\n… +{} lines",
+        lines[..max_lines].join("\n"),
+        lines.len() - max_lines
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSCRIPT: &str = r#"{"type":"summary","summary":"Hello script","leafUuid":"a"}
+{"type":"user","timestamp":"2025-06-01T10:00:00Z","isMeta":true,"message":{"role":"user","content":"Caveat: the messages below were generated by the user"}}
+{"type":"user","timestamp":"2025-06-01T10:00:01Z","message":{"role":"user","content":"Create hello.py\nand run it"}}
+{"type":"assistant","timestamp":"2025-06-01T10:00:02Z","message":{"role":"assistant","model":"claude-sonnet-4","content":[{"type":"thinking","thinking":"Simple."},{"type":"text","text":"I'll create the script."}]}}
+{"type":"assistant","timestamp":"2025-06-01T10:00:03Z","message":{"role":"assistant","content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"python hello.py","description":"Run it"}}]}}
+{"type":"user","timestamp":"2025-06-01T10:00:04Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":[{"type":"text","text":"Hello"}]}]}}
+{"type":"assistant","timestamp":"2025-06-01T10:00:05Z","isSidechain":true,"message":{"role":"assistant","content":"subagent chatter"}}
+not json
+{"type":"assistant","timestamp":"2025-06-01T10:00:06Z","message":{"role":"assistant","content":[{"type":"text","text":"Done! It prints \"Hello\"."}]}}
+"#;
+
+    #[test]
+    fn parses_messages_tool_calls_and_results() {
+        let entries = parse_entries(TRANSCRIPT);
+        assert_eq!(entries.len(), 5);
+
+        let TranscriptEntry::User(user) = &entries[0] else {
+            panic!("expected a user message, got {:?}", entries[0]);
+        };
+        assert_eq!(user.text, "Create hello.py\nand run it");
+
+        let TranscriptEntry::Assistant(reply) = &entries[1] else {
+            panic!("expected an assistant message, got {:?}", entries[1]);
+        };
+        assert_eq!(reply.text, "I'll create the script.");
+        assert_eq!(reply.model.as_deref(), Some("claude-sonnet-4"));
+
+        let TranscriptEntry::ToolCall(call) = &entries[2] else {
+            panic!("expected a tool call, got {:?}", entries[2]);
+        };
+        assert_eq!(call.name, "Bash");
+        assert_eq!(tool_call_summary(call), "Bash(python hello.py)");
+
+        let TranscriptEntry::ToolResult(result) = &entries[3] else {
+            panic!("expected a tool result, got {:?}", entries[3]);
+        };
+        assert_eq!(result.tool_use_id, "toolu_1");
+        assert_eq!(result.content, "Hello");
+        assert!(!result.is_error);
+    }
+
+    #[test]
+    fn renders_a_clean_conversation() {
+        let transcript = Transcript {
+            conversation_id: "abc".to_string(),
+            path: PathBuf::from("abc.jsonl"),
+            entries: parse_entries(TRANSCRIPT),
+        };
+        assert_eq!(
+            transcript.render_text(),
+            "> Create hello.py\n  and run it\n\n⏺ I'll create the script.\n\n⏺ Bash(python hello.py)\n  ⎿  Hello\n\n⏺ Done! It prints \"Hello\".\n"
+        );
+    }
+
+    #[test]
+    fn finds_transcripts_by_id_or_recency() {
+        let projects = tempfile::tempdir().unwrap();
+        let working_dir = Path::new("/home/dev/my.app");
+        assert_eq!(project_dir_name(working_dir), "-home-dev-my-app");
+
+        let project = projects.path().join("-home-dev-my-app");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::write(project.join("older.jsonl"), "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(project.join("newer.jsonl"), "").unwrap();
+        let elsewhere = projects.path().join("-other");
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        std::fs::write(elsewhere.join("moved.jsonl"), "").unwrap();
+
+        let long_ago = Utc::now() - chrono::Duration::hours(1);
+        let find = |id, since| find_transcript(projects.path(), id, Some(working_dir), since);
+        assert_eq!(
+            find(Some("older"), long_ago),
+            Some(project.join("older.jsonl"))
+        );
+        assert_eq!(
+            find(Some("moved"), long_ago),
+            Some(elsewhere.join("moved.jsonl"))
+        );
+        assert_eq!(find(None, long_ago), Some(project.join("newer.jsonl")));
+        assert_eq!(find(None, Utc::now() + chrono::Duration::hours(1)), None);
+        assert_eq!(find(Some("missing"), long_ago), None);
+    }
+}
@@ -0,0 +1,1183 @@
+//! End-to-end tests driving the CLI against a private tmux server and the
+//! scripted `fake-claude-code` binary.
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+/// An isolated HOME, a PATH whose `claude-code` is the fake CLI, and a
+/// dedicated tmux socket that is torn down on drop
+struct TestEnv {
+    home: TempDir,
+    bin_dir: TempDir,
+    socket: String,
+    envs: Vec<(String, String)>,
+}
+
+impl TestEnv {
+    /// Returns `None` when tmux is not installed, so the tests are skipped
+    fn new(label: &str) -> Option<Self> {
+        if Command::new("tmux").arg("-V").output().is_err() {
+            eprintln!("tmux not available, skipping");
+            return None;
+        }
+
+        let bin_dir = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(
+            env!("CARGO_BIN_EXE_fake-claude-code"),
+            bin_dir.path().join("claude-code"),
+        )
+        .unwrap();
+
+        Some(Self {
+            home: tempfile::tempdir().unwrap(),
+            bin_dir,
+            socket: format!("ccm-test-{}-{label}", std::process::id()),
+            envs: Vec::new(),
+        })
+    }
+
+    /// Set an environment variable for the fake CLI (only applies until the
+    /// first session starts this env's tmux server)
+    fn env(mut self, key: &str, value: &str) -> Self {
+        self.envs.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    fn session(&self, label: &str) -> String {
+        format!("claude-e2e-{}-{label}", std::process::id())
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let path = format!(
+            "{}:{}",
+            self.bin_dir.path().display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+
+        let mut command = Command::new(env!("CARGO_BIN_EXE_claude-code-manager"));
+        command
+            .args(args)
+            .current_dir(self.home.path())
+            .env("HOME", self.home.path())
+            .env("PATH", path)
+            .env("CLAUDE_CODE_MANAGER_TMUX_SOCKET", &self.socket)
+            .env_remove("TMUX")
+            .envs(self.envs.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// Run with `input` piped to stdin
+    fn run_with_stdin(&self, args: &[&str], input: &str) -> Output {
+        let mut child = self
+            .command(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    fn run_ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        Self::expect_success(args, output)
+    }
+
+    fn expect_success(args: &[&str], output: Output) -> String {
+        assert!(
+            output.status.success(),
+            "command {:?} failed:\nstdout: {}\nstderr: {}",
+            args,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn run_json(&self, args: &[&str]) -> Value {
+        let mut args = args.to_vec();
+        args.extend(["--output", "json"]);
+        serde_json::from_str(&self.run_ok(&args)).unwrap()
+    }
+
+    /// Kill a session behind the manager's back, as a reboot would
+    fn kill_tmux_session(&self, session: &str) {
+        Command::new("tmux")
+            .args(["-L", &self.socket, "kill-session", "-t", session])
+            .output()
+            .unwrap();
+    }
+
+    fn home(&self) -> &Path {
+        self.home.path()
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        let _ = Command::new("tmux")
+            .args(["-L", &self.socket, "kill-server"])
+            .output();
+    }
+}
+
+/// A running `serve` process, killed on drop
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start(env: &TestEnv) -> Self {
+        let mut child = env
+            .command(&["serve", "--port", "0", "--output", "jsonl"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let announced: Value = serde_json::from_str(&line).unwrap();
+        let url = announced["url"].as_str().unwrap();
+        Self {
+            child,
+            addr: url.trim_start_matches("http://").to_string(),
+        }
+    }
+
+    /// Open the event stream at `path`
+    fn events(&self, path: &str) -> EventStream {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_secs(30)))
+            .unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: {}\r\n\r\n", self.addr).unwrap();
+        EventStream(BufReader::new(stream))
+    }
+
+    /// Make a request and return the status code and JSON body
+    fn request(&self, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            self.addr,
+            body.len()
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+}
+
+/// A server-sent event stream
+struct EventStream(BufReader<TcpStream>);
+
+impl EventStream {
+    /// The next event's type and data
+    fn next(&mut self) -> (String, Value) {
+        let mut kind = String::new();
+        loop {
+            let mut line = String::new();
+            assert!(self.0.read_line(&mut line).unwrap() > 0, "stream ended");
+            if let Some(event) = line.strip_prefix("event: ") {
+                kind = event.trim().to_string();
+            } else if let Some(data) = line.strip_prefix("data: ") {
+                return (kind, serde_json::from_str(data).unwrap());
+            }
+        }
+    }
+
+    /// Skip ahead to the next event of type `kind`
+    fn next_of(&mut self, kind: &str) -> Value {
+        loop {
+            let (next, data) = self.next();
+            if next == kind {
+                return data;
+            }
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn send_with_stop_hook_returns_only_the_reply() {
+    let Some(env) = TestEnv::new("hook") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = env.session("hook");
+
+    env.run_ok(&[
+        "start",
+        "-m",
+        "first task",
+        "-s",
+        &session,
+        "--wait",
+        "-t",
+        "20",
+    ]);
+    let report = env.run_json(&["send", "second task", "-s", &session, "-t", "20"]);
+
+    assert_eq!(report["session"], session.as_str());
+    assert_eq!(report["status"], "idle");
+    assert_eq!(report["exit_reason"], "stop_hook");
+    assert_eq!(report["response"], "Echo: second task");
+}
+
+#[test]
+fn send_falls_back_to_heuristic_without_stop_hook() {
+    let Some(env) = TestEnv::new("heuristic") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_REPLY", "Done! {prompt}");
+    let session = env.session("heuristic");
+
+    env.run_ok(&[
+        "start", "-m", "warm up", "-s", &session, "--wait", "-t", "2",
+    ]);
+    let report = env.run_json(&["send", "check it", "-s", &session, "-t", "2"]);
+
+    assert_eq!(report["exit_reason"], "completion_indicator");
+    assert_eq!(report["response"], "Done! check it");
+
+    let full = env.run_json(&["send", "again", "-s", &session, "-t", "2", "--full"]);
+    let pane = full["response"].as_str().unwrap();
+    assert!(pane.contains("? for shortcuts"));
+    assert!(pane.contains("> again"));
+}
+
+#[test]
+fn list_reports_recorded_metadata_and_kill_removes_it() {
+    let Some(env) = TestEnv::new("list") else {
+        return;
+    };
+    let session = env.session("list");
+
+    env.run_ok(&["start", "-m", "inspect the repo", "-s", &session]);
+
+    let sessions = env.run_json(&["list"]);
+    let listed = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["name"] == session.as_str())
+        .expect("session should be listed");
+    assert_eq!(listed["status"], "active");
+    assert_eq!(listed["initial_message"], "inspect the repo");
+    assert_eq!(listed["permission_mode"], "default");
+    assert_eq!(
+        PathBuf::from(listed["working_dir"].as_str().unwrap()),
+        env.home().to_path_buf()
+    );
+
+    env.run_ok(&["kill", "-s", &session]);
+    let sessions = env.run_json(&["list"]);
+    assert!(sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|s| s["name"] != session.as_str()));
+}
+
+#[test]
+fn export_writes_the_session_log() {
+    let Some(env) = TestEnv::new("export") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = env.session("export");
+
+    env.run_ok(&[
+        "start",
+        "-m",
+        "write docs",
+        "-s",
+        &session,
+        "--wait",
+        "-t",
+        "20",
+    ]);
+    env.run_ok(&["send", "summarize", "-s", &session, "-t", "20"]);
+
+    let export_path = env.home().join("exports").join("session.txt");
+    env.run_ok(&[
+        "export",
+        "-s",
+        &session,
+        "-o",
+        export_path.to_str().unwrap(),
+    ]);
+
+    let exported = std::fs::read_to_string(&export_path).unwrap();
+    assert!(exported.contains("> summarize"));
+    assert!(exported.contains("Echo: summarize"));
+
+    let markdown_path = env.home().join("exports").join("session.md");
+    env.run_ok(&[
+        "export",
+        "-s",
+        &session,
+        "-o",
+        markdown_path.to_str().unwrap(),
+    ]);
+    let markdown = std::fs::read_to_string(&markdown_path).unwrap();
+    assert!(markdown.starts_with(&format!("# Claude Code session: {session}\n")));
+    assert!(markdown.contains("## User · "));
+    assert!(markdown.contains("## Claude · "));
+    assert!(markdown.contains("\nEcho: summarize\n"));
+
+    let json_path = env.home().join("exports").join("session.out");
+    env.run_ok(&[
+        "export",
+        "-s",
+        &session,
+        "-o",
+        json_path.to_str().unwrap(),
+        "--format",
+        "json",
+    ]);
+    let document: Value =
+        serde_json::from_str(&std::fs::read_to_string(&json_path).unwrap()).unwrap();
+    assert_eq!(document["entries"][3]["text"], "Echo: summarize");
+
+    // The terminal log, replayed so the spinner redraws and escapes are gone
+    let clean_path = env.home().join("exports").join("terminal.txt");
+    env.run_ok(&[
+        "export",
+        "-s",
+        &session,
+        "-o",
+        clean_path.to_str().unwrap(),
+        "--raw",
+        "--clean",
+    ]);
+    let clean = std::fs::read_to_string(&clean_path).unwrap();
+    assert!(clean.contains("> summarize\n"));
+    assert!(clean.contains("⏺ Echo: summarize\n"));
+    assert!(!clean.contains("Thinking"));
+    assert!(!clean.contains('\x1b'));
+
+    // `--output <file>`, the flag's name before `-o/--output-file`
+    let legacy_path = env.home().join("exports").join("legacy.txt");
+    env.run_ok(&[
+        "export",
+        "-s",
+        &session,
+        "--output",
+        legacy_path.to_str().unwrap(),
+    ]);
+    let legacy = std::fs::read_to_string(&legacy_path).unwrap();
+    assert!(legacy.contains("Echo: summarize"));
+}
+
+#[test]
+fn send_delivers_special_characters_and_multi_line_text_exactly() {
+    let Some(env) = TestEnv::new("paste") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = env.session("paste");
+
+    env.run_ok(&[
+        "start", "-m", "warm up", "-s", &session, "--wait", "-t", "20",
+    ]);
+
+    let report = env.run_json(&["send", "-x Enter C-c", "-s", &session, "-t", "20"]);
+    assert_eq!(report["response"], "Echo: -x Enter C-c");
+
+    let message = "fn main() {\n    println!(\"$HOME `pwd`\");\n}";
+    let args = ["send", "-", "-s", &session, "-t", "20", "--output", "json"];
+    let output = env.run_with_stdin(&args, &format!("{message}\n"));
+    let report: Value = serde_json::from_str(&TestEnv::expect_success(&args, output)).unwrap();
+    assert_eq!(report["response"], format!("Echo: {message}").as_str());
+
+    let prompt_file = env.home().join("prompt.md");
+    std::fs::write(&prompt_file, "# Spec\n\n- first\n- second\n").unwrap();
+    let report = env.run_json(&[
+        "send",
+        "--file",
+        prompt_file.to_str().unwrap(),
+        "-s",
+        &session,
+        "-t",
+        "20",
+    ]);
+    assert_eq!(report["response"], "Echo: # Spec\n\n- first\n- second");
+}
+
+#[test]
+fn start_waits_for_a_slow_cli_and_reports_startup_failures() {
+    let Some(env) = TestEnv::new("startup") else {
+        return;
+    };
+    let slow = env
+        .env("FAKE_CLAUDE_STARTUP_DELAY_MS", "1500")
+        .env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = slow.session("slow");
+
+    slow.run_ok(&[
+        "start",
+        "-m",
+        "first message",
+        "-s",
+        &session,
+        "--wait",
+        "-t",
+        "20",
+    ]);
+    let history = slow.run_ok(&["status", "-s", &session]);
+    assert!(history.contains("Echo: first message"));
+
+    // A fresh tmux server, since sessions inherit the server's environment
+    let Some(failing) = TestEnv::new("startup-fail") else {
+        return;
+    };
+    let failing = failing.env("FAKE_CLAUDE_EXIT_ON_START", "Invalid API key");
+    let session = failing.session("failing");
+    let output = failing.run(&["start", "-m", "hello", "-s", &session]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("exited during startup"), "{stderr}");
+    assert!(stderr.contains("Invalid API key"), "{stderr}");
+
+    let sessions = failing.run_json(&["list"]);
+    assert!(sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|s| s["name"] != session.as_str()));
+}
+
+#[test]
+fn start_passes_binary_arguments_and_environment_without_a_shell() {
+    let Some(env) = TestEnv::new("launch") else {
+        return;
+    };
+    let session = env.session("launch");
+
+    let report = env.run_json(&[
+        "start",
+        "-m",
+        "hi",
+        "-s",
+        &session,
+        "--claude-binary",
+        env!("CARGO_BIN_EXE_fake-claude-code"),
+        "--claude-arg",
+        "--model",
+        "--claude-arg",
+        "opus 4; $(rm -rf x)",
+        "--claude-arg",
+        ";",
+        "--env",
+        "FAKE_CLAUDE_REPLY=Args: {args}",
+        "--env",
+        "FAKE_CLAUDE_STOP_HOOK=1",
+        "--wait",
+        "-t",
+        "20",
+    ]);
+    assert_eq!(
+        report["response"],
+        "Args: [--model] [opus 4; $(rm -rf x)] [;]"
+    );
+
+    let sessions = env.run_json(&["list"]);
+    let listed = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["name"] == session.as_str())
+        .expect("session should be listed");
+    assert_eq!(listed["command"][1], "--model");
+}
+
+#[test]
+fn each_run_of_a_session_gets_its_own_history() {
+    let Some(env) = TestEnv::new("runs") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = env.session("runs");
+
+    env.run_ok(&[
+        "start",
+        "-m",
+        "first task",
+        "-s",
+        &session,
+        "--wait",
+        "-t",
+        "20",
+    ]);
+
+    // Starting over a running session is refused instead of replacing it
+    let output = env.run(&["start", "-m", "again", "-s", &session]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+
+    env.run_ok(&["kill", "-s", &session, "--forget"]);
+    env.run_ok(&[
+        "start",
+        "-m",
+        "second task",
+        "-s",
+        &session,
+        "--wait",
+        "-t",
+        "20",
+    ]);
+
+    let current = env.run_json(&["history", "-s", &session]);
+    let current = current["history"].as_str().unwrap();
+    assert!(current.contains("second task"));
+    assert!(!current.contains("first task"));
+
+    let first = env.run_json(&["history", "-s", &session, "--run", "1"]);
+    assert_eq!(first["run"], 1);
+    assert_eq!(first["source"], "transcript");
+    assert!(first["history"]
+        .as_str()
+        .unwrap()
+        .contains("Echo: first task"));
+
+    let first_log = env.run_json(&["history", "-s", &session, "--run", "1", "--raw"]);
+    assert_eq!(first_log["source"], "log");
+    let first_log = first_log["history"].as_str().unwrap();
+    assert!(first_log.contains("> first task"));
+    assert!(!first_log.contains("second task"));
+
+    let runs = env.run_json(&["history", "-s", &session, "--all-runs"]);
+    let runs = runs.as_array().unwrap();
+    assert_eq!(runs.len(), 2);
+    assert!(runs[1]["history"].as_str().unwrap().contains("second task"));
+
+    assert!(!env
+        .run(&["history", "-s", &session, "--run", "3"])
+        .status
+        .success());
+}
+
+#[test]
+fn search_finds_messages_across_sessions() {
+    let Some(env) = TestEnv::new("search") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let first = env.session("search-a");
+    let second = env.session("search-b");
+
+    for (session, message) in [
+        (&first, "look at parser.rs"),
+        (&second, "update the README"),
+    ] {
+        env.run_ok(&["start", "-m", message, "-s", session, "--wait", "-t", "20"]);
+    }
+
+    let hits = env.run_json(&["search", "PARSER\\.rs", "-i", "-C", "0"]);
+    let hits = hits.as_array().unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0]["session"], first.as_str());
+    assert_eq!(hits[0]["source"], "transcript");
+    assert_eq!(hits[0]["lines"][0]["text"], "> look at parser.rs");
+    assert!(hits[0]["timestamp"].is_string());
+
+    let hits = env.run_json(&["search", "Echo: update", "--raw"]);
+    let hits = hits.as_array().unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0]["session"], second.as_str());
+    assert_eq!(hits[0]["source"], "log");
+
+    let hits = env.run_json(&["search", "README", "-s", &first]);
+    assert!(hits.as_array().unwrap().is_empty());
+    // The prompt and the reply are close enough to share one hit
+    let hits = env.run_json(&["search", "README", "--since", "1h"]);
+    assert_eq!(hits.as_array().unwrap().len(), 1);
+    let matched = hits[0]["lines"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|line| line["matched"] == true)
+        .count();
+    assert_eq!(matched, 2);
+    let hits = env.run_json(&["search", "README", "--until", "2000-01-01"]);
+    assert!(hits.as_array().unwrap().is_empty());
+}
+
+#[test]
+fn revive_resumes_the_conversation_of_an_ended_session() {
+    let Some(env) = TestEnv::new("revive") else {
+        return;
+    };
+    let env = env
+        .env("FAKE_CLAUDE_STOP_HOOK", "1")
+        .env("FAKE_CLAUDE_REPLY", "Args:{args} Echo: {prompt}");
+    let session = env.session("revive");
+
+    env.run_ok(&[
+        "start",
+        "-m",
+        "remember 42",
+        "-s",
+        &session,
+        "--wait",
+        "-t",
+        "20",
+    ]);
+    let find = |sessions: &Value| {
+        sessions
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["name"] == session.as_str())
+            .cloned()
+    };
+    let listed = find(&env.run_json(&["list"])).expect("session should be listed");
+    let conversation_id = listed["conversation_id"].as_str().unwrap().to_string();
+    assert!(conversation_id.starts_with("fake-conversation-"));
+
+    env.kill_tmux_session(&session);
+    let listed = find(&env.run_json(&["list"])).expect("ended session should be kept");
+    assert_eq!(listed["status"], "ended");
+
+    let report = env.run_json(&[
+        "revive",
+        "-s",
+        &session,
+        "-m",
+        "what was it?",
+        "--wait",
+        "-t",
+        "20",
+    ]);
+    assert_eq!(
+        report["response"],
+        format!("Args:[--resume] [{conversation_id}] Echo: what was it?").as_str()
+    );
+    let listed = find(&env.run_json(&["list"])).unwrap();
+    assert_eq!(listed["status"], "active");
+
+    // kill keeps a revivable record until it is forgotten
+    env.run_ok(&["kill", "-s", &session]);
+    let listed = find(&env.run_json(&["list"])).unwrap();
+    assert_eq!(listed["status"], "ended");
+    env.run_ok(&["kill", "-s", &session, "--forget"]);
+    assert!(find(&env.run_json(&["list"])).is_none());
+}
+
+#[test]
+fn history_reads_the_transcript_even_after_the_session_ended() {
+    let Some(env) = TestEnv::new("transcript") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = env.session("transcript");
+
+    env.run_ok(&[
+        "start",
+        "-m",
+        "plan the release",
+        "-s",
+        &session,
+        "--wait",
+        "-t",
+        "20",
+    ]);
+    env.run_ok(&["send", "line one\nline two", "-s", &session, "-t", "20"]);
+
+    let history = env.run_json(&["history", "-s", &session]);
+    assert_eq!(history["source"], "transcript");
+    assert_eq!(
+        history["history"],
+        "> plan the release\n\n⏺ Echo: plan the release\n\n> line one\n  line two\n\n⏺ Echo: line one\n  line two\n"
+    );
+    assert_eq!(history["entries"][0]["type"], "user");
+    assert_eq!(history["entries"][1]["text"], "Echo: plan the release");
+
+    env.kill_tmux_session(&session);
+    let history = env.run_ok(&["history", "-s", &session, "-l", "2"]);
+    assert!(
+        history.ends_with("⏺ Echo: line one\n  line two\n"),
+        "{history}"
+    );
+
+    let raw = env.run(&["history", "-s", &session, "--raw"]);
+    assert!(!raw.status.success());
+}
+
+#[test]
+fn serve_exposes_sessions_over_http() {
+    let Some(env) = TestEnv::new("serve") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = env.session("serve");
+    let server = Server::start(&env);
+
+    let (code, report) = server.request(
+        "POST",
+        "/sessions",
+        Some(serde_json::json!({
+            "message": "first task",
+            "session": session,
+            "wait": true,
+            "timeout": 20,
+        })),
+    );
+    assert_eq!(code, 200, "{report}");
+    assert_eq!(report["session"], session.as_str());
+    assert_eq!(report["exit_reason"], "stop_hook");
+
+    let (code, _) = server.request(
+        "POST",
+        "/sessions",
+        Some(serde_json::json!({ "message": "again", "session": session })),
+    );
+    assert_eq!(code, 409);
+
+    let (code, report) = server.request(
+        "POST",
+        &format!("/sessions/{session}/messages"),
+        Some(serde_json::json!({ "message": "second task", "timeout": 20 })),
+    );
+    assert_eq!(code, 200, "{report}");
+    assert_eq!(report["status"], "idle");
+    assert_eq!(report["response"], "Echo: second task");
+
+    let (code, status) = server.request("GET", &format!("/sessions/{session}?lines=10"), None);
+    assert_eq!(code, 200);
+    assert_eq!(status["lines"], 10);
+    assert!(status["output"]
+        .as_str()
+        .unwrap()
+        .contains("Echo: second task"));
+
+    let (code, history) = server.request("GET", &format!("/sessions/{session}/history"), None);
+    assert_eq!(code, 200);
+    assert_eq!(history["source"], "transcript");
+    assert_eq!(history["entries"][3]["text"], "Echo: second task");
+
+    let (code, sessions) = server.request("GET", "/sessions", None);
+    assert_eq!(code, 200);
+    assert!(sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|listed| listed["name"] == session.as_str()));
+
+    let (code, killed) = server.request("DELETE", &format!("/sessions/{session}"), None);
+    assert_eq!(code, 200);
+    assert_eq!(killed["killed"], true);
+
+    let (code, error) = server.request("GET", &format!("/sessions/{session}"), None);
+    assert_eq!(code, 404);
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .contains("Session not found"));
+}
+
+#[test]
+fn serve_runs_concurrent_messages_to_a_session_one_after_another() {
+    let Some(env) = TestEnv::new("serve-concurrent") else {
+        return;
+    };
+    let env = env
+        .env("FAKE_CLAUDE_STOP_HOOK", "1")
+        .env("FAKE_CLAUDE_DELAY_MS", "1500");
+    let session = env.session("serve-concurrent");
+    let server = Server::start(&env);
+
+    let (code, _) = server.request(
+        "POST",
+        "/sessions",
+        Some(serde_json::json!({ "message": "warm up", "session": session, "wait": true, "timeout": 20 })),
+    );
+    assert_eq!(code, 200);
+
+    // Each reply belongs to its own request, so neither typed into a busy pane
+    let path = format!("/sessions/{session}/messages");
+    let reports: Vec<(u16, Value)> = std::thread::scope(|scope| {
+        let requests: Vec<_> = ["first task", "second task"]
+            .into_iter()
+            .map(|message| {
+                let (server, path) = (&server, &path);
+                scope.spawn(move || {
+                    server.request(
+                        "POST",
+                        path,
+                        Some(serde_json::json!({ "message": message, "timeout": 20 })),
+                    )
+                })
+            })
+            .collect();
+        requests.into_iter().map(|r| r.join().unwrap()).collect()
+    });
+    for ((code, report), message) in reports.iter().zip(["first task", "second task"]) {
+        assert_eq!(*code, 200, "{report}");
+        assert_eq!(report["exit_reason"], "stop_hook", "{report}");
+        assert_eq!(report["response"], format!("Echo: {message}"), "{report}");
+    }
+}
+
+#[test]
+fn serve_streams_output_state_and_completion_events() {
+    let Some(env) = TestEnv::new("events") else {
+        return;
+    };
+    let env = env
+        .env("FAKE_CLAUDE_STOP_HOOK", "1")
+        .env("FAKE_CLAUDE_DELAY_MS", "1500");
+    let session = env.session("events");
+    let server = Server::start(&env);
+
+    let (code, _) = server.request(
+        "POST",
+        "/sessions",
+        Some(serde_json::json!({ "message": "warm up", "session": session, "wait": true, "timeout": 20 })),
+    );
+    assert_eq!(code, 200);
+
+    let mut events = server.events(&format!("/sessions/{session}/events"));
+    let output = events.next_of("output");
+    assert_eq!(output["from"], 0);
+    assert!(output["lines"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|line| line == "⏺ Echo: warm up"));
+    assert_eq!(events.next_of("state")["state"], "idle");
+
+    let (code, report) = server.request(
+        "POST",
+        &format!("/sessions/{session}/messages"),
+        Some(serde_json::json!({ "message": "stream this", "wait": false })),
+    );
+    assert_eq!(code, 200);
+    assert_eq!(report["exit_reason"], "not_waited");
+
+    assert_eq!(events.next_of("state")["state"], "working");
+    assert_eq!(events.next_of("state")["state"], "idle");
+    let completed = events.next_of("completed");
+    assert_eq!(completed["response"], "Echo: stream this");
+
+    env.kill_tmux_session(&session);
+    assert_eq!(events.next_of("state")["state"], "exited");
+}
+
+#[test]
+fn mcp_exposes_sessions_as_tools() {
+    let Some(env) = TestEnv::new("mcp") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = env.session("mcp");
+
+    // Responses to concurrent tool calls can come back in any order
+    let call = |requests: &[Value]| -> Vec<Value> {
+        let input: String = requests.iter().map(|r| format!("{r}\n")).collect();
+        let output = env.run_with_stdin(&["mcp"], &input);
+        let stdout = TestEnv::expect_success(&["mcp"], output);
+        let mut responses: Vec<Value> = stdout
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        responses.sort_by_key(|response| response["id"].as_i64());
+        responses
+    };
+    let tool = |id: i64, name: &str, arguments: Value| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments },
+        })
+    };
+    let document = |response: &Value| -> Value {
+        assert_eq!(response["result"]["isError"], false, "{response}");
+        serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap()
+    };
+
+    let responses = call(&[
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": { "protocolVersion": "2025-06-18", "capabilities": {} },
+        }),
+        serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+        tool(
+            3,
+            "start_session",
+            serde_json::json!({ "session": session, "message": "first task", "wait": true, "timeout": 20 }),
+        ),
+    ]);
+    assert_eq!(responses.len(), 3);
+    assert_eq!(
+        responses[0]["result"]["serverInfo"]["name"],
+        "claude-code-manager"
+    );
+    assert_eq!(responses[1]["result"]["tools"].as_array().unwrap().len(), 6);
+    assert_eq!(document(&responses[2])["response"], "Echo: first task");
+
+    let responses = call(&[
+        tool(
+            1,
+            "send_message",
+            serde_json::json!({ "session": session, "message": "second task", "timeout": 20 }),
+        ),
+        tool(2, "list_sessions", serde_json::json!({})),
+        tool(
+            3,
+            "get_session_status",
+            serde_json::json!({ "session": "no-such-session" }),
+        ),
+    ]);
+    assert_eq!(document(&responses[0])["response"], "Echo: second task");
+    assert!(document(&responses[1])
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|listed| listed["name"] == session.as_str()));
+    assert_eq!(responses[2]["result"]["isError"], true);
+
+    let responses = call(&[tool(
+        1,
+        "kill_session",
+        serde_json::json!({ "session": session }),
+    )]);
+    assert_eq!(document(&responses[0])["killed"], true);
+}
+
+#[test]
+fn queued_messages_are_sent_one_turn_at_a_time() {
+    let Some(env) = TestEnv::new("queue") else {
+        return;
+    };
+    let env = env
+        .env("FAKE_CLAUDE_STOP_HOOK", "1")
+        .env("FAKE_CLAUDE_DELAY_MS", "800");
+    let session = env.session("queue");
+
+    env.run_ok(&[
+        "start", "-m", "warm up", "-s", &session, "--wait", "-t", "20",
+    ]);
+    let added = env.run_json(&[
+        "queue",
+        "add",
+        "implement X",
+        "write tests",
+        "run lint and fix",
+        "-s",
+        &session,
+        "-t",
+        "20",
+    ]);
+    assert_eq!(added.as_array().unwrap().len(), 3);
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(60);
+    let queue = loop {
+        let queue = env.run_json(&["queue", "list", "-s", &session]);
+        if queue
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|message| message["status"] == "done")
+        {
+            break queue;
+        }
+        assert!(std::time::Instant::now() < deadline, "{queue}");
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    };
+
+    let queue = queue.as_array().unwrap();
+    let responses: Vec<&str> = queue
+        .iter()
+        .map(|message| message["response"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        responses,
+        [
+            "Echo: implement X",
+            "Echo: write tests",
+            "Echo: run lint and fix"
+        ]
+    );
+    // Each message went out only after the one before had finished
+    let time =
+        |value: &Value| chrono::DateTime::parse_from_rfc3339(value.as_str().unwrap()).unwrap();
+    for pair in queue.windows(2) {
+        assert!(time(&pair[1]["started_at"]) >= time(&pair[0]["finished_at"]));
+    }
+
+    let cleared = env.run_json(&["queue", "clear", "-s", &session]);
+    assert_eq!(cleared["cleared"], 3);
+    assert_eq!(
+        env.run_json(&["queue", "list", "-s", &session]),
+        Value::Array(Vec::new())
+    );
+}
+
+/// Stops the env's daemon on drop
+struct DaemonGuard<'a>(&'a TestEnv);
+
+impl Drop for DaemonGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.run(&["daemon", "stop"]);
+    }
+}
+
+#[test]
+fn daemon_records_completions_and_notices_claude_exiting() {
+    let Some(env) = TestEnv::new("daemon") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = env.session("daemon");
+
+    let started = env.run_json(&["daemon", "start"]);
+    let _daemon = DaemonGuard(&env);
+    assert_eq!(started["pid"], env.run_json(&["daemon", "start"])["pid"]);
+
+    env.run_ok(&["start", "-m", "hello", "-s", &session, "--wait", "-t", "20"]);
+
+    let wait_for = |event: &str, response: Option<&str>| {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+        loop {
+            let events = env.run_json(&["daemon", "events", "-s", &session, "-n", "100"]);
+            if events
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|recorded| {
+                    recorded["event"] == event
+                        && response.is_none_or(|response| recorded["response"] == response)
+                })
+                .is_some()
+            {
+                return;
+            }
+            assert!(std::time::Instant::now() < deadline, "{events}");
+            std::thread::sleep(std::time::Duration::from_millis(300));
+        }
+    };
+    wait_for("discovered", None);
+
+    env.run_ok(&["send", "second", "-s", &session, "-t", "20"]);
+    wait_for("completed", Some("Echo: second"));
+
+    let status = env.run_json(&["daemon", "status"]);
+    let tracked = status["sessions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|tracked| tracked["session"] == session.as_str())
+        .unwrap();
+    assert!(tracked["last_completed_at"].is_string(), "{status}");
+
+    env.run_ok(&["send", "/exit", "-s", &session, "--no-wait"]);
+    wait_for("exited", None);
+
+    let stopped = env.run_json(&["daemon", "stop"]);
+    assert_eq!(stopped["stopped"], true);
+    assert_eq!(env.run_json(&["daemon", "status"])["running"], false);
+}
+
+#[test]
+fn fanout_starts_directory_sessions_and_reports_each_one() {
+    let Some(env) = TestEnv::new("fanout") else {
+        return;
+    };
+    let env = env.env("FAKE_CLAUDE_STOP_HOOK", "1");
+    let session = env.session("fanout");
+    for service in ["svc-api", "svc-web", "docs"] {
+        std::fs::create_dir(env.home().join(service)).unwrap();
+    }
+    env.run_ok(&[
+        "start", "-m", "warm up", "-s", &session, "--wait", "-t", "20",
+    ]);
+
+    let pattern = format!("{}/svc-*", env.home().display());
+    let output = env.run(&[
+        "fanout",
+        "migrate",
+        "--dirs",
+        &pattern,
+        "--sessions",
+        &format!("{session},missing-session"),
+        "-j",
+        "2",
+        "-t",
+        "30",
+        "--output",
+        "jsonl",
+    ]);
+    assert!(!output.status.success());
+    let results: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    let by_session = |name: &str| {
+        results
+            .iter()
+            .find(|result| result["session"] == name)
+            .unwrap_or_else(|| panic!("no result for {name}: {results:?}"))
+    };
+    assert_eq!(results.len(), 4);
+    assert_eq!(by_session(&session)["response"], "Echo: migrate");
+    assert_eq!(by_session(&session)["started"], false);
+    assert_eq!(by_session("missing-session")["status"], "failed");
+    assert_eq!(by_session("missing-session")["exit_code"], 1);
+
+    let started: Vec<&Value> = results
+        .iter()
+        .filter(|result| result["started"] == true)
+        .collect();
+    assert_eq!(started.len(), 2);
+    for result in started {
+        assert_eq!(result["status"], "done", "{result}");
+        assert_eq!(result["exit_code"], 0);
+        assert_eq!(result["response"], "Echo: migrate");
+        let dir = result["working_dir"].as_str().unwrap();
+        assert!(dir.contains("svc-"), "{dir}");
+    }
+
+    // The directory sessions are now there to send follow-ups to
+    let list = env.run_json(&["list"]);
+    assert_eq!(list.as_array().unwrap().len(), 3, "{list}");
+}
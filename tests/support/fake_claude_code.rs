@@ -0,0 +1,195 @@
+//! Scripted stand-in for the `claude-code` CLI, used by the integration tests.
+//!
+//! Prints a banner and an input box, then for every line read from stdin echoes
+//! the prompt, shows a "Thinking…" status line and replies. Like the real CLI
+//! it enables bracketed paste, so a pasted multi-line prompt arrives as one
+//! message, and appends each exchange to a JSONL transcript under
+//! `~/.claude/projects/`. Behaviour is
+//! controlled through environment variables:
+//!
+//! - `FAKE_CLAUDE_REPLY`: reply text, `{prompt}` is replaced with the prompt
+//!   and `{args}` with the command-line arguments, each in brackets
+//!   (default: `Echo: {prompt}`)
+//! - `FAKE_CLAUDE_DELAY_MS`: how long to "think" before replying (default: 300)
+//! - `FAKE_CLAUDE_STARTUP_DELAY_MS`: how long to take before showing the input
+//!   box (default: 0)
+//! - `FAKE_CLAUDE_EXIT_ON_START`: print this message and exit with status 1
+//!   instead of starting, like the real CLI does without credentials
+//! - `FAKE_CLAUDE_STOP_HOOK`: when set, record a Stop hook event after each
+//!   reply, like the hook installed by `claude-code-manager hooks install`.
+//!   The event's conversation ID is the one passed with `--resume`, or a new
+//!   one derived from the process ID
+
+use std::io::{self, BufRead, Write};
+use std::process::Command;
+use std::time::Duration;
+
+const BOX_WIDTH: usize = 50;
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+fn main() {
+    let reply_template =
+        std::env::var("FAKE_CLAUDE_REPLY").unwrap_or_else(|_| "Echo: {prompt}".to_string());
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .map(|arg| format!("[{arg}]"))
+        .collect();
+    let reply_template = reply_template.replace("{args}", &args.join(" "));
+    let conversation_id = std::env::args()
+        .skip_while(|arg| arg != "--resume")
+        .nth(1)
+        .unwrap_or_else(|| format!("fake-conversation-{}", std::process::id()));
+    let delay = std::env::var("FAKE_CLAUDE_DELAY_MS")
+        .ok()
+        .and_then(|ms| ms.parse().ok())
+        .unwrap_or(300);
+    let stop_hook = std::env::var_os("FAKE_CLAUDE_STOP_HOOK").is_some();
+
+    if let Ok(message) = std::env::var("FAKE_CLAUDE_EXIT_ON_START") {
+        eprintln!("{message}");
+        std::process::exit(1);
+    }
+
+    let startup_delay = std::env::var("FAKE_CLAUDE_STARTUP_DELAY_MS")
+        .ok()
+        .and_then(|ms| ms.parse().ok())
+        .unwrap_or(0);
+    std::thread::sleep(Duration::from_millis(startup_delay));
+
+    let mut stdout = io::stdout();
+    // Ask the terminal to bracket pastes
+    print!("\x1b[?2004h");
+    print_box(&mut stdout, "✻ Welcome to Claude Code!");
+    println!();
+    print_box(&mut stdout, ">");
+    println!("  ? for shortcuts");
+    stdout.flush().unwrap();
+
+    let mut lines = io::stdin().lock().lines();
+    while let Some(Ok(line)) = lines.next() {
+        let prompt = if let Some(pasted) = line.strip_prefix(PASTE_START) {
+            // Newlines inside a paste are part of the prompt, not a submit
+            let mut prompt = pasted.to_string();
+            while !prompt.contains(PASTE_END) {
+                let Some(Ok(next)) = lines.next() else { break };
+                prompt.push('\n');
+                prompt.push_str(&next);
+            }
+            prompt.replace(PASTE_END, "")
+        } else {
+            line
+        };
+
+        if prompt == "/exit" {
+            return;
+        }
+
+        println!("> {}", prompt.replace('\n', "\n  "));
+        println!();
+        print!("✻ Thinking… (esc to interrupt)");
+        stdout.flush().unwrap();
+
+        std::thread::sleep(Duration::from_millis(delay));
+
+        // Erase the status line the way the real TUI does
+        print!("\r\x1b[2K");
+        let reply = reply_template.replace("{prompt}", &prompt);
+        record_transcript(&conversation_id, &prompt, &reply);
+        for (index, reply_line) in reply.lines().enumerate() {
+            if index == 0 {
+                println!("⏺ {reply_line}");
+            } else {
+                println!("  {reply_line}");
+            }
+        }
+        println!();
+        print_box(&mut stdout, ">");
+        println!("  ? for shortcuts");
+        stdout.flush().unwrap();
+
+        if stop_hook {
+            record_stop_event(&conversation_id);
+        }
+    }
+}
+
+fn print_box(stdout: &mut io::Stdout, content: &str) {
+    let border = "─".repeat(BOX_WIDTH);
+    let padding = BOX_WIDTH.saturating_sub(content.chars().count() + 1);
+    writeln!(stdout, "╭{border}╮").unwrap();
+    writeln!(stdout, "│ {content}{}│", " ".repeat(padding)).unwrap();
+    writeln!(stdout, "╰{border}╯").unwrap();
+}
+
+/// Append the exchange to the transcript, like the real CLI does
+fn record_transcript(conversation_id: &str, prompt: &str, reply: &str) {
+    let (Some(home), Ok(cwd)) = (std::env::var_os("HOME"), std::env::current_dir()) else {
+        return;
+    };
+    let project: String = cwd
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let dir = std::path::Path::new(&home)
+        .join(".claude")
+        .join("projects")
+        .join(project);
+    let _ = std::fs::create_dir_all(&dir);
+
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let lines = [
+        serde_json::json!({
+            "type": "user",
+            "sessionId": conversation_id,
+            "timestamp": timestamp,
+            "message": { "role": "user", "content": prompt },
+        }),
+        serde_json::json!({
+            "type": "assistant",
+            "sessionId": conversation_id,
+            "timestamp": timestamp,
+            "message": {
+                "role": "assistant",
+                "content": [{ "type": "text", "text": reply }],
+            },
+        }),
+    ];
+    if let Ok(mut file) = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(format!("{conversation_id}.jsonl")))
+    {
+        for line in lines {
+            let _ = writeln!(file, "{line}");
+        }
+    }
+}
+
+fn record_stop_event(conversation_id: &str) {
+    let session = Command::new("tmux")
+        .args(["display-message", "-p", "#{session_name}"])
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let marker_dir = std::path::Path::new("/tmp/claude-code-manager");
+    let _ = std::fs::create_dir_all(marker_dir);
+    let event = serde_json::json!({
+        "session": session,
+        "hook": "Stop",
+        "claude_session_id": conversation_id,
+        "stop_reason": "completed",
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+    if let Ok(mut file) = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(marker_dir.join(format!("{session}.events.jsonl")))
+    {
+        let _ = writeln!(file, "{event}");
+    }
+}